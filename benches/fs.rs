@@ -1,7 +1,7 @@
 use criterion::{criterion_group, criterion_main, Criterion};
 use tempfile::NamedTempFile;
 
-criterion_group!(fs, read, write);
+criterion_group!(fs, read, cached_read, write);
 criterion_main!(fs);
 
 fn read(c: &mut Criterion) {
@@ -54,6 +54,71 @@ fn read(c: &mut Criterion) {
     group.finish();
 }
 
+fn cached_read(c: &mut Criterion) {
+    // The file is read repeatedly, so that it stays in the cache, and the
+    // reads could complete synchronously.
+    let mut group = c.benchmark_group("cached_read");
+
+    group.bench_function("std", |b| {
+        use std::io::{Read, Seek, SeekFrom};
+
+        let mut file = std::fs::File::open("Cargo.toml").unwrap();
+        let mut buffer = vec![0; 1024];
+        b.iter(|| {
+            file.seek(SeekFrom::Start(0)).unwrap();
+            file.read(&mut buffer).unwrap()
+        })
+    });
+
+    group.bench_function("tokio", |b| {
+        use std::io::SeekFrom;
+        use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let file = runtime
+            .block_on(tokio::fs::File::open("Cargo.toml"))
+            .unwrap();
+        let file = tokio::sync::Mutex::new(file);
+        b.to_async(&runtime).iter(|| async {
+            let mut file = file.lock().await;
+            let mut buffer = vec![0; 1024];
+            file.seek(SeekFrom::Start(0)).await.unwrap();
+            file.read(&mut buffer).await.unwrap()
+        })
+    });
+
+    // The reads completing synchronously skip the completion port on Windows,
+    // which is compared with the handle attached without the mode.
+    #[cfg(windows)]
+    for (id, skip) in [("skip", true), ("no_skip", false)] {
+        group.bench_function(id, |b| {
+            let runtime = tokio_iocp::runtime::Builder::new()
+                .skip_on_success(skip)
+                .build()
+                .unwrap();
+            cached_read_iocp(b, &runtime);
+        });
+    }
+
+    // There is no equivalent path on Linux, where the reads always complete
+    // through the driver, so the crate is not measured here.
+
+    group.finish();
+}
+
+#[cfg(windows)]
+fn cached_read_iocp(b: &mut criterion::Bencher, runtime: &tokio_iocp::runtime::Runtime) {
+    let file = runtime.block_on(async { tokio_iocp::fs::File::open("Cargo.toml").unwrap() });
+    b.to_async(runtime).iter(|| async {
+        let (res, buffer) = file.read_at(Vec::with_capacity(1024), 0).await;
+        res.unwrap();
+        buffer
+    })
+}
+
 static CONTENT: &[u8] = include_bytes!("../Cargo.toml");
 
 fn write(c: &mut Criterion) {
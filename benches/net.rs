@@ -1,6 +1,6 @@
 use criterion::{criterion_group, criterion_main, Criterion};
 
-criterion_group!(net, tcp);
+criterion_group!(net, tcp, tcp_concurrent);
 criterion_main!(net);
 
 fn tcp(c: &mut Criterion) {
@@ -60,3 +60,43 @@ fn tcp(c: &mut Criterion) {
 
     group.finish();
 }
+
+fn tcp_concurrent(c: &mut Criterion) {
+    const CONNECTIONS: usize = 256;
+    static PACKET: &[u8] = &[1u8; 64];
+
+    let mut group = c.benchmark_group("tcp_concurrent");
+
+    for batch in [1, 64] {
+        let runtime = tokio_iocp::runtime::Runtime::builder()
+            .event_batch_size(batch)
+            .build()
+            .unwrap();
+        let pairs = runtime.block_on(async {
+            let listener = tokio_iocp::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            let addr = listener.local_addr().unwrap();
+            let mut pairs = Vec::with_capacity(CONNECTIONS);
+            for _ in 0..CONNECTIONS {
+                let tx = tokio_iocp::net::TcpStream::connect(addr);
+                let rx = listener.accept();
+                let (tx, (rx, _)) = tokio::try_join!(tx, rx).unwrap();
+                pairs.push((tx, rx));
+            }
+            pairs
+        });
+        group.bench_function(format!("batch-{batch}"), |b| {
+            b.to_async(&runtime).iter(|| {
+                futures_util::future::join_all(pairs.iter().map(|(tx, rx)| async move {
+                    let recv = rx.recv(Vec::with_capacity(PACKET.len()));
+                    let send = tx.send(PACKET);
+                    let ((res, buffer), (sent, _)) = tokio::join!(recv, send);
+                    sent.unwrap();
+                    res.unwrap();
+                    buffer
+                }))
+            })
+        });
+    }
+
+    group.finish();
+}
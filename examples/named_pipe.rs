@@ -1,8 +1,9 @@
-use tokio_iocp::net::named_pipe::{ClientOptions, ServerOptions};
+#[cfg(windows)]
+fn main() {
+    use tokio_iocp::net::named_pipe::{ClientOptions, ServerOptions};
 
-const PIPE_NAME: &str = r"\\.\pipe\tokio-iocp-named-pipe";
+    const PIPE_NAME: &str = r"\\.\pipe\tokio-iocp-named-pipe";
 
-fn main() {
     tokio_iocp::start(async {
         let server = ServerOptions::new()
             .access_inbound(false)
@@ -22,3 +23,8 @@ fn main() {
         println!("{}", String::from_utf8(buffer).unwrap());
     });
 }
+
+#[cfg(not(windows))]
+fn main() {
+    println!("Named pipes are only supported on Windows.");
+}
@@ -1,25 +1,46 @@
 use crate::buf::*;
-use windows_sys::Win32::Networking::WinSock::WSABUF;
+
+/// Creates the buffer descriptors of the buffers, limiting the total length to `cap`.
+fn io_vecs(buffers: impl Iterator<Item = (*const u8, usize)>, mut cap: usize) -> Vec<IoVec> {
+    buffers
+        .map(|(ptr, len)| {
+            let len = len.min(cap);
+            cap -= len;
+            io_vec(ptr, len)
+        })
+        .collect()
+}
 
 pub struct BufWrapper<T> {
     buffer: T,
+    cap: usize,
 }
 
 impl<T: IoBuf> WrapBuf for BufWrapper<T> {
     type Buffer = T;
 
     fn new(buffer: Self::Buffer) -> Self {
-        Self { buffer }
+        Self {
+            buffer,
+            cap: usize::MAX,
+        }
     }
 
     fn into_inner(self) -> Self::Buffer {
         self.buffer
     }
+
+    fn set_cap(&mut self, cap: usize) {
+        self.cap = cap;
+    }
 }
 
 impl<T: IoBuf> WithBuf for BufWrapper<T> {
     fn with_buf<R>(&self, f: impl FnOnce(*const u8, usize) -> R) -> R {
-        f(self.buffer.as_buf_ptr(), self.buffer.buf_len())
+        f(
+            self.buffer.as_buf_ptr(),
+            self.buffer.buf_len().min(self.cap),
+        )
     }
 }
 
@@ -33,57 +54,63 @@ impl<T: IoBufMut> WithBufMut for BufWrapper<T> {
     fn with_buf_mut<R>(&mut self, f: impl FnOnce(*mut u8, usize) -> R) -> R {
         f(
             unsafe { self.buffer.as_buf_mut_ptr().add(self.buffer.buf_len()) },
-            self.buffer.buf_capacity() - self.buffer.buf_len(),
+            (self.buffer.buf_capacity() - self.buffer.buf_len()).min(self.cap),
         )
     }
 }
 
-impl<T: IoBuf> WithWsaBuf for BufWrapper<T> {
-    fn with_wsa_buf<R>(&self, f: impl FnOnce(*const WSABUF, usize) -> R) -> R {
-        let buffer = WSABUF {
-            len: self.buffer.buf_len() as _,
-            buf: self.buffer.as_buf_ptr() as _,
-        };
+impl<T: IoBuf> WithIoVec for BufWrapper<T> {
+    fn with_io_vec<R>(&self, f: impl FnOnce(*const IoVec, usize) -> R) -> R {
+        let buffer = io_vec(
+            self.buffer.as_buf_ptr(),
+            self.buffer.buf_len().min(self.cap),
+        );
         f(&buffer, 1)
     }
 }
 
-impl<T: IoBufMut> WithWsaBufMut for BufWrapper<T> {
-    fn with_wsa_buf_mut<R>(&mut self, f: impl FnOnce(*const WSABUF, usize) -> R) -> R {
-        let buffer = WSABUF {
-            len: (self.buffer.buf_capacity() - self.buffer.buf_len()) as _,
-            buf: unsafe { self.buffer.as_buf_mut_ptr().add(self.buffer.buf_len()) },
-        };
+impl<T: IoBufMut> WithIoVecMut for BufWrapper<T> {
+    fn with_io_vec_mut<R>(&mut self, f: impl FnOnce(*const IoVec, usize) -> R) -> R {
+        let buffer = io_vec(
+            unsafe { self.buffer.as_buf_mut_ptr().add(self.buffer.buf_len()) },
+            (self.buffer.buf_capacity() - self.buffer.buf_len()).min(self.cap),
+        );
         f(&buffer, 1)
     }
 }
 
 pub struct VectoredBufWrapper<T> {
     buffer: Vec<T>,
+    cap: usize,
 }
 
 impl<T: IoBuf> WrapBuf for VectoredBufWrapper<T> {
     type Buffer = Vec<T>;
 
     fn new(buffer: Self::Buffer) -> Self {
-        Self { buffer }
+        Self {
+            buffer,
+            cap: usize::MAX,
+        }
     }
 
     fn into_inner(self) -> Self::Buffer {
         self.buffer
     }
+
+    fn set_cap(&mut self, cap: usize) {
+        self.cap = cap;
+    }
 }
 
-impl<T: IoBuf> WithWsaBuf for VectoredBufWrapper<T> {
-    fn with_wsa_buf<R>(&self, f: impl FnOnce(*const WSABUF, usize) -> R) -> R {
-        let buffers = self
-            .buffer
-            .iter()
-            .map(|buf| WSABUF {
-                len: buf.buf_len() as _,
-                buf: buf.as_buf_ptr() as _,
-            })
-            .collect::<Vec<_>>();
+impl<T: IoBuf> WithIoVec for VectoredBufWrapper<T> {
+    fn with_io_vec<R>(&self, f: impl FnOnce(*const IoVec, usize) -> R) -> R {
+        let buffers = io_vecs(
+            self.buffer
+                .iter()
+                .map(|buf| (buf.as_buf_ptr(), buf.buf_len())),
+            self.cap,
+        );
         f(buffers.as_ptr(), buffers.len())
     }
 }
@@ -103,16 +130,17 @@ impl<T: IoBufMut> WrapBufMut for VectoredBufWrapper<T> {
     }
 }
 
-impl<T: IoBufMut> WithWsaBufMut for VectoredBufWrapper<T> {
-    fn with_wsa_buf_mut<R>(&mut self, f: impl FnOnce(*const WSABUF, usize) -> R) -> R {
-        let buffers = self
-            .buffer
-            .iter_mut()
-            .map(|buf| WSABUF {
-                len: (buf.buf_capacity() - buf.buf_len()) as _,
-                buf: unsafe { buf.as_buf_mut_ptr().add(buf.buf_len()) } as _,
-            })
-            .collect::<Vec<_>>();
+impl<T: IoBufMut> WithIoVecMut for VectoredBufWrapper<T> {
+    fn with_io_vec_mut<R>(&mut self, f: impl FnOnce(*const IoVec, usize) -> R) -> R {
+        let buffers = io_vecs(
+            self.buffer.iter_mut().map(|buf| {
+                (
+                    unsafe { buf.as_buf_mut_ptr().add(buf.buf_len()) } as *const u8,
+                    buf.buf_capacity() - buf.buf_len(),
+                )
+            }),
+            self.cap,
+        );
         f(buffers.as_ptr(), buffers.len())
     }
 }
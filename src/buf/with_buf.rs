@@ -1,10 +1,46 @@
-use windows_sys::Win32::Networking::WinSock::WSABUF;
+#[cfg(target_os = "linux")]
+pub use libc::iovec as IoVec;
+#[cfg(windows)]
+pub use windows_sys::Win32::Networking::WinSock::WSABUF as IoVec;
+
+/// Creates a platform buffer descriptor from the pointer and length.
+pub fn io_vec(ptr: *const u8, len: usize) -> IoVec {
+    #[cfg(windows)]
+    {
+        IoVec {
+            len: len as _,
+            buf: ptr as _,
+        }
+    }
+    #[cfg(target_os = "linux")]
+    {
+        IoVec {
+            iov_base: ptr as _,
+            iov_len: len,
+        }
+    }
+}
+
+/// Gets the pointer and length of a platform buffer descriptor.
+pub fn io_vec_parts(buffer: &IoVec) -> (*mut u8, usize) {
+    #[cfg(windows)]
+    {
+        (buffer.buf, buffer.len as _)
+    }
+    #[cfg(target_os = "linux")]
+    {
+        (buffer.iov_base as _, buffer.iov_len)
+    }
+}
 
 pub trait WrapBuf {
-    type Buffer;
+    type Buffer: 'static;
 
     fn new(buffer: Self::Buffer) -> Self;
     fn into_inner(self) -> Self::Buffer;
+
+    /// Limits the length of the buffer exposed to the operation.
+    fn set_cap(&mut self, cap: usize);
 }
 
 pub trait WrapBufMut {
@@ -19,10 +55,10 @@ pub trait WithBufMut: WrapBufMut + WithBuf {
     fn with_buf_mut<R>(&mut self, f: impl FnOnce(*mut u8, usize) -> R) -> R;
 }
 
-pub trait WithWsaBuf: WrapBuf {
-    fn with_wsa_buf<R>(&self, f: impl FnOnce(*const WSABUF, usize) -> R) -> R;
+pub trait WithIoVec: WrapBuf {
+    fn with_io_vec<R>(&self, f: impl FnOnce(*const IoVec, usize) -> R) -> R;
 }
 
-pub trait WithWsaBufMut: WrapBufMut + WithWsaBuf {
-    fn with_wsa_buf_mut<R>(&mut self, f: impl FnOnce(*const WSABUF, usize) -> R) -> R;
+pub trait WithIoVecMut: WrapBufMut + WithIoVec {
+    fn with_io_vec_mut<R>(&mut self, f: impl FnOnce(*const IoVec, usize) -> R) -> R;
 }
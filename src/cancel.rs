@@ -0,0 +1,429 @@
+//! Explicit cancellation of operations.
+//!
+//! Dropping the future of an operation cancels it, but the buffer is lost.
+//! Instead, a future could be wrapped by [`CancelExt::with_cancel`]. When the
+//! [`CancelToken`] is cancelled, the operations submitted by the future are
+//! cancelled, and they resolve to an error of [`ErrorKind::Interrupted`] with
+//! the buffer. An operation completed before the cancellation takes effect
+//! returns its result as usual.
+//!
+//! Similarly, [`CancelExt::timeout`] cancels the operations of a future when
+//! the duration elapses, and they resolve to an error of
+//! [`ErrorKind::TimedOut`] with the buffer.
+//!
+//! [`race`] runs several operations, and cancels the others when one of them
+//! completes, returning all buffers.
+//!
+//! ```
+//! use std::io::ErrorKind;
+//! use tokio_iocp::{cancel::{CancelExt, CancelToken}, net::UdpSocket};
+//!
+//! tokio_iocp::start(async {
+//!     let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+//!     let token = CancelToken::new();
+//!     let recv = tokio_iocp::spawn({
+//!         let token = token.clone();
+//!         async move { socket.recv(Vec::with_capacity(64)).with_cancel(&token).await }
+//!     });
+//!     tokio::task::yield_now().await;
+//!
+//!     token.cancel();
+//!     let (res, buf) = recv.await.unwrap();
+//!     assert_eq!(res.unwrap_err().kind(), ErrorKind::Interrupted);
+//!     assert_eq!(buf.capacity(), 64);
+//! });
+//! ```
+//!
+//! [`ErrorKind::Interrupted`]: std::io::ErrorKind::Interrupted
+//! [`ErrorKind::TimedOut`]: std::io::ErrorKind::TimedOut
+
+use crate::*;
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    future::{poll_fn, Future},
+    io::ErrorKind,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+
+thread_local! {
+    // The tokens of the futures being polled on the current thread.
+    static CURRENT: RefCell<Vec<CancelToken>> = const { RefCell::new(Vec::new()) };
+}
+
+#[derive(Debug, Default)]
+struct State {
+    // The error kind of the cancelled operations.
+    reason: Option<ErrorKind>,
+    wakers: HashMap<usize, Waker>,
+    next_key: usize,
+}
+
+/// A token to cancel the operations of the futures wrapped by
+/// [`CancelExt::with_cancel`].
+///
+/// The token is cheap to clone, and could be cancelled from any thread.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    state: Arc<Mutex<State>>,
+}
+
+impl CancelToken {
+    /// Creates a token which is not cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels the operations in flight, and the ones submitted later.
+    pub fn cancel(&self) {
+        self.cancel_with(ErrorKind::Interrupted);
+    }
+
+    /// Whether the token is cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.state.lock().unwrap().reason.is_some()
+    }
+
+    /// Cancels the operations with an error of `kind`, unless the token is
+    /// already cancelled.
+    fn cancel_with(&self, kind: ErrorKind) {
+        let wakers = {
+            let mut state = self.state.lock().unwrap();
+            state.reason.get_or_insert(kind);
+            std::mem::take(&mut state.wakers)
+        };
+        wakers.into_values().for_each(Waker::wake);
+    }
+
+    /// Registers or updates the waker of `key`, and returns the error kind if
+    /// the token is cancelled.
+    fn register(&self, key: &mut Option<usize>, waker: &Waker) -> Option<ErrorKind> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(kind) = state.reason {
+            return Some(kind);
+        }
+        let key = *key.get_or_insert_with(|| {
+            state.next_key += 1;
+            state.next_key
+        });
+        match state.wakers.get_mut(&key) {
+            Some(current) if current.will_wake(waker) => {}
+            _ => {
+                state.wakers.insert(key, waker.clone());
+            }
+        }
+        None
+    }
+
+    fn unregister(&self, key: usize) {
+        self.state.lock().unwrap().wakers.remove(&key);
+    }
+
+    fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.state, &other.state)
+    }
+}
+
+/// The registrations of an operation to the tokens of the futures being
+/// polled.
+#[derive(Debug, Default)]
+pub(crate) struct Registration {
+    tokens: Vec<(CancelToken, Option<usize>)>,
+}
+
+impl Registration {
+    /// Registers the waker to the tokens of the current thread, and returns
+    /// the error kind of the first cancelled one.
+    pub fn poll_cancelled(&mut self, cx: &mut Context<'_>) -> Option<ErrorKind> {
+        CURRENT.with(|current| {
+            current.borrow().iter().find_map(|token| {
+                let index = match self.tokens.iter().position(|(t, _)| t.ptr_eq(token)) {
+                    Some(index) => index,
+                    None => {
+                        self.tokens.push((token.clone(), None));
+                        self.tokens.len() - 1
+                    }
+                };
+                let (token, key) = &mut self.tokens[index];
+                token.register(key, cx.waker())
+            })
+        })
+    }
+
+    /// Removes the wakers from the tokens.
+    pub fn clear(&mut self) {
+        for (token, key) in self.tokens.drain(..) {
+            if let Some(key) = key {
+                token.unregister(key);
+            }
+        }
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+/// The error of an operation cancelled by a [`CancelToken`].
+pub(crate) fn cancelled(kind: ErrorKind) -> IoError {
+    match kind {
+        ErrorKind::TimedOut => IoError::new(kind, "the operation timed out"),
+        _ => IoError::new(kind, "the operation is cancelled"),
+    }
+}
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// The future of an operation of the resources of this crate, e.g.,
+/// [`UdpSocket::recv`].
+///
+/// The trait is sealed. Only these futures could be passed to
+/// [`CancelExt::timeout`] and [`race`], which wait for the operations to
+/// complete after cancelling them. Any other future, submitting no operation,
+/// would never complete.
+///
+/// ```compile_fail
+/// use std::time::Duration;
+/// use tokio_iocp::cancel::CancelExt;
+///
+/// tokio_iocp::start(async {
+///     let sleep = tokio::time::sleep(Duration::from_secs(60));
+///     sleep.timeout(Duration::from_millis(10)).await;
+/// });
+/// ```
+///
+/// [`UdpSocket::recv`]: crate::net::UdpSocket::recv
+pub trait OpFuture: Future + private::Sealed {}
+
+/// Marks the future of an operation, which completes after it is cancelled.
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub(crate) struct Op<F> {
+    future: F,
+}
+
+impl<F: Future> Op<F> {
+    pub fn new(future: F) -> Self {
+        Self { future }
+    }
+}
+
+impl<F: Future> Future for Op<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // SAFETY: `future` is structurally pinned, and never moved.
+        unsafe { self.map_unchecked_mut(|this| &mut this.future) }.poll(cx)
+    }
+}
+
+impl<F> private::Sealed for Op<F> {}
+
+impl<F: Future> OpFuture for Op<F> {}
+
+/// A future whose operations could be cancelled by a [`CancelToken`].
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct WithCancel<F> {
+    future: F,
+    token: CancelToken,
+}
+
+impl<F: Future> Future for WithCancel<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        struct Guard;
+
+        impl Drop for Guard {
+            fn drop(&mut self) {
+                CURRENT.with(|current| current.borrow_mut().pop());
+            }
+        }
+
+        CURRENT.with(|current| current.borrow_mut().push(self.token.clone()));
+        let _guard = Guard;
+        // SAFETY: `future` is structurally pinned, and never moved.
+        let future = unsafe { self.map_unchecked_mut(|this| &mut this.future) };
+        future.poll(cx)
+    }
+}
+
+/// A future whose operations are cancelled when the duration elapses.
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Timeout<F> {
+    future: WithCancel<F>,
+    sleep: Option<time::Sleep>,
+}
+
+impl<F: Future> Future for Timeout<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // SAFETY: `future` is structurally pinned, and never moved.
+        let this = unsafe { self.get_unchecked_mut() };
+        if let Some(sleep) = &mut this.sleep {
+            if Pin::new(sleep).poll(cx).is_ready() {
+                this.sleep = None;
+                this.future.token.cancel_with(ErrorKind::TimedOut);
+            }
+        }
+        // The operations wait for the cancellation to complete.
+        // SAFETY: `this` is pinned, and so is `future`.
+        unsafe { Pin::new_unchecked(&mut this.future) }.poll(cx)
+    }
+}
+
+/// The output of [`race`].
+#[derive(Debug)]
+pub struct Race<R, T> {
+    /// The index of the first completed future.
+    pub index: usize,
+    /// The result of the first completed future.
+    pub winner: BufResult<R, T>,
+    /// The results of the other futures in order. They are cancelled, and
+    /// usually fail with [`ErrorKind::Interrupted`], unless they completed
+    /// before the cancellation took effect.
+    pub losers: Vec<BufResult<R, T>>,
+}
+
+/// Polls the futures concurrently until one of them completes, then cancels
+/// the others and waits for the cancellations to complete.
+///
+/// The buffers of the cancelled futures are returned, instead of dropped
+/// with the futures as `tokio::select!` does.
+///
+/// # Panics
+///
+/// Panics if there is no future.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::ErrorKind;
+/// use tokio_iocp::{cancel::race, net::UdpSocket};
+///
+/// tokio_iocp::start(async {
+///     let primary = UdpSocket::bind("127.0.0.1:0").unwrap();
+///     let replica = UdpSocket::bind("127.0.0.1:0").unwrap();
+///     let addr = replica.local_addr().unwrap();
+///     primary.send_to("hello", addr).await.0.unwrap();
+///
+///     let res = race([
+///         primary.recv(Vec::with_capacity(64)),
+///         replica.recv(Vec::with_capacity(64)),
+///     ])
+///     .await;
+///     assert_eq!(res.index, 1);
+///     assert_eq!(res.winner.1, b"hello");
+///
+///     let (loser, buffer) = res.losers.into_iter().next().unwrap();
+///     assert_eq!(loser.unwrap_err().kind(), ErrorKind::Interrupted);
+///     assert_eq!(buffer.capacity(), 64);
+/// });
+/// ```
+pub async fn race<F, R, T>(futures: impl IntoIterator<Item = F>) -> Race<R, T>
+where
+    F: OpFuture<Output = BufResult<R, T>>,
+{
+    let mut futures = futures
+        .into_iter()
+        .map(|future| Some(Box::pin(future.with_cancel(&CancelToken::new()))))
+        .collect::<Vec<_>>();
+    assert!(!futures.is_empty(), "no future to race");
+    let mut results = futures.iter().map(|_| None).collect::<Vec<_>>();
+    let mut index = None;
+    poll_fn(|cx| {
+        for (future, result) in futures.iter_mut().zip(&mut results) {
+            if let Some(Poll::Ready(res)) = future.as_mut().map(|f| f.as_mut().poll(cx)) {
+                *future = None;
+                *result = Some(res);
+            }
+        }
+        if index.is_none() {
+            index = results.iter().position(Option::is_some);
+            if index.is_some() {
+                for future in futures.iter().flatten() {
+                    future.token.cancel();
+                }
+                // Poll the cancelled futures again.
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+        }
+        if futures.iter().all(Option::is_none) {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    })
+    .await;
+
+    let index = index.unwrap();
+    let mut results = results.into_iter().map(Option::unwrap).collect::<Vec<_>>();
+    let winner = results.remove(index);
+    Race {
+        index,
+        winner,
+        losers: results,
+    }
+}
+
+/// An extension trait to cancel the operations of a future.
+pub trait CancelExt: Future + Sized {
+    /// Cancels the operations submitted by the future when `token` is
+    /// cancelled.
+    ///
+    /// It works for the operations of all resources of this crate. Nested
+    /// tokens are supported, and the operations are cancelled by any of them.
+    fn with_cancel(self, token: &CancelToken) -> WithCancel<Self> {
+        WithCancel {
+            future: self,
+            token: token.clone(),
+        }
+    }
+
+    /// Cancels the operations submitted by the future when `duration`
+    /// elapses.
+    ///
+    /// Unlike [`tokio::time::timeout`], the future is not dropped. The
+    /// operations in flight are cancelled, and resolve to an error of
+    /// [`ErrorKind::TimedOut`] with the buffer when the cancellation
+    /// completes. Only the futures of the operations of this crate could be
+    /// wrapped, see [`OpFuture`].
+    ///
+    /// ```
+    /// use std::{io::ErrorKind, time::Duration};
+    /// use tokio_iocp::{cancel::CancelExt, net::UdpSocket};
+    ///
+    /// tokio_iocp::start(async {
+    ///     let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    ///     let mut buf = Vec::with_capacity(64);
+    ///     for _ in 0..3 {
+    ///         let (res, buffer) = socket.recv(buf).timeout(Duration::from_millis(10)).await;
+    ///         assert_eq!(res.unwrap_err().kind(), ErrorKind::TimedOut);
+    ///         // Reuse the buffer to retry.
+    ///         buf = buffer;
+    ///     }
+    /// });
+    /// ```
+    fn timeout(self, duration: Duration) -> Timeout<Self>
+    where
+        Self: OpFuture,
+    {
+        Timeout {
+            future: self.with_cancel(&CancelToken::new()),
+            sleep: Some(time::sleep(duration)),
+        }
+    }
+}
+
+impl<F: Future> CancelExt for F {}
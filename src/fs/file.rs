@@ -1,18 +1,30 @@
 use crate::{
     buf::*,
     fs::OpenOptions,
-    io_port::IO_PORT,
+    io_port::{self, Attached, BorrowedRes, RawRes},
     op::{self, BufResultExt, BufResultIntoInner},
+    runtime::{
+        self,
+        fault::{set_handle_fault_policy, FaultPolicy, FaultTarget},
+        Detached, Resource,
+    },
     *,
 };
-use std::{
-    os::windows::prelude::{
-        AsHandle, AsRawHandle, BorrowedHandle, IntoRawHandle, OwnedHandle, RawHandle,
-    },
-    path::Path,
+#[cfg(target_os = "linux")]
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, IntoRawFd, OwnedFd, RawFd};
+#[cfg(windows)]
+use std::os::windows::prelude::{
+    AsHandle, AsRawHandle, BorrowedHandle, IntoRawHandle, OwnedHandle, RawHandle,
 };
+use std::path::Path;
+#[cfg(windows)]
 use windows_sys::Win32::Storage::FileSystem::FlushFileBuffers;
 
+#[cfg(windows)]
+type OwnedRes = OwnedHandle;
+#[cfg(target_os = "linux")]
+type OwnedRes = OwnedFd;
+
 /// A reference to an open file on the filesystem.
 ///
 /// An instance of a `File` can be read and/or written depending on what options
@@ -45,7 +57,7 @@ use windows_sys::Win32::Storage::FileSystem::FlushFileBuffers;
 /// ```
 #[derive(Debug)]
 pub struct File {
-    handle: OwnedHandle,
+    handle: Attached<OwnedRes>,
 }
 
 impl File {
@@ -70,10 +82,10 @@ impl File {
             .open(path)
     }
 
-    pub(crate) fn from_handle(handle: OwnedHandle) -> IoResult<Self> {
-        let file = Self { handle };
-        file.attach()?;
-        Ok(file)
+    pub(crate) fn from_handle(handle: OwnedRes) -> IoResult<Self> {
+        Ok(Self {
+            handle: Attached::new(handle)?,
+        })
     }
 
     /// Create an [`OpenOptions`].
@@ -82,8 +94,14 @@ impl File {
         OpenOptions::new()
     }
 
-    fn attach(&self) -> IoResult<()> {
-        IO_PORT.with(|port| port.attach(self.handle.as_raw_handle() as _))
+    #[cfg(windows)]
+    fn as_res(&self) -> BorrowedHandle<'_> {
+        self.handle.as_handle()
+    }
+
+    #[cfg(target_os = "linux")]
+    fn as_res(&self) -> BorrowedFd<'_> {
+        self.handle.as_fd()
     }
 
     /// Read some bytes at the specified offset from the file into the specified
@@ -108,11 +126,17 @@ impl File {
     ///
     /// If this function encounters any form of I/O or other error, an error
     /// variant will be returned. The buffer is returned on error.
-    pub async fn read_at<T: IoBufMut>(&self, buffer: T, pos: usize) -> BufResult<usize, T> {
-        op::read_at(self.as_handle(), buffer, pos)
-            .await
-            .map_advanced()
-            .into_inner()
+    pub fn read_at<T: IoBufMut>(
+        &self,
+        buffer: T,
+        pos: usize,
+    ) -> impl cancel::OpFuture<Output = BufResult<usize, T>> + use<'_, T> {
+        cancel::Op::new(async move {
+            op::read_at(self.as_res(), buffer, pos)
+                .await
+                .map_advanced()
+                .into_inner()
+        })
     }
 
     /// Write a buffer into this file at the specified offset, returning how
@@ -137,15 +161,19 @@ impl File {
     ///
     /// It is **not** considered an error if the entire buffer could not be
     /// written to this writer.
-    pub async fn write_at<T: IoBuf>(&self, buffer: T, pos: usize) -> BufResult<usize, T> {
-        op::write_at(self.as_handle(), buffer, pos)
-            .await
-            .into_inner()
+    pub fn write_at<T: IoBuf>(
+        &self,
+        buffer: T,
+        pos: usize,
+    ) -> impl cancel::OpFuture<Output = BufResult<usize, T>> + use<'_, T> {
+        cancel::Op::new(async move { op::write_at(self.as_res(), buffer, pos).await.into_inner() })
     }
 
     /// Attempts to flush write buffers to disk.
     ///
     /// This function will error if the file doesn't have write permission.
+    /// It blocks the runtime thread, see [`File::sync_all`] instead.
+    #[cfg(windows)]
     pub fn flush(&self) -> IoResult<()> {
         let res = unsafe { FlushFileBuffers(self.as_raw_handle() as _) };
         if res == 0 {
@@ -154,22 +182,131 @@ impl File {
             Ok(())
         }
     }
+
+    /// Attempts to flush write buffers to disk.
+    ///
+    /// This function will error if the file doesn't have write permission.
+    /// It blocks the runtime thread, see [`File::sync_all`] instead.
+    #[cfg(target_os = "linux")]
+    pub fn flush(&self) -> IoResult<()> {
+        let res = unsafe { libc::fsync(self.as_raw_fd()) };
+        if res == 0 {
+            Ok(())
+        } else {
+            Err(IoError::last_os_error())
+        }
+    }
+
+    /// Attempts to sync all data and metadata to disk, on the blocking pool
+    /// of the runtime.
+    ///
+    /// Unlike [`File::flush`], it doesn't block the runtime thread. See
+    /// [`std::fs::File::sync_all`].
+    pub async fn sync_all(&self) -> IoResult<()> {
+        let file = self.try_clone_std()?;
+        runtime::spawn_blocking(move || file.sync_all()).await
+    }
+
+    /// Attempts to sync the data to disk, without the metadata if possible, on
+    /// the blocking pool of the runtime.
+    ///
+    /// See [`std::fs::File::sync_data`].
+    pub async fn sync_data(&self) -> IoResult<()> {
+        let file = self.try_clone_std()?;
+        runtime::spawn_blocking(move || file.sync_data()).await
+    }
+
+    /// Detaches the file from the driver of the current thread, to be sent to
+    /// another runtime thread. See [`Detached`].
+    pub fn into_detached(self) -> IoResult<Detached<Self>> {
+        Detached::detach(self)
+    }
+
+    /// Closes the file, and returns the error of closing, which is ignored when
+    /// the file is dropped.
+    ///
+    /// The operations whose futures were dropped are cancelled, but they may
+    /// still be in flight. They are waited for before the handle is closed, so
+    /// that the handle won't be reused while the kernel still refers to it.
+    ///
+    /// There is no buffer in user space to flush, and the written data is not
+    /// synced to disk. Call [`File::sync_all`] before if it is needed.
+    ///
+    /// ```
+    /// use tempfile::NamedTempFile;
+    /// use tokio_iocp::{fs::File, IoResult};
+    ///
+    /// fn main() -> IoResult<()> {
+    ///     tokio_iocp::start(async {
+    ///         let file = File::create(NamedTempFile::new()?)?;
+    ///         let (res, _) = file.write_at("hello world", 0).await;
+    ///         res?;
+    ///         file.close().await
+    ///     })
+    /// }
+    /// ```
+    pub async fn close(self) -> IoResult<()> {
+        io_port::prepare_close(self.as_raw_res()).await;
+        io_port::close_handle(self.handle.into_inner())
+    }
+
+    /// Duplicates the handle, which is still valid on the blocking pool even if
+    /// `self` is dropped.
+    fn try_clone_std(&self) -> IoResult<std::fs::File> {
+        Ok(self.handle.try_clone()?.into())
+    }
+}
+
+impl Resource for File {
+    fn as_raw_res(&self) -> RawRes {
+        BorrowedRes::from(self.as_res()).as_raw()
+    }
 }
 
+impl FaultTarget for File {
+    fn set_fault_policy(&self, policy: Option<FaultPolicy>) {
+        set_handle_fault_policy(BorrowedRes::from(self.as_res()).as_raw(), policy)
+    }
+}
+
+#[cfg(windows)]
 impl AsRawHandle for File {
     fn as_raw_handle(&self) -> RawHandle {
         self.handle.as_raw_handle()
     }
 }
 
+#[cfg(windows)]
 impl IntoRawHandle for File {
     fn into_raw_handle(self) -> RawHandle {
         self.handle.into_raw_handle()
     }
 }
 
+#[cfg(windows)]
 impl AsHandle for File {
-    fn as_handle(&self) -> BorrowedHandle {
+    fn as_handle(&self) -> BorrowedHandle<'_> {
         self.handle.as_handle()
     }
 }
+
+#[cfg(target_os = "linux")]
+impl AsRawFd for File {
+    fn as_raw_fd(&self) -> RawFd {
+        self.handle.as_raw_fd()
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl IntoRawFd for File {
+    fn into_raw_fd(self) -> RawFd {
+        self.handle.into_raw_fd()
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl AsFd for File {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.handle.as_fd()
+    }
+}
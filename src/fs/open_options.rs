@@ -1,5 +1,10 @@
-use crate::{fs::File, *};
-use std::{fs::OpenOptions as StdOpenOptions, os::windows::prelude::OpenOptionsExt, path::Path};
+use crate::{fs::File, runtime, *};
+#[cfg(target_os = "linux")]
+use std::os::unix::fs::OpenOptionsExt;
+#[cfg(windows)]
+use std::os::windows::prelude::OpenOptionsExt;
+use std::{fs::OpenOptions as StdOpenOptions, path::Path};
+#[cfg(windows)]
 use windows_sys::Win32::Storage::FileSystem::FILE_FLAG_OVERLAPPED;
 
 /// Options and flags which can be used to configure how a file is opened.
@@ -45,13 +50,16 @@ impl OpenOptions {
     /// Creates a blank new set of options ready for configuration.
     ///
     /// All options are initially set to `false`.
-    /// We internally set [`FILE_FLAG_OVERLAPPED`] flag to make sure
-    /// IOCP support is enabled.
+    /// On Windows we internally set `FILE_FLAG_OVERLAPPED` flag to make sure
+    /// IOCP support is enabled. On Linux the file is opened with `O_CLOEXEC`.
     #[allow(clippy::new_without_default)]
     #[must_use]
     pub fn new() -> Self {
         let mut options = StdOpenOptions::new();
+        #[cfg(windows)]
         options.custom_flags(FILE_FLAG_OVERLAPPED);
+        #[cfg(target_os = "linux")]
+        options.custom_flags(libc::O_CLOEXEC);
         Self(options)
     }
 
@@ -120,4 +128,26 @@ impl OpenOptions {
     pub fn open(&self, path: impl AsRef<Path>) -> IoResult<File> {
         File::from_handle(self.0.open(path)?.into())
     }
+
+    /// Opens a file at `path` with the options specified by `self`, on the
+    /// blocking pool of the runtime.
+    ///
+    /// Unlike [`OpenOptions::open`], it doesn't block the runtime thread, e.g.,
+    /// when opening a file on a slow network share.
+    ///
+    /// ```
+    /// use tokio_iocp::fs::OpenOptions;
+    ///
+    /// tokio_iocp::start(async {
+    ///     let file = OpenOptions::new().read(true).open_async("Cargo.toml").await.unwrap();
+    ///     let (res, _) = file.read_at(Vec::with_capacity(64), 0).await;
+    ///     assert_eq!(res.unwrap(), 64);
+    /// });
+    /// ```
+    pub async fn open_async(&self, path: impl AsRef<Path>) -> IoResult<File> {
+        let options = self.0.clone();
+        let path = path.as_ref().to_path_buf();
+        let file = runtime::spawn_blocking(move || options.open(path)).await?;
+        File::from_handle(file.into())
+    }
 }
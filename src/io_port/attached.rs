@@ -0,0 +1,79 @@
+use crate::{
+    io_port::{sys::AsRawRes, IO_PORT},
+    *,
+};
+use std::{mem::ManuallyDrop, ops::Deref};
+
+/// An owned handle attached to the driver of the current thread.
+///
+/// The handle is forgotten by the driver when it is dropped. Otherwise, a new
+/// handle with the same value, created on another thread and sent here, would
+/// be treated as attached, and its operations would never complete.
+#[derive(Debug)]
+pub struct Attached<T: AsRawRes> {
+    handle: ManuallyDrop<T>,
+}
+
+impl<T: AsRawRes> Attached<T> {
+    /// Attaches the handle to the driver of the current thread.
+    pub fn new(handle: T) -> IoResult<Self> {
+        IO_PORT.with(|port| port.attach(handle.as_raw_res()))?;
+        Ok(Self {
+            handle: ManuallyDrop::new(handle),
+        })
+    }
+
+    /// Forgets the handle in the driver, and returns it.
+    pub fn into_inner(self) -> T {
+        let mut this = ManuallyDrop::new(self);
+        forget(this.handle.as_raw_res());
+        unsafe { ManuallyDrop::take(&mut this.handle) }
+    }
+}
+
+impl<T: AsRawRes> Deref for Attached<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.handle
+    }
+}
+
+impl<T: AsRawRes> Drop for Attached<T> {
+    fn drop(&mut self) {
+        // Before the handle is closed, and the value could be reused.
+        forget(self.handle.as_raw_res());
+        unsafe { ManuallyDrop::drop(&mut self.handle) }
+    }
+}
+
+/// Forgets the handle in the driver of the current thread, if the thread is
+/// not exiting.
+fn forget(handle: io_port::RawRes) {
+    IO_PORT.try_with(|port| port.forget(handle)).ok();
+}
+
+#[cfg(target_os = "linux")]
+impl<T: AsRawRes + std::os::fd::IntoRawFd> std::os::fd::IntoRawFd for Attached<T> {
+    fn into_raw_fd(self) -> std::os::fd::RawFd {
+        self.into_inner().into_raw_fd()
+    }
+}
+
+#[cfg(windows)]
+impl<T: AsRawRes + std::os::windows::io::IntoRawHandle> std::os::windows::io::IntoRawHandle
+    for Attached<T>
+{
+    fn into_raw_handle(self) -> std::os::windows::io::RawHandle {
+        self.into_inner().into_raw_handle()
+    }
+}
+
+#[cfg(windows)]
+impl<T: AsRawRes + std::os::windows::io::IntoRawSocket> std::os::windows::io::IntoRawSocket
+    for Attached<T>
+{
+    fn into_raw_socket(self) -> std::os::windows::io::RawSocket {
+        self.into_inner().into_raw_socket()
+    }
+}
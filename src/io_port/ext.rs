@@ -0,0 +1,84 @@
+use std::any::Any;
+
+/// The kind of an operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum OpKind {
+    /// Reads from a file.
+    Read,
+    /// Writes to a file.
+    Write,
+    /// Accepts a connection.
+    Accept,
+    /// Connects to a remote address.
+    Connect,
+    /// Receives from a socket.
+    Recv,
+    /// Sends to a socket.
+    Send,
+    /// Receives from a socket with the remote address.
+    RecvFrom,
+    /// Sends to a remote address.
+    SendTo,
+    /// Waits for a client of a named pipe.
+    #[cfg(windows)]
+    ConnectNamedPipe,
+}
+
+impl OpKind {
+    /// All kinds of operations on the current platform.
+    pub const ALL: &'static [OpKind] = &[
+        Self::Read,
+        Self::Write,
+        Self::Accept,
+        Self::Connect,
+        Self::Recv,
+        Self::Send,
+        Self::RecvFrom,
+        Self::SendTo,
+        #[cfg(windows)]
+        Self::ConnectNamedPipe,
+    ];
+}
+
+/// The platform independent methods of an operation, used by the simulated
+/// driver and the fault injection.
+pub trait OpCodeExt {
+    /// The kind of the operation.
+    fn kind(&self) -> OpKind;
+
+    /// Copies `data` into the buffer of a read operation.
+    ///
+    /// Returns the count of bytes copied.
+    fn fill(&mut self, _data: &[u8]) -> usize {
+        0
+    }
+
+    /// The capacity of the buffer of a read operation, or the length of the
+    /// buffer of a write operation.
+    fn buf_len(&mut self) -> usize {
+        0
+    }
+
+    /// The offset of a file operation.
+    #[cfg(feature = "tracing")]
+    fn offset(&self) -> Option<usize> {
+        None
+    }
+
+    /// The data of a write operation.
+    fn data(&self) -> Vec<u8> {
+        vec![]
+    }
+
+    /// Limits the count of bytes transferred by the operation.
+    fn set_cap(&mut self, _cap: usize) {}
+
+    /// Takes the buffer owned by the operation, for the graveyard.
+    fn into_buffer(self) -> Option<Box<dyn Any>>
+    where
+        Self: Sized,
+    {
+        None
+    }
+}
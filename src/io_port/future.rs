@@ -1,144 +1,238 @@
-use crate::{io_port::waker::*, *};
+use crate::{
+    cancel::{self, Registration},
+    io_port::{waker::*, BorrowedRes, IoPort, OpCode, OpCodeExt, OpKind, IO_PORT},
+    *,
+};
 use std::{
     future::Future,
-    os::windows::prelude::{AsRawHandle, AsRawSocket, BorrowedHandle, BorrowedSocket},
+    io::ErrorKind,
     pin::Pin,
-    rc::Rc,
     task::{Context, Poll},
+    time::{Duration, Instant},
 };
-use windows_sys::Win32::{
-    Foundation::{GetLastError, ERROR_HANDLE_EOF, ERROR_IO_INCOMPLETE},
-    System::IO::{CancelIoEx, GetOverlappedResult, OVERLAPPED},
-};
-
-pub enum BorrowedRes<'a> {
-    Handle(BorrowedHandle<'a>),
-    Socket(BorrowedSocket<'a>),
-}
-
-impl BorrowedRes<'_> {
-    pub fn as_raw_handle(&self) -> usize {
-        match self {
-            Self::Handle(h) => h.as_raw_handle() as _,
-            Self::Socket(h) => h.as_raw_socket() as _,
-        }
-    }
-}
-
-impl<'a> From<BorrowedHandle<'a>> for BorrowedRes<'a> {
-    fn from(h: BorrowedHandle<'a>) -> Self {
-        Self::Handle(h)
-    }
-}
-
-impl<'a> From<BorrowedSocket<'a>> for BorrowedRes<'a> {
-    fn from(h: BorrowedSocket<'a>) -> Self {
-        Self::Socket(h)
-    }
-}
 
 pub struct IocpFuture<'a, T> {
     handle: BorrowedRes<'a>,
-    result: Option<Poll<IoResult<()>>>,
-    overlapped: Rc<OverlappedWaker<T>>,
+    result: Option<Poll<IoResult<usize>>>,
+    // Whether the operation completed inline, and the task hasn't yielded.
+    inline: bool,
+    // The ticket of the operation waiting for a slot to be submitted.
+    queued: Option<u64>,
+    overlapped: OverlappedWaker<T>,
+    kind: OpKind,
+    start: Instant,
+    // The injected delay, which starts when the operation completes.
+    delay: Duration,
+    sleep: Option<time::Sleep>,
+    // The tokens of `cancel::WithCancel`, and the error kind of the
+    // cancelled one.
+    registration: Registration,
+    interrupted: Option<ErrorKind>,
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
 }
 
-impl<'a, T> IocpFuture<'a, T> {
-    pub fn new(
-        handle: impl Into<BorrowedRes<'a>>,
-        buffer: T,
-        op: impl FnOnce(usize, *mut OVERLAPPED, &mut T) -> Poll<IoResult<()>>,
-    ) -> Self {
+impl<'a, T: OpCode + OpCodeExt> IocpFuture<'a, T> {
+    pub fn new(handle: impl Into<BorrowedRes<'a>>, op: T) -> Self {
         let handle = handle.into();
-        let overlapped = Rc::new(OverlappedWaker::new(buffer));
-        let overlapped_ptr =
-            Rc::into_raw(overlapped.clone()) as *const OVERLAPPED as *mut OVERLAPPED;
-        let result = {
-            let mut buffer = overlapped.buffer_mut();
-            op(
-                handle.as_raw_handle(),
-                overlapped_ptr,
-                buffer.as_mut().unwrap(),
-            )
-        };
-        if result.is_ready() {
-            unsafe { Rc::from_raw(overlapped_ptr as *mut OverlappedWaker<T>) };
-        }
+        let kind = op.kind();
+        let start = Instant::now();
+        let overlapped = IO_PORT.with(|port| OverlappedWaker::new(port, op));
+        #[cfg(feature = "tracing")]
+        let span = span(handle.as_raw(), overlapped.buffer_mut().as_mut().unwrap());
+        let mut delay = Duration::ZERO;
+        let mut queued = None;
+        let result = IO_PORT.with(|port| {
+            port.metrics().submit(kind);
+            let injection = port.inject(handle.as_raw(), kind);
+            if let Some(cap) = injection.cap {
+                overlapped.buffer_mut().as_mut().unwrap().set_cap(cap);
+            }
+            delay = injection.delay;
+            match injection.error {
+                Some(kind) => Poll::Ready(Err(kind.into())),
+                None => {
+                    queued = port.enqueue();
+                    match queued {
+                        Some(_) => Poll::Pending,
+                        None => submit(port, &handle, &overlapped),
+                    }
+                }
+            }
+        });
         Self {
             handle,
+            inline: result.is_ready(),
+            queued,
             result: Some(result),
             overlapped,
+            kind,
+            start,
+            delay,
+            sleep: None,
+            registration: Registration::default(),
+            interrupted: None,
+            #[cfg(feature = "tracing")]
+            span,
         }
     }
+}
 
-    fn result(&mut self, res: IoResult<usize>) -> BufResult<usize, T> {
+/// Submits the operation to the driver.
+fn submit<T: OpCode + OpCodeExt>(
+    port: &IoPort,
+    handle: &BorrowedRes<'_>,
+    overlapped: &OverlappedWaker<T>,
+) -> Poll<IoResult<usize>> {
+    let overlapped_ptr = overlapped.leak();
+    let result = port.submit(
+        handle.as_raw(),
+        overlapped_ptr,
+        overlapped.buffer_mut().as_mut().unwrap(),
+    );
+    if result.is_ready() {
+        // The kernel won't post a completion for this operation.
+        unsafe { OverlappedWakerBase::release(overlapped_ptr) };
+    }
+    result
+}
+
+/// Creates a span for the operation, with the fields recorded when it
+/// completes or is cancelled.
+#[cfg(feature = "tracing")]
+fn span(handle: crate::io_port::RawRes, op: &mut impl OpCodeExt) -> tracing::Span {
+    use tracing::field::Empty;
+
+    tracing::debug_span!(
+        "op",
+        kind = ?op.kind(),
+        handle,
+        len = op.buf_len(),
+        offset = op.offset(),
+        bytes = Empty,
+        error = Empty,
+        latency = Empty,
+        cancelled = false,
+    )
+}
+
+impl<T> IocpFuture<'_, T> {
+    fn result(&self, res: IoResult<usize>) -> BufResult<usize, T> {
         (res, self.overlapped.take_buffer())
     }
 }
 
-impl<T> Future for IocpFuture<'_, T> {
+impl<T: OpCode + OpCodeExt> Future for IocpFuture<'_, T> {
     type Output = BufResult<usize, T>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let this = unsafe { self.get_unchecked_mut() };
+        let this = self.get_mut();
+        #[cfg(feature = "tracing")]
+        let _enter = this.span.enter();
 
-        if let Some(result) = this.result.take() {
-            // We need to set the recent waker.
-            this.overlapped.set_waker(cx.waker().clone());
-            match result {
-                Poll::Pending | Poll::Ready(Ok(())) => {
-                    let overlapped_ptr = this.overlapped.as_ref() as *const _ as *const OVERLAPPED;
-                    let mut transferred = 0;
-                    let res = unsafe {
-                        GetOverlappedResult(
-                            this.handle.as_raw_handle() as _,
-                            overlapped_ptr,
-                            &mut transferred,
-                            0,
-                        )
-                    };
-                    if matches!(result, Poll::Pending) && res == 0 {
-                        let error = unsafe { GetLastError() };
-                        match error {
-                            ERROR_IO_INCOMPLETE => {
-                                cx.waker().wake_by_ref();
-                                this.result = Some(Poll::Pending);
-                                Poll::Pending
-                            }
-                            ERROR_HANDLE_EOF => Poll::Ready(this.result(Ok(0))),
-                            _ => Poll::Ready(
-                                this.result(Err(IoError::from_raw_os_error(error as _))),
-                            ),
-                        }
-                    } else {
-                        let err = this.overlapped.take_err();
-                        match err {
-                            None => {
-                                let transferred = transferred as usize;
-                                Poll::Ready(this.result(Ok(transferred)))
-                            }
-                            Some(err) => Poll::Ready(this.result(Err(err))),
+        if let Some(ticket) = this.queued {
+            if let Some(kind) = this.registration.poll_cancelled(cx) {
+                // The operation is never submitted.
+                this.queued = None;
+                this.interrupted = Some(kind);
+                IO_PORT.with(|port| port.dequeue(ticket));
+                this.result = Some(Poll::Ready(Err(cancel::cancelled(kind))));
+            } else if IO_PORT.with(|port| port.poll_granted(ticket, cx.waker())) {
+                this.queued = None;
+                let result = IO_PORT.with(|port| submit(port, &this.handle, &this.overlapped));
+                this.inline = result.is_ready();
+                this.result = Some(result);
+            } else {
+                return Poll::Pending;
+            }
+        }
+        let res = match this.result.take() {
+            Some(Poll::Ready(res)) => {
+                if std::mem::take(&mut this.inline) && IO_PORT.with(|port| port.complete_inline()) {
+                    // Too many operations completed inline in a row, and the
+                    // task yields to let others run.
+                    this.result = Some(Poll::Ready(res));
+                    cx.waker().wake_by_ref();
+                    return Poll::Pending;
+                }
+                res
+            }
+            Some(Poll::Pending) => {
+                if this.interrupted.is_none() {
+                    this.interrupted = this.registration.poll_cancelled(cx);
+                    if this.interrupted.is_some() {
+                        this.registration.clear();
+                        let overlapped_ptr = this.overlapped.as_ptr();
+                        if !this.overlapped.has_result() {
+                            IO_PORT
+                                .with(|port| port.cancel_op(this.handle.as_raw(), overlapped_ptr));
                         }
                     }
                 }
-                Poll::Ready(Err(e)) => Poll::Ready(this.result(Err(e))),
+                if let Some(res) = this.overlapped.take_result() {
+                    res
+                } else {
+                    // We need to set the recent waker, which is woken by the
+                    // driver when the operation completes.
+                    this.overlapped.set_waker(cx.waker().clone());
+                    this.result = Some(Poll::Pending);
+                    return Poll::Pending;
+                }
             }
-        } else {
-            unreachable!()
+            None => unreachable!(),
+        };
+        this.registration.clear();
+        let res = match (res, this.interrupted) {
+            (Err(_), Some(kind)) => Err(cancel::cancelled(kind)),
+            (res, _) => res,
+        };
+        if !this.delay.is_zero() {
+            let delay = std::mem::take(&mut this.delay);
+            this.sleep = Some(time::sleep(delay));
+        }
+        if let Some(sleep) = &mut this.sleep {
+            if Pin::new(sleep).poll(cx).is_pending() {
+                this.result = Some(Poll::Ready(res));
+                return Poll::Pending;
+            }
+        }
+        let latency = this.start.elapsed();
+        IO_PORT.with(|port| port.metrics().complete(this.kind, &res, latency));
+        #[cfg(feature = "tracing")]
+        {
+            match &res {
+                Ok(n) => this.span.record("bytes", n),
+                Err(e) => this.span.record("error", tracing::field::display(e)),
+            };
+            this.span.record("latency", tracing::field::debug(latency));
         }
+        Poll::Ready(this.result(res))
     }
 }
 
 impl<T> Drop for IocpFuture<'_, T> {
     fn drop(&mut self) {
-        if let Some(Poll::Pending) = self.result.take() {
-            self.overlapped.take_waker();
-            unsafe {
-                CancelIoEx(
-                    self.handle.as_raw_handle() as _,
-                    self.overlapped.as_ref() as *const _ as *const OVERLAPPED,
-                )
-            };
+        if let Some(result) = self.result.take() {
+            #[cfg(feature = "tracing")]
+            self.span.record("cancelled", true);
+            if result.is_pending() {
+                self.overlapped.take_waker();
+            }
+            IO_PORT
+                .try_with(|port| {
+                    if let Some(ticket) = self.queued {
+                        // The operation is never submitted.
+                        port.dequeue(ticket);
+                    } else if result.is_pending() && !self.overlapped.has_result() {
+                        let overlapped_ptr = self.overlapped.as_ptr();
+                        // Only the operations in flight are counted, but not
+                        // the completed ones whose results are not taken.
+                        if port.cancel(self.handle.as_raw(), overlapped_ptr) {
+                            port.metrics().cancel(self.kind);
+                        }
+                    }
+                })
+                .ok();
         }
     }
 }
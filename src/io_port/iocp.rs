@@ -0,0 +1,523 @@
+use crate::{io_port::OverlappedWakerBase, *};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashSet,
+    os::windows::io::{
+        AsRawHandle, AsRawSocket, BorrowedHandle, BorrowedSocket, HandleOrNull, IntoRawHandle,
+        OwnedHandle, OwnedSocket,
+    },
+    ptr::null_mut,
+    sync::{Arc, OnceLock},
+    task::Poll,
+    time::{Duration, Instant},
+};
+use windows_sys::Win32::{
+    Foundation::{
+        CloseHandle, RtlNtStatusToDosError, ERROR_HANDLE_EOF, ERROR_NO_DATA, ERROR_PIPE_CONNECTED,
+        FILETIME, HANDLE, INVALID_HANDLE_VALUE, NTSTATUS,
+    },
+    Networking::WinSock::{
+        getsockopt, WSAEnumProtocolsW, WSAGetLastError, SOL_SOCKET, SO_PROTOCOL_INFOW, WSAENOTSOCK,
+        WSANOTINITIALISED, WSAPROTOCOL_INFOW, XP1_IFS_HANDLES,
+    },
+    Storage::FileSystem::SetFileCompletionNotificationModes,
+    System::{
+        Threading::{
+            CloseThreadpoolTimer, CreateThreadpoolTimer, SetThreadpoolTimer,
+            WaitForThreadpoolTimerCallbacks, INFINITE, PTP_CALLBACK_INSTANCE, PTP_TIMER,
+        },
+        IO::{
+            CancelIoEx, CreateIoCompletionPort, GetQueuedCompletionStatusEx,
+            PostQueuedCompletionStatus, OVERLAPPED, OVERLAPPED_ENTRY,
+        },
+    },
+};
+
+pub type RawRes = usize;
+
+// `FileReplaceCompletionInformation` of `FILE_INFORMATION_CLASS`.
+const FILE_REPLACE_COMPLETION_INFORMATION: i32 = 61;
+
+// The flags of `SetFileCompletionNotificationModes`.
+const FILE_SKIP_COMPLETION_PORT_ON_SUCCESS: u8 = 1;
+const FILE_SKIP_SET_EVENT_ON_HANDLE: u8 = 2;
+
+#[repr(C)]
+struct IoStatusBlock {
+    status: usize,
+    information: usize,
+}
+
+#[repr(C)]
+struct FileCompletionInformation {
+    port: HANDLE,
+    key: usize,
+}
+
+#[link(name = "ntdll")]
+extern "system" {
+    fn NtSetInformationFile(
+        handle: HANDLE,
+        block: *mut IoStatusBlock,
+        info: *mut std::ffi::c_void,
+        len: u32,
+        class: i32,
+    ) -> NTSTATUS;
+}
+
+pub enum BorrowedRes<'a> {
+    Handle(BorrowedHandle<'a>),
+    Socket(BorrowedSocket<'a>),
+}
+
+impl BorrowedRes<'_> {
+    pub fn as_raw(&self) -> RawRes {
+        match self {
+            Self::Handle(h) => h.as_raw_handle() as _,
+            Self::Socket(h) => h.as_raw_socket() as _,
+        }
+    }
+}
+
+impl<'a> From<BorrowedHandle<'a>> for BorrowedRes<'a> {
+    fn from(h: BorrowedHandle<'a>) -> Self {
+        Self::Handle(h)
+    }
+}
+
+impl<'a> From<BorrowedSocket<'a>> for BorrowedRes<'a> {
+    fn from(h: BorrowedSocket<'a>) -> Self {
+        Self::Socket(h)
+    }
+}
+
+/// An owned handle which could be attached to the driver.
+pub trait AsRawRes {
+    fn as_raw_res(&self) -> RawRes;
+}
+
+impl AsRawRes for OwnedHandle {
+    fn as_raw_res(&self) -> RawRes {
+        self.as_raw_handle() as _
+    }
+}
+
+impl AsRawRes for OwnedSocket {
+    fn as_raw_res(&self) -> RawRes {
+        self.as_raw_socket() as _
+    }
+}
+
+/// An overlapped operation.
+pub trait OpCode {
+    /// Starts the operation with the `OVERLAPPED` pointer.
+    ///
+    /// Returns [`Poll::Pending`] if the operation is pending, and the result
+    /// if it completes synchronously. A completion packet is still posted for
+    /// a successful one, unless the handle skips it.
+    ///
+    /// # Safety
+    ///
+    /// `optr` should be valid until the completion packet is dequeued.
+    unsafe fn operate(&mut self, handle: RawRes, optr: *mut OVERLAPPED) -> Poll<IoResult<usize>>;
+}
+
+/// The completion key posted by [`Signal::post`].
+const SIGNAL_KEY: usize = usize::MAX - 1;
+
+/// The completion key posted when the timer expires.
+const TIMER_KEY: usize = usize::MAX - 2;
+
+/// The port shared with other threads, which post the signal to wake the
+/// driver.
+#[derive(Debug, Clone)]
+pub struct Signal(Arc<OwnedHandle>);
+
+impl Signal {
+    pub fn post(&self) -> IoResult<()> {
+        let res = unsafe {
+            PostQueuedCompletionStatus(self.0.as_raw_handle() as _, 0, SIGNAL_KEY, null_mut())
+        };
+        if res == 0 {
+            Err(IoError::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// Closes the handle, and returns the result.
+pub fn close_handle(handle: OwnedHandle) -> IoResult<()> {
+    let res = unsafe { CloseHandle(handle.into_raw_handle() as _) };
+    if res == 0 {
+        Err(IoError::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// The driver of IOCP.
+///
+/// An IOCP handle could not be waited by the Tokio IO driver, so the port is
+/// dequeued by the runtime thread when it parks, see [`Driver::poll`]. Other
+/// threads wake it by posting a packet to the port.
+pub struct Driver {
+    port: Signal,
+    entries: RefCell<Vec<OVERLAPPED_ENTRY>>,
+    // The handles skipping the completion packets on synchronous success.
+    skipping: RefCell<HashSet<RawRes>>,
+    // Whether the handles attached later may skip the completion packets.
+    skip_on_success: Cell<bool>,
+    // The completions to handle, which is reused by every poll.
+    completions: RefCell<Vec<(usize, IoResult<usize>)>>,
+    notified: Cell<bool>,
+    timer: PTP_TIMER,
+}
+
+impl Driver {
+    pub fn new() -> IoResult<Self> {
+        let port = unsafe { CreateIoCompletionPort(INVALID_HANDLE_VALUE, 0, 0, 0) };
+        let port = OwnedHandle::try_from(unsafe { HandleOrNull::from_raw_handle(port as _) })
+            .map_err(|_| IoError::last_os_error())?;
+        // The timer posts a packet to the port when it expires. The port
+        // outlives the timer, which is closed first on drop.
+        let timer = unsafe {
+            CreateThreadpoolTimer(Some(timer_callback), port.as_raw_handle(), std::ptr::null())
+        };
+        if timer == 0 {
+            return Err(IoError::last_os_error());
+        }
+        Ok(Self {
+            port: Signal(Arc::new(port)),
+            entries: RefCell::new(Vec::new()),
+            skipping: RefCell::new(HashSet::new()),
+            skip_on_success: Cell::new(true),
+            completions: RefCell::new(Vec::new()),
+            notified: Cell::new(false),
+            timer,
+        })
+    }
+
+    /// Arms the thread pool timer, which posts a packet to the port at the
+    /// deadline.
+    pub fn set_timer(&self, deadline: Instant) -> IoResult<()> {
+        // A negative due time is relative, in 100 nanoseconds.
+        let due = deadline
+            .saturating_duration_since(Instant::now())
+            .as_nanos()
+            / 100;
+        let due = -(due.clamp(1, i64::MAX as u128) as i64);
+        let due = FILETIME {
+            dwLowDateTime: due as u32,
+            dwHighDateTime: (due >> 32) as u32,
+        };
+        unsafe { SetThreadpoolTimer(self.timer, &due, 0, 0) };
+        Ok(())
+    }
+
+    fn as_raw_handle(&self) -> HANDLE {
+        self.port.0.as_raw_handle() as _
+    }
+
+    /// The signal to wake the driver from other threads.
+    pub fn signal(&self) -> &Signal {
+        &self.port
+    }
+
+    /// Whether the signal is received since the last call.
+    pub fn take_notified(&self) -> bool {
+        self.notified.replace(false)
+    }
+
+    /// Sets whether the handles attached later skip the completion packets of
+    /// the operations completing synchronously.
+    pub fn set_skip_on_success(&self, skip: bool) {
+        self.skip_on_success.set(skip);
+    }
+
+    /// Associates the handle with the port.
+    ///
+    /// The handle skips the completion packets of the operations completing
+    /// synchronously if it is enabled and safe, see [`can_skip`]. The mode could not be
+    /// reverted, so it stays enabled if the handle is attached again.
+    pub fn attach(&self, handle: RawRes) -> IoResult<()> {
+        let port = unsafe { CreateIoCompletionPort(handle as isize, self.as_raw_handle(), 0, 0) };
+        if port == 0 {
+            return Err(IoError::last_os_error());
+        }
+        let skip = self.skip_on_success.get()
+            && can_skip(handle)
+            && unsafe {
+                SetFileCompletionNotificationModes(
+                    handle as _,
+                    FILE_SKIP_COMPLETION_PORT_ON_SUCCESS | FILE_SKIP_SET_EVENT_ON_HANDLE,
+                )
+            } != 0;
+        let mut skipping = self.skipping.borrow_mut();
+        // The handle value may be reused by a new resource.
+        if skip {
+            skipping.insert(handle);
+        } else {
+            skipping.remove(&handle);
+        }
+        Ok(())
+    }
+
+    /// Removes the association of the handle with the port, which is supported
+    /// since Windows 8.1.
+    pub fn detach(&self, handle: RawRes) -> IoResult<()> {
+        self.skipping.borrow_mut().remove(&handle);
+        let mut block = IoStatusBlock {
+            status: 0,
+            information: 0,
+        };
+        let mut info = FileCompletionInformation { port: 0, key: 0 };
+        let status = unsafe {
+            NtSetInformationFile(
+                handle as _,
+                &mut block,
+                std::ptr::addr_of_mut!(info).cast(),
+                std::mem::size_of_val(&info) as _,
+                FILE_REPLACE_COMPLETION_INFORMATION,
+            )
+        };
+        if status < 0 {
+            Err(IoError::from_raw_os_error(
+                unsafe { RtlNtStatusToDosError(status) } as _,
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Forgets the handle, which is about to be closed.
+    pub fn forget(&self, handle: RawRes) {
+        self.skipping.borrow_mut().remove(&handle);
+    }
+
+    pub fn submit(
+        &self,
+        handle: RawRes,
+        overlapped_ptr: *const OverlappedWakerBase,
+        op: &mut impl OpCode,
+    ) -> Poll<IoResult<usize>> {
+        match unsafe { op.operate(handle, overlapped_ptr as *mut OVERLAPPED) } {
+            // The completion packet is still posted.
+            Poll::Ready(Ok(_)) if !self.skipping.borrow().contains(&handle) => Poll::Pending,
+            Poll::Ready(Err(e)) => Poll::Ready(sync_failure(e)),
+            res => res,
+        }
+    }
+
+    pub fn cancel(
+        &self,
+        handle: RawRes,
+        overlapped_ptr: *const OverlappedWakerBase,
+    ) -> Option<IoResult<usize>> {
+        unsafe { CancelIoEx(handle as _, overlapped_ptr as *const OVERLAPPED) };
+        None
+    }
+
+    /// Dequeues at most `batch` completion packets, waiting up to `timeout`
+    /// for the first one, and handles the completions.
+    ///
+    /// Returns the count of the completions handled.
+    pub fn poll(
+        &self,
+        timeout: Duration,
+        batch: usize,
+        mut f: impl FnMut(*const OverlappedWakerBase, IoResult<usize>),
+    ) -> usize {
+        // The completions are copied out, so that the driver is not borrowed
+        // when calling `f`.
+        let mut completions = std::mem::take(&mut *self.completions.borrow_mut());
+        {
+            let mut entries = self.entries.borrow_mut();
+            dequeue(
+                self.as_raw_handle(),
+                &mut entries,
+                batch,
+                timeout_ms(timeout),
+            );
+            if entries
+                .iter()
+                .any(|entry| entry.lpCompletionKey == SIGNAL_KEY)
+            {
+                self.notified.set(true);
+            }
+            // The packets of the signal and the timer have no `OVERLAPPED`.
+            completions.extend(
+                entries
+                    .iter()
+                    .filter(|entry| !entry.lpOverlapped.is_null())
+                    .map(completion),
+            );
+        }
+        let len = completions.len();
+        for (overlapped_ptr, res) in completions.drain(..) {
+            f(overlapped_ptr as *const OverlappedWakerBase, res);
+        }
+        *self.completions.borrow_mut() = completions;
+        len
+    }
+}
+
+impl Drop for Driver {
+    fn drop(&mut self) {
+        unsafe {
+            SetThreadpoolTimer(self.timer, std::ptr::null(), 0, 0);
+            WaitForThreadpoolTimerCallbacks(self.timer, 1);
+            CloseThreadpoolTimer(self.timer);
+        }
+    }
+}
+
+unsafe extern "system" fn timer_callback(
+    _instance: PTP_CALLBACK_INSTANCE,
+    port: *mut std::ffi::c_void,
+    _timer: PTP_TIMER,
+) {
+    PostQueuedCompletionStatus(port as _, 0, TIMER_KEY, null_mut());
+}
+
+/// Whether the completion packets of the operations completing synchronously
+/// could be skipped for the handle.
+///
+/// A socket created by a layered service provider which doesn't return IFS
+/// handles may never post the completion packet of an operation completing
+/// synchronously, or post it anyway. So it is skipped only if the provider
+/// and all layers of its protocol chain are IFS providers. Other handles are
+/// always safe.
+fn can_skip(handle: RawRes) -> bool {
+    let mut info: WSAPROTOCOL_INFOW = unsafe { std::mem::zeroed() };
+    let mut len = std::mem::size_of_val(&info) as i32;
+    let res = unsafe {
+        getsockopt(
+            handle,
+            SOL_SOCKET,
+            SO_PROTOCOL_INFOW,
+            std::ptr::addr_of_mut!(info).cast(),
+            &mut len,
+        )
+    };
+    if res != 0 {
+        // Winsock is not initialized if no socket is ever created.
+        return matches!(
+            unsafe { WSAGetLastError() },
+            WSAENOTSOCK | WSANOTINITIALISED
+        );
+    }
+    if info.dwServiceFlags1 & XP1_IFS_HANDLES == 0 {
+        return false;
+    }
+    // A base provider has a chain of length 1, and the entries of a layered
+    // chain are listed.
+    let chain = &info.ProtocolChain;
+    if chain.ChainLen <= 1 {
+        return true;
+    }
+    let len = (chain.ChainLen as usize).min(chain.ChainEntries.len());
+    chain.ChainEntries[..len].iter().all(|id| {
+        protocols()
+            .iter()
+            .find(|protocol| protocol.dwCatalogEntryId == *id)
+            .is_some_and(|protocol| protocol.dwServiceFlags1 & XP1_IFS_HANDLES != 0)
+    })
+}
+
+/// The installed protocols, enumerated once. It is empty if the enumeration
+/// fails, and no layered socket is treated as safe then.
+fn protocols() -> &'static [WSAPROTOCOL_INFOW] {
+    static PROTOCOLS: OnceLock<Vec<WSAPROTOCOL_INFOW>> = OnceLock::new();
+
+    PROTOCOLS.get_or_init(|| {
+        let mut protocols = Vec::<WSAPROTOCOL_INFOW>::new();
+        // The length in bytes is returned if the buffer is too small.
+        let mut len = 0u32;
+        loop {
+            let res =
+                unsafe { WSAEnumProtocolsW(std::ptr::null(), protocols.as_mut_ptr(), &mut len) };
+            if res >= 0 {
+                unsafe { protocols.set_len(res as usize) };
+                break protocols;
+            }
+            let needed = len as usize / std::mem::size_of::<WSAPROTOCOL_INFOW>();
+            if needed <= protocols.capacity() {
+                break Vec::new();
+            }
+            protocols.reserve_exact(needed);
+        }
+    })
+}
+
+/// Dequeues at most `batch` completion packets into `entries`, waiting up to
+/// `timeout` milliseconds for the first one.
+fn dequeue(port: HANDLE, entries: &mut Vec<OVERLAPPED_ENTRY>, batch: usize, timeout: u32) -> bool {
+    entries.clear();
+    entries.reserve(batch);
+    let mut removed = 0;
+    let res = unsafe {
+        GetQueuedCompletionStatusEx(
+            port,
+            entries.as_mut_ptr(),
+            batch.min(u32::MAX as usize) as _,
+            &mut removed,
+            timeout,
+            0,
+        )
+    };
+    if res != 0 {
+        unsafe { entries.set_len(removed as _) };
+    }
+    res != 0
+}
+
+/// Converts the timeout to milliseconds, rounded up so that a short timeout
+/// doesn't return immediately.
+fn timeout_ms(timeout: Duration) -> u32 {
+    timeout
+        .as_nanos()
+        .div_ceil(1_000_000)
+        .try_into()
+        .unwrap_or(INFINITE - 1)
+        .min(INFINITE - 1)
+}
+
+/// Maps the error of an operation failing synchronously. Some errors mean
+/// that it succeeded without transferring any byte.
+fn sync_failure(error: IoError) -> IoResult<usize> {
+    match error.raw_os_error().map(|code| code as u32) {
+        Some(ERROR_HANDLE_EOF | ERROR_PIPE_CONNECTED | ERROR_NO_DATA) => Ok(0),
+        _ => Err(error),
+    }
+}
+
+/// Gets the result of a completion packet.
+fn completion(entry: &OVERLAPPED_ENTRY) -> (usize, IoResult<usize>) {
+    let overlapped_ptr = entry.lpOverlapped;
+    let transferred = entry.dwNumberOfBytesTransferred;
+    // The status of the operation is stored in `Internal`.
+    let status = unsafe { (*overlapped_ptr).Internal } as NTSTATUS;
+    let res = if status >= 0 {
+        Ok(transferred as _)
+    } else {
+        let error = unsafe { RtlNtStatusToDosError(status) };
+        match error {
+            ERROR_HANDLE_EOF => Ok(transferred as _),
+            _ => Err(IoError::from_raw_os_error(error as _)),
+        }
+    };
+    (overlapped_ptr as usize, res)
+}
+
+impl std::fmt::Debug for Driver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Driver")
+            .field("port", &self.port.0)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,516 @@
+use crate::{
+    io_port::{
+        sys::{BlockingCall, Interest, OpCode, RawRes, Signal},
+        OverlappedWakerBase,
+    },
+    *,
+};
+use std::{
+    cell::{Cell, RefCell},
+    collections::{HashMap, HashSet, VecDeque},
+    os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+    sync::{Arc, Mutex},
+    task::Poll,
+    time::{Duration, Instant},
+};
+use tokio::runtime::Handle;
+
+/// Operations waiting for the readiness of one file descriptor.
+#[derive(Debug, Default)]
+struct WaitQueue {
+    read: VecDeque<*const OverlappedWakerBase>,
+    write: VecDeque<*const OverlappedWakerBase>,
+}
+
+impl WaitQueue {
+    fn queue(&mut self, interest: Interest) -> &mut VecDeque<*const OverlappedWakerBase> {
+        match interest {
+            Interest::Readable => &mut self.read,
+            Interest::Writable => &mut self.write,
+        }
+    }
+}
+
+/// The operations completed on the blocking pool, and the eventfd posted
+/// when any is pushed.
+#[derive(Debug)]
+struct Completions {
+    signal: Signal,
+    // The addresses of the states of the operations, which are only turned
+    // into pointers on the driver thread.
+    completed: Mutex<Vec<(usize, IoResult<usize>)>>,
+}
+
+impl Completions {
+    fn push(&self, id: usize, res: IoResult<usize>) {
+        self.completed.lock().unwrap().push((id, res));
+        self.signal.post().ok();
+    }
+
+    /// Takes at most `max` completions. The signal is posted again if there
+    /// are more.
+    fn take(&self, max: usize) -> Vec<(usize, IoResult<usize>)> {
+        // Reset before taking, so that a completion pushed after is signaled
+        // again.
+        self.signal.reset();
+        let mut completed = self.completed.lock().unwrap();
+        if completed.len() > max {
+            self.signal.post().ok();
+            completed.drain(..max).collect()
+        } else {
+            std::mem::take(&mut *completed)
+        }
+    }
+}
+
+/// An operation on a regular file, which runs on the blocking pool.
+///
+/// The result is pushed when it is dropped, so that the operation completes
+/// even if the pool is shut down before running it.
+#[derive(Debug)]
+struct BlockingOp {
+    // The address of the state of the operation, which is not dereferenced.
+    id: usize,
+    call: BlockingCall,
+    // A duplicate, in case the file descriptor is closed and the value is
+    // reused while the operation runs.
+    fd: OwnedFd,
+    res: Option<IoResult<usize>>,
+    completions: Arc<Completions>,
+}
+
+impl BlockingOp {
+    fn run(mut self) {
+        // The buffer is kept by the state until the result is pushed.
+        self.res = Some(unsafe { self.call.run(self.fd.as_raw_fd()) });
+    }
+}
+
+impl Drop for BlockingOp {
+    fn drop(&mut self) {
+        let res = self
+            .res
+            .take()
+            .unwrap_or_else(|| Err(IoError::from_raw_os_error(libc::ECANCELED)));
+        self.completions.push(self.id, res);
+    }
+}
+
+/// A driver emulating the completion model on top of epoll.
+///
+/// An operation is tried with a non-blocking syscall when submitted. If it
+/// would block, it is queued and retried when the file descriptor is ready.
+/// All file descriptors are registered edge-triggered when attached.
+///
+/// Regular files don't support epoll, and their syscalls always block, so the
+/// operations on them run on the blocking pool of the runtime.
+#[derive(Debug)]
+pub struct Driver {
+    epoll: OwnedFd,
+    events: RefCell<Vec<libc::epoll_event>>,
+    // The queues are kept until the file descriptor is detached, so that
+    // waiting for the readiness doesn't allocate.
+    waiting: RefCell<HashMap<RawRes, WaitQueue>>,
+    // The original flags of the file descriptors, on which `O_NONBLOCK` is
+    // set when attached.
+    flags: RefCell<HashMap<RawRes, i32>>,
+    // The attached file descriptors which don't support epoll.
+    files: RefCell<HashSet<RawRes>>,
+    // The operations on them, spawned to the blocking pool by the next poll,
+    // when they are no longer borrowed by the submitter.
+    offloading: RefCell<Vec<BlockingOp>>,
+    completions: Arc<Completions>,
+    pool: RefCell<Option<Handle>>,
+    signal: Signal,
+    notified: Cell<bool>,
+    timer: OwnedFd,
+}
+
+impl Driver {
+    pub fn new(signal: Signal) -> IoResult<Self> {
+        let epoll = unsafe { libc::epoll_create1(libc::EPOLL_CLOEXEC) };
+        if epoll < 0 {
+            return Err(IoError::last_os_error());
+        }
+        let epoll = unsafe { OwnedFd::from_raw_fd(epoll) };
+        let timer = unsafe {
+            libc::timerfd_create(
+                libc::CLOCK_MONOTONIC,
+                libc::TFD_CLOEXEC | libc::TFD_NONBLOCK,
+            )
+        };
+        if timer < 0 {
+            return Err(IoError::last_os_error());
+        }
+        let driver = Self {
+            epoll,
+            events: RefCell::new(Vec::new()),
+            waiting: RefCell::new(HashMap::new()),
+            flags: RefCell::new(HashMap::new()),
+            files: RefCell::new(HashSet::new()),
+            offloading: RefCell::new(Vec::new()),
+            completions: Arc::new(Completions {
+                signal: Signal::new()?,
+                completed: Mutex::new(Vec::new()),
+            }),
+            pool: RefCell::new(None),
+            signal,
+            notified: Cell::new(false),
+            timer: unsafe { OwnedFd::from_raw_fd(timer) },
+        };
+        // They are only readable.
+        driver.register(driver.signal.as_raw_fd(), libc::EPOLLIN)?;
+        driver.register(driver.timer.as_raw_fd(), libc::EPOLLIN)?;
+        driver.register(driver.completions.signal.as_raw_fd(), libc::EPOLLIN)?;
+        Ok(driver)
+    }
+
+    pub fn signal(&self) -> &Signal {
+        &self.signal
+    }
+
+    pub fn take_notified(&self) -> bool {
+        self.notified.replace(false)
+    }
+
+    /// Sets the blocking pool running the operations on regular files. The
+    /// pool of the current Tokio runtime is used if it is `None`.
+    pub fn set_blocking_pool(&self, pool: Option<Handle>) {
+        *self.pool.borrow_mut() = pool;
+    }
+
+    pub fn attach(&self, fd: RawRes) -> IoResult<()> {
+        match self.register(fd, libc::EPOLLIN | libc::EPOLLOUT | libc::EPOLLRDHUP) {
+            Err(error) => match error.raw_os_error() {
+                // Regular files don't support epoll.
+                Some(libc::EPERM) => {
+                    self.files.borrow_mut().insert(fd);
+                    Ok(())
+                }
+                Some(libc::EEXIST) => Ok(()),
+                _ => Err(error),
+            },
+            Ok(()) => {
+                if let Err(error) = self.set_nonblocking(fd) {
+                    self.deregister(fd).ok();
+                    return Err(error);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Sets `O_NONBLOCK` on the file descriptor, and remembers the original
+    /// flags if it is not set.
+    ///
+    /// The flag is shared by all duplicates of the file descriptor, so it is
+    /// restored when the file descriptor is detached.
+    fn set_nonblocking(&self, fd: RawRes) -> IoResult<()> {
+        let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+        if flags < 0 {
+            return Err(IoError::last_os_error());
+        }
+        if flags & libc::O_NONBLOCK == 0 {
+            if unsafe { libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) } < 0 {
+                return Err(IoError::last_os_error());
+            }
+            self.flags.borrow_mut().insert(fd, flags);
+        }
+        Ok(())
+    }
+
+    /// Registers the file descriptor edge-triggered.
+    fn register(&self, fd: RawFd, events: i32) -> IoResult<()> {
+        let mut event = libc::epoll_event {
+            events: (events | libc::EPOLLET) as _,
+            u64: fd as _,
+        };
+        let res =
+            unsafe { libc::epoll_ctl(self.epoll.as_raw_fd(), libc::EPOLL_CTL_ADD, fd, &mut event) };
+        if res < 0 {
+            Err(IoError::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn detach(&self, fd: RawRes) -> IoResult<()> {
+        self.waiting.borrow_mut().remove(&fd);
+        self.files.borrow_mut().remove(&fd);
+        if let Some(flags) = self.flags.borrow_mut().remove(&fd) {
+            unsafe { libc::fcntl(fd, libc::F_SETFL, flags) };
+        }
+        if let Err(error) = self.deregister(fd) {
+            match error.raw_os_error() {
+                // Regular files are not registered.
+                Some(libc::EPERM) | Some(libc::ENOENT) => {}
+                _ => return Err(error),
+            }
+        }
+        Ok(())
+    }
+
+    fn deregister(&self, fd: RawFd) -> IoResult<()> {
+        let res = unsafe {
+            libc::epoll_ctl(
+                self.epoll.as_raw_fd(),
+                libc::EPOLL_CTL_DEL,
+                fd,
+                std::ptr::null_mut(),
+            )
+        };
+        if res < 0 {
+            Err(IoError::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn submit(
+        &self,
+        handle: RawRes,
+        overlapped_ptr: *const OverlappedWakerBase,
+        op: &mut impl OpCode,
+    ) -> Poll<IoResult<usize>> {
+        if self.files.borrow().contains(&handle) {
+            return self.offload(handle, overlapped_ptr, op);
+        }
+        let interest = op.interest();
+        let mut waiting = self.waiting.borrow_mut();
+        let queue = waiting.entry(handle).or_default().queue(interest);
+        // Keep the order of operations waiting for the same readiness.
+        if queue.is_empty() {
+            if let Poll::Ready(res) = op.operate(handle) {
+                return Poll::Ready(res);
+            }
+        }
+        queue.push_back(overlapped_ptr);
+        Poll::Pending
+    }
+
+    /// Queues the operation on a regular file, which is spawned to the
+    /// blocking pool by the next poll.
+    fn offload(
+        &self,
+        handle: RawRes,
+        overlapped_ptr: *const OverlappedWakerBase,
+        op: &mut impl OpCode,
+    ) -> Poll<IoResult<usize>> {
+        let Some(call) = op.blocking_call() else {
+            return Poll::Ready(Err(std::io::ErrorKind::Unsupported.into()));
+        };
+        let fd = unsafe { libc::fcntl(handle, libc::F_DUPFD_CLOEXEC, 0) };
+        if fd < 0 {
+            return Poll::Ready(Err(IoError::last_os_error()));
+        }
+        // Wake the driver to spawn it.
+        if let Err(error) = self.completions.signal.post() {
+            drop(unsafe { OwnedFd::from_raw_fd(fd) });
+            return Poll::Ready(Err(error));
+        }
+        self.offloading.borrow_mut().push(BlockingOp {
+            id: overlapped_ptr as usize,
+            call,
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+            res: None,
+            completions: self.completions.clone(),
+        });
+        Poll::Pending
+    }
+
+    /// Spawns the queued operations to the blocking pool, or runs them inline
+    /// if there is no pool.
+    fn spawn_offloading(&self) {
+        let ops = std::mem::take(&mut *self.offloading.borrow_mut());
+        if ops.is_empty() {
+            return;
+        }
+        let pool = self
+            .pool
+            .borrow()
+            .clone()
+            .or_else(|| Handle::try_current().ok());
+        for op in ops {
+            match &pool {
+                Some(pool) => drop(pool.spawn_blocking(move || op.run())),
+                None => op.run(),
+            }
+        }
+    }
+
+    /// Removes the operation waiting for the readiness. No readiness event
+    /// will come for it, so it is cancelled immediately.
+    ///
+    /// The operations on regular files are not interrupted, and complete by
+    /// the next poll.
+    pub fn cancel(
+        &self,
+        handle: RawRes,
+        overlapped_ptr: *const OverlappedWakerBase,
+    ) -> Option<IoResult<usize>> {
+        // The queued operation is not run, and completes with an error by the
+        // next poll.
+        let op = {
+            let mut offloading = self.offloading.borrow_mut();
+            let index = offloading
+                .iter()
+                .position(|op| op.id == overlapped_ptr as usize);
+            index.map(|index| offloading.swap_remove(index))
+        };
+        if op.is_some() {
+            return None;
+        }
+        let mut cancelled = false;
+        {
+            let mut waiting = self.waiting.borrow_mut();
+            if let Some(queues) = waiting.get_mut(&handle) {
+                for queue in [&mut queues.read, &mut queues.write] {
+                    if let Some(index) = queue.iter().position(|ptr| *ptr == overlapped_ptr) {
+                        queue.remove(index);
+                        cancelled = true;
+                    }
+                }
+            }
+        }
+        cancelled.then(|| Err(IoError::from_raw_os_error(libc::ECANCELED)))
+    }
+
+    /// Arms the timerfd, which is readable at the deadline.
+    pub fn set_timer(&self, deadline: Instant) -> IoResult<()> {
+        // A zero value disarms the timer.
+        let timeout = deadline
+            .saturating_duration_since(Instant::now())
+            .max(Duration::from_nanos(1));
+        let value = libc::itimerspec {
+            it_interval: libc::timespec {
+                tv_sec: 0,
+                tv_nsec: 0,
+            },
+            it_value: libc::timespec {
+                tv_sec: timeout.as_secs() as _,
+                tv_nsec: timeout.subsec_nanos() as _,
+            },
+        };
+        let res = unsafe {
+            libc::timerfd_settime(self.timer.as_raw_fd(), 0, &value, std::ptr::null_mut())
+        };
+        if res < 0 {
+            Err(IoError::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Retries the operations waiting for the readiness until one would block.
+    fn retry(
+        &self,
+        fd: RawRes,
+        interest: Interest,
+        f: &mut impl FnMut(*const OverlappedWakerBase, IoResult<usize>),
+    ) {
+        loop {
+            let ptr = match self.waiting.borrow_mut().get_mut(&fd) {
+                Some(queues) => match queues.queue(interest).front() {
+                    Some(ptr) => *ptr,
+                    None => break,
+                },
+                None => break,
+            };
+            match unsafe { OverlappedWakerBase::operate(ptr, fd) } {
+                Poll::Ready(res) => {
+                    self.waiting
+                        .borrow_mut()
+                        .get_mut(&fd)
+                        .unwrap()
+                        .queue(interest)
+                        .pop_front();
+                    // The driver should not be borrowed when calling `f`.
+                    f(ptr, res);
+                }
+                Poll::Pending => break,
+            }
+        }
+    }
+
+    /// The epoll instance is readable when any readiness event is ready.
+    pub fn as_raw_fd(&self) -> RawFd {
+        self.epoll.as_raw_fd()
+    }
+
+    /// Receives at most `batch` readiness events, waiting up to `timeout` for
+    /// the first one, and retries the operations waiting for them.
+    ///
+    /// Returns the count of the events received, where each completion on the
+    /// blocking pool counts as one.
+    pub fn poll(
+        &self,
+        timeout: Duration,
+        batch: usize,
+        mut f: impl FnMut(*const OverlappedWakerBase, IoResult<usize>),
+    ) -> usize {
+        self.spawn_offloading();
+        let len = {
+            let mut events = self.events.borrow_mut();
+            events.clear();
+            events.reserve(batch);
+            let res = unsafe {
+                libc::epoll_wait(
+                    self.epoll.as_raw_fd(),
+                    events.as_mut_ptr(),
+                    batch.min(i32::MAX as usize) as _,
+                    timeout_ms(timeout),
+                )
+            };
+            let len = if res < 0 { 0 } else { res as usize };
+            unsafe { events.set_len(len) };
+            len
+        };
+        let mut count = len;
+        for i in 0..len {
+            // The events should not be borrowed when retrying the operations.
+            let (fd, flags) = {
+                let event = self.events.borrow()[i];
+                (event.u64 as RawRes, event.events as i32)
+            };
+            if fd == self.signal.as_raw_fd() {
+                self.notified.set(true);
+                self.signal.reset();
+                continue;
+            }
+            if fd == self.completions.signal.as_raw_fd() {
+                let completed = self.completions.take(batch);
+                count = count - 1 + completed.len();
+                for (id, res) in completed {
+                    f(id as *const OverlappedWakerBase, res);
+                }
+                continue;
+            }
+            if fd == self.timer.as_raw_fd() {
+                // Resets the expiration count.
+                let mut count: u64 = 0;
+                unsafe {
+                    libc::read(
+                        fd,
+                        std::ptr::addr_of_mut!(count).cast(),
+                        std::mem::size_of_val(&count),
+                    )
+                };
+                continue;
+            }
+            let closed = flags & (libc::EPOLLERR | libc::EPOLLHUP) != 0;
+            if closed || flags & (libc::EPOLLIN | libc::EPOLLRDHUP) != 0 {
+                self.retry(fd, Interest::Readable, &mut f);
+            }
+            if closed || flags & libc::EPOLLOUT != 0 {
+                self.retry(fd, Interest::Writable, &mut f);
+            }
+        }
+        count
+    }
+}
+
+/// Converts the timeout to milliseconds, rounding up.
+fn timeout_ms(timeout: Duration) -> i32 {
+    let ms = timeout.as_nanos().div_ceil(1_000_000);
+    ms.try_into().unwrap_or(i32::MAX)
+}
@@ -0,0 +1,213 @@
+use crate::{
+    io_port::{
+        sys::{OpCode, RawRes, Signal},
+        OverlappedWakerBase,
+    },
+    *,
+};
+use io_uring::{
+    opcode::{AsyncCancel, PollAdd, Timeout, TimeoutRemove},
+    squeue::Entry,
+    types::{Fd, Timespec},
+    IoUring,
+};
+use std::{
+    cell::{Cell, RefCell},
+    os::fd::{AsRawFd, RawFd},
+    task::Poll,
+    time::{Duration, Instant},
+};
+
+/// The entry count of the submission queue.
+const ENTRIES: u32 = 1024;
+
+/// The `user_data` of entries whose completions are ignored, e.g. cancellation.
+const IGNORED_USER_DATA: u64 = 0;
+
+/// The `user_data` of the poll entry of the signal.
+const SIGNAL_USER_DATA: u64 = 1;
+
+/// The `user_data` of the timeout entry armed by [`Driver::set_timer`].
+const TIMER_USER_DATA: u64 = 2;
+
+pub struct Driver {
+    ring: RefCell<IoUring>,
+    signal: Signal,
+    notified: Cell<bool>,
+    // The timespec of the timer entry, which may stay in the submission queue
+    // until the next poll.
+    timespec: Box<Cell<Timespec>>,
+    // The count of the timer entries not completed, including the removed
+    // ones.
+    timers: Cell<usize>,
+}
+
+impl Driver {
+    pub fn new(signal: Signal) -> IoResult<Self> {
+        let driver = Self {
+            ring: RefCell::new(IoUring::new(ENTRIES)?),
+            signal,
+            notified: Cell::new(false),
+            timespec: Box::new(Cell::new(Timespec::new())),
+            timers: Cell::new(0),
+        };
+        driver.poll_signal()?;
+        Ok(driver)
+    }
+
+    /// Waits for the signal to be readable.
+    fn poll_signal(&self) -> IoResult<()> {
+        let entry = PollAdd::new(Fd(self.signal.as_raw_fd()), libc::POLLIN as _)
+            .build()
+            .user_data(SIGNAL_USER_DATA);
+        self.push(entry)
+    }
+
+    pub fn signal(&self) -> &Signal {
+        &self.signal
+    }
+
+    pub fn take_notified(&self) -> bool {
+        self.notified.replace(false)
+    }
+
+    pub fn attach(&self, _fd: RawRes) -> IoResult<()> {
+        Ok(())
+    }
+
+    fn push(&self, entry: Entry) -> IoResult<()> {
+        let mut ring = self.ring.borrow_mut();
+        if ring.submission().is_full() {
+            ring.submit()?;
+        }
+        unsafe { ring.submission().push(&entry) }
+            .map_err(|_| IoError::other("submission queue is full"))?;
+        // If the submission fails, the entry stays in the queue and will be
+        // submitted on the next poll.
+        ring.submit().ok();
+        Ok(())
+    }
+
+    pub fn submit(
+        &self,
+        handle: RawRes,
+        overlapped_ptr: *const OverlappedWakerBase,
+        op: &mut impl OpCode,
+    ) -> Poll<IoResult<usize>> {
+        let entry = op.create_entry(handle).user_data(overlapped_ptr as _);
+        match self.push(entry) {
+            Ok(()) => Poll::Pending,
+            Err(e) => Poll::Ready(Err(e)),
+        }
+    }
+
+    pub fn cancel(
+        &self,
+        _handle: RawRes,
+        overlapped_ptr: *const OverlappedWakerBase,
+    ) -> Option<IoResult<usize>> {
+        let entry = AsyncCancel::new(overlapped_ptr as _)
+            .build()
+            .user_data(IGNORED_USER_DATA);
+        self.push(entry).ok();
+        None
+    }
+
+    /// Submits a timeout entry completing at the deadline, which replaces the
+    /// previous one. The entry is ignored when reaped, but the ring is
+    /// readable then.
+    pub fn set_timer(&self, deadline: Instant) -> IoResult<()> {
+        if self.timers.get() > 0 {
+            // The entries are handled in order, so the previous one is removed
+            // rather than the new one. It fails if the previous one completes.
+            let entry = TimeoutRemove::new(TIMER_USER_DATA)
+                .build()
+                .user_data(IGNORED_USER_DATA);
+            self.push(entry)?;
+        }
+        self.timespec.set(Timespec::from(
+            deadline.saturating_duration_since(Instant::now()),
+        ));
+        let entry = Timeout::new(unsafe { &*self.timespec.as_ptr() })
+            .build()
+            .user_data(TIMER_USER_DATA);
+        self.push(entry)?;
+        self.timers.set(self.timers.get() + 1);
+        Ok(())
+    }
+
+    /// The ring is readable when the completion queue is not empty.
+    pub fn as_raw_fd(&self) -> RawFd {
+        self.ring.borrow().as_raw_fd()
+    }
+
+    /// Reaps at most `batch` completion entries, waiting up to `timeout` for
+    /// the first one.
+    pub fn poll(
+        &self,
+        timeout: Duration,
+        batch: usize,
+        mut f: impl FnMut(*const OverlappedWakerBase, IoResult<usize>),
+    ) -> usize {
+        {
+            let mut ring = self.ring.borrow_mut();
+            if !timeout.is_zero() && ring.completion().is_empty() {
+                // The timeout completes when any other entry completes.
+                let timespec = Timespec::from(timeout);
+                let entry = Timeout::new(&timespec)
+                    .count(1)
+                    .build()
+                    .user_data(IGNORED_USER_DATA);
+                if ring.submission().is_full() {
+                    ring.submit().ok();
+                }
+                // The timespec is copied when the entry is submitted.
+                if unsafe { ring.submission().push(&entry) }.is_ok() {
+                    ring.submit_and_wait(1).ok();
+                } else {
+                    ring.submit().ok();
+                }
+            } else {
+                ring.submit().ok();
+            }
+        }
+        let mut reaped = 0;
+        while reaped < batch {
+            // The ring should not be borrowed when calling `f`.
+            let entry = self.ring.borrow_mut().completion().next();
+            match entry {
+                Some(entry) => {
+                    if entry.user_data() == IGNORED_USER_DATA {
+                        continue;
+                    }
+                    if entry.user_data() == TIMER_USER_DATA {
+                        self.timers.set(self.timers.get() - 1);
+                        continue;
+                    }
+                    if entry.user_data() == SIGNAL_USER_DATA {
+                        self.notified.set(true);
+                        self.signal.reset();
+                        self.poll_signal().ok();
+                        continue;
+                    }
+                    let res = entry.result();
+                    let res = if res < 0 {
+                        Err(IoError::from_raw_os_error(-res))
+                    } else {
+                        Ok(res as _)
+                    };
+                    f(entry.user_data() as _, res);
+                    reaped += 1;
+                }
+                None => break,
+            }
+        }
+        reaped
+    }
+}
+
+impl std::fmt::Debug for Driver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Driver").finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,330 @@
+mod epoll;
+mod iour;
+
+use crate::{io_port::OverlappedWakerBase, runtime::DriverKind, *};
+use io_uring::squeue::Entry;
+use std::{
+    os::fd::{AsRawFd, BorrowedFd, FromRawFd, IntoRawFd, OwnedFd, RawFd},
+    sync::Arc,
+    task::Poll,
+    time::{Duration, Instant},
+};
+
+pub type RawRes = RawFd;
+
+pub struct BorrowedRes<'a>(BorrowedFd<'a>);
+
+impl BorrowedRes<'_> {
+    pub fn as_raw(&self) -> RawRes {
+        self.0.as_raw_fd()
+    }
+}
+
+impl<'a> From<BorrowedFd<'a>> for BorrowedRes<'a> {
+    fn from(fd: BorrowedFd<'a>) -> Self {
+        Self(fd)
+    }
+}
+
+/// An owned handle which could be attached to the driver.
+pub trait AsRawRes {
+    fn as_raw_res(&self) -> RawRes;
+}
+
+impl AsRawRes for OwnedFd {
+    fn as_raw_res(&self) -> RawRes {
+        self.as_raw_fd()
+    }
+}
+
+/// An eventfd watched by the driver, which is posted by other threads to wake
+/// it.
+#[derive(Debug, Clone)]
+pub struct Signal(Arc<OwnedFd>);
+
+impl Signal {
+    fn new() -> IoResult<Self> {
+        let fd = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC | libc::EFD_NONBLOCK) };
+        if fd < 0 {
+            return Err(IoError::last_os_error());
+        }
+        Ok(Self(Arc::new(unsafe { OwnedFd::from_raw_fd(fd) })))
+    }
+
+    pub fn post(&self) -> IoResult<()> {
+        let value: u64 = 1;
+        let res = unsafe {
+            libc::write(
+                self.as_raw_fd(),
+                std::ptr::addr_of!(value).cast(),
+                std::mem::size_of_val(&value),
+            )
+        };
+        if res < 0 {
+            let error = IoError::last_os_error();
+            // The counter is full, and the eventfd is readable anyway.
+            if error.kind() != std::io::ErrorKind::WouldBlock {
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+
+    /// Resets the counter after the signal is received.
+    fn reset(&self) {
+        let mut value: u64 = 0;
+        unsafe {
+            libc::read(
+                self.as_raw_fd(),
+                std::ptr::addr_of_mut!(value).cast(),
+                std::mem::size_of_val(&value),
+            )
+        };
+    }
+
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// Closes the file descriptor, and returns the result.
+pub fn close_handle(fd: OwnedFd) -> IoResult<()> {
+    let res = unsafe { libc::close(fd.into_raw_fd()) };
+    if res < 0 {
+        Err(IoError::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// The readiness an operation waits for when it would block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interest {
+    Readable,
+    Writable,
+}
+
+/// An operation which could be driven by io_uring or epoll.
+pub trait OpCode {
+    /// Creates the submission entry of the operation.
+    ///
+    /// The pointers in the entry should point into `self`, which is pinned
+    /// until the completion entry is reaped.
+    fn create_entry(&mut self, fd: RawRes) -> Entry;
+
+    /// The readiness to wait for if [`OpCode::operate`] would block.
+    fn interest(&self) -> Interest;
+
+    /// Performs the operation with a non-blocking syscall.
+    ///
+    /// Returns [`Poll::Pending`] if the operation would block.
+    fn operate(&mut self, fd: RawRes) -> Poll<IoResult<usize>>;
+
+    /// The blocking syscall performing the operation on a regular file, which
+    /// runs on the blocking pool with the epoll driver.
+    ///
+    /// Returns `None` if the operation is not supported on regular files.
+    fn blocking_call(&mut self) -> Option<BlockingCall> {
+        None
+    }
+}
+
+/// A blocking syscall on a regular file, with the buffer of the operation.
+///
+/// It only holds the pointer and the length of the buffer, and is taken on the
+/// thread of the driver, so that the state of the operation is never touched by
+/// the blocking pool.
+#[derive(Debug, Clone, Copy)]
+pub enum BlockingCall {
+    /// `pread` into the buffer.
+    Read {
+        ptr: *mut u8,
+        len: usize,
+        pos: usize,
+    },
+    /// `pwrite` from the buffer.
+    Write {
+        ptr: *const u8,
+        len: usize,
+        pos: usize,
+    },
+}
+
+// The buffer is owned by the state of the operation, which is neither moved nor
+// accessed by the driver thread until the operation completes, the same as a
+// buffer passed to io_uring.
+unsafe impl Send for BlockingCall {}
+
+impl BlockingCall {
+    /// Performs the syscall on the file descriptor.
+    ///
+    /// # Safety
+    ///
+    /// The buffer should be valid and not accessed by others until it returns.
+    pub unsafe fn run(self, fd: RawFd) -> IoResult<usize> {
+        let res = match self {
+            Self::Read { ptr, len, pos } => libc::pread(fd, ptr as _, len, pos as _),
+            Self::Write { ptr, len, pos } => libc::pwrite(fd, ptr as _, len, pos as _),
+        };
+        if res < 0 {
+            Err(IoError::last_os_error())
+        } else {
+            Ok(res as _)
+        }
+    }
+}
+
+/// The environment variable to choose the driver.
+///
+/// The only recognized value is `epoll`, which forces the epoll driver.
+const DRIVER_ENV: &str = "TOKIO_IOCP_DRIVER";
+
+/// The driver on Linux.
+///
+/// The io_uring driver is preferred. If io_uring is not available, e.g., disabled
+/// by seccomp, the driver falls back to epoll.
+#[derive(Debug)]
+pub enum Driver {
+    IoUring(iour::Driver),
+    Epoll(epoll::Driver),
+}
+
+impl Driver {
+    pub fn new() -> IoResult<Self> {
+        Self::with_signal(None, Signal::new()?)
+    }
+
+    /// Creates the driver of `kind` watching the signal, or the preferred one
+    /// if `kind` is `None`.
+    pub fn with_signal(kind: Option<DriverKind>, signal: Signal) -> IoResult<Self> {
+        let kind = kind.or_else(|| {
+            std::env::var_os(DRIVER_ENV)
+                .is_some_and(|driver| driver == "epoll")
+                .then_some(DriverKind::Epoll)
+        });
+        match kind {
+            Some(DriverKind::IoUring) => Ok(Self::IoUring(iour::Driver::new(signal)?)),
+            Some(DriverKind::Epoll) => Ok(Self::Epoll(epoll::Driver::new(signal)?)),
+            None => match iour::Driver::new(signal.clone()) {
+                Ok(driver) => Ok(Self::IoUring(driver)),
+                Err(_) => Ok(Self::Epoll(epoll::Driver::new(signal)?)),
+            },
+        }
+    }
+
+    pub fn kind(&self) -> DriverKind {
+        match self {
+            Self::IoUring(_) => DriverKind::IoUring,
+            Self::Epoll(_) => DriverKind::Epoll,
+        }
+    }
+
+    /// Sets the blocking pool running the operations which could not be
+    /// driven by the driver.
+    pub fn set_blocking_pool(&self, pool: Option<tokio::runtime::Handle>) {
+        match self {
+            Self::IoUring(_) => {}
+            Self::Epoll(driver) => driver.set_blocking_pool(pool),
+        }
+    }
+
+    pub fn attach(&self, fd: RawRes) -> IoResult<()> {
+        match self {
+            Self::IoUring(driver) => driver.attach(fd),
+            Self::Epoll(driver) => driver.attach(fd),
+        }
+    }
+
+    pub fn detach(&self, fd: RawRes) -> IoResult<()> {
+        match self {
+            Self::IoUring(_) => Ok(()),
+            Self::Epoll(driver) => driver.detach(fd),
+        }
+    }
+
+    /// Forgets the file descriptor, which is about to be closed.
+    pub fn forget(&self, fd: RawRes) {
+        match self {
+            Self::IoUring(_) => {}
+            // The registration outlives the file descriptor if it is duplicated.
+            Self::Epoll(driver) => {
+                driver.detach(fd).ok();
+            }
+        }
+    }
+
+    pub fn submit(
+        &self,
+        handle: RawRes,
+        overlapped_ptr: *const OverlappedWakerBase,
+        op: &mut impl OpCode,
+    ) -> Poll<IoResult<usize>> {
+        match self {
+            Self::IoUring(driver) => driver.submit(handle, overlapped_ptr, op),
+            Self::Epoll(driver) => driver.submit(handle, overlapped_ptr, op),
+        }
+    }
+
+    /// Cancels the operation.
+    ///
+    /// Returns the result if the operation is cancelled immediately, and
+    /// won't be completed by [`Driver::poll`].
+    pub fn cancel(
+        &self,
+        handle: RawRes,
+        overlapped_ptr: *const OverlappedWakerBase,
+    ) -> Option<IoResult<usize>> {
+        match self {
+            Self::IoUring(driver) => driver.cancel(handle, overlapped_ptr),
+            Self::Epoll(driver) => driver.cancel(handle, overlapped_ptr),
+        }
+    }
+
+    /// The file descriptor which is readable when completions are ready.
+    pub fn as_raw_fd(&self) -> RawFd {
+        match self {
+            Self::IoUring(driver) => driver.as_raw_fd(),
+            Self::Epoll(driver) => driver.as_raw_fd(),
+        }
+    }
+
+    pub fn poll(
+        &self,
+        timeout: Duration,
+        batch: usize,
+        f: impl FnMut(*const OverlappedWakerBase, IoResult<usize>),
+    ) -> usize {
+        match self {
+            Self::IoUring(driver) => driver.poll(timeout, batch, f),
+            Self::Epoll(driver) => driver.poll(timeout, batch, f),
+        }
+    }
+
+    /// Arms the timer of the driver, which wakes it at the deadline.
+    pub fn set_timer(&self, deadline: Instant) -> IoResult<()> {
+        match self {
+            Self::IoUring(driver) => driver.set_timer(deadline),
+            Self::Epoll(driver) => driver.set_timer(deadline),
+        }
+    }
+
+    /// The signal to wake the driver from other threads.
+    pub fn signal(&self) -> &Signal {
+        match self {
+            Self::IoUring(driver) => driver.signal(),
+            Self::Epoll(driver) => driver.signal(),
+        }
+    }
+
+    /// Whether the signal is received since the last call.
+    pub fn take_notified(&self) -> bool {
+        match self {
+            Self::IoUring(driver) => driver.take_notified(),
+            Self::Epoll(driver) => driver.take_notified(),
+        }
+    }
+}
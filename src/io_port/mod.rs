@@ -3,78 +3,856 @@ pub use future::IocpFuture;
 
 mod waker;
 
-use crate::*;
-use std::{
-    os::windows::io::{AsRawHandle, HandleOrNull, OwnedHandle},
-    ptr::null_mut,
-    rc::Rc,
+mod ext;
+pub use ext::*;
+
+mod sim;
+
+mod timer;
+
+mod queue;
+
+mod slab;
+
+mod park;
+pub use park::Parker;
+
+mod attached;
+pub use attached::Attached;
+
+#[cfg(windows)]
+mod iocp;
+#[cfg(windows)]
+use iocp as sys;
+
+#[cfg(target_os = "linux")]
+mod linux;
+#[cfg(target_os = "linux")]
+use linux as sys;
+
+pub use sys::{close_handle, BorrowedRes, OpCode, RawRes, Signal};
+#[cfg(target_os = "linux")]
+pub use sys::{BlockingCall, Interest};
+
+use crate::{
+    runtime::{
+        fault::{FaultPolicy, Injection},
+        metrics::IoMetrics,
+        Graveyard,
+    },
+    *,
 };
-use windows_sys::Win32::{
-    Foundation::{GetLastError, ERROR_HANDLE_EOF, INVALID_HANDLE_VALUE, WAIT_TIMEOUT},
-    System::IO::{CreateIoCompletionPort, GetQueuedCompletionStatus},
+use std::{
+    cell::{Cell, Ref, RefCell},
+    collections::{HashMap, HashSet},
+    future::{poll_fn, Future},
+    pin::pin,
+    sync::Arc,
+    task::{Context, Poll, Waker},
+    time::{Duration, Instant},
 };
+use tokio::task::LocalSet;
+use waker::OverlappedWakerBase;
+
+/// The default max count of completions handled by one poll.
+pub const DEFAULT_BATCH_SIZE: usize = 64;
+
+/// The default max count of completions handled before the driver yields, and
+/// of inline completions in a row before an operation yields.
+pub const DEFAULT_COMPLETION_BUDGET: usize = 128;
+
+/// The max time to wait for a completion each time the driver is polled when
+/// shutting down, so that the outstanding operations are checked in time.
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// An operation submitted to the driver and not completed.
+#[derive(Debug)]
+struct Submitted {
+    handle: RawRes,
+    // Whether the future is dropped.
+    dropped: bool,
+}
+
+fn not_attached() -> IoError {
+    IoError::new(
+        std::io::ErrorKind::InvalidInput,
+        "the handle is not attached to the driver of the current thread",
+    )
+}
 
 thread_local! {
     pub static IO_PORT: IoPort = IoPort::new().unwrap();
 }
 
+/// The driver of the current thread.
+///
+/// It wraps an IOCP handle on Windows, and an io_uring instance on Linux,
+/// or an epoll instance if io_uring is not available.
+///
+/// If the simulated driver is enabled, all operations are queued in it instead.
 #[derive(Debug)]
 pub struct IoPort {
-    port: OwnedHandle,
+    driver: RefCell<sys::Driver>,
+    // The signal of the driver, which is kept if the driver is replaced.
+    signal: Signal,
+    parker: Parker,
+    // The waker of the future passed to Tokio, if the thread blocks on the
+    // driver when it parks.
+    park_waker: RefCell<Option<Waker>>,
+    // Whether the driver is polled when the thread parks, since the future
+    // passed to Tokio is polled.
+    parked: Cell<bool>,
+    sim: RefCell<Option<sim::Driver>>,
+    batch_size: Cell<usize>,
+    budget: Cell<usize>,
+    // The budget left before the driver yields.
+    remaining: Cell<usize>,
+    // The count of operations completed inline in a row.
+    inline_streak: Cell<usize>,
+    max_in_flight: Cell<usize>,
+    queue: RefCell<queue::SubmitQueue>,
+    fault_policy: RefCell<Option<FaultPolicy>>,
+    handle_fault_policies: RefCell<HashMap<RawRes, FaultPolicy>>,
+    // The handles attached to this driver.
+    attached: RefCell<HashSet<RawRes>>,
+    metrics: RefCell<Arc<IoMetrics>>,
+    ops: RefCell<HashMap<*const OverlappedWakerBase, Submitted>>,
+    slab: RefCell<slab::OpSlab>,
+    graveyard: RefCell<Option<Graveyard>>,
+    blocking_pool: RefCell<Option<tokio::runtime::Handle>>,
+    // The count of the submitted operations whose futures are dropped.
+    cancelling: Cell<usize>,
+    drain_wakers: RefCell<Vec<Waker>>,
+    // The tasks waiting for the operations on the handles to close.
+    close_wakers: RefCell<Vec<(RawRes, Waker)>>,
+    // The tasks waiting for the signal of the driver.
+    notify_wakers: RefCell<Vec<Waker>>,
+    timers: RefCell<timer::Timers>,
+    // The deadline the timer of the driver is armed with.
+    armed: Cell<Option<Instant>>,
 }
 
 impl IoPort {
     pub fn new() -> IoResult<Self> {
-        let port = unsafe { CreateIoCompletionPort(INVALID_HANDLE_VALUE, 0, 0, 0) };
-        let port = OwnedHandle::try_from(unsafe { HandleOrNull::from_raw_handle(port as _) })
-            .map_err(|_| IoError::last_os_error())?;
-        Ok(Self { port })
+        let driver = sys::Driver::new()?;
+        Ok(Self {
+            signal: driver.signal().clone(),
+            parker: Parker::new(driver.signal().clone()),
+            park_waker: RefCell::new(None),
+            parked: Cell::new(false),
+            driver: RefCell::new(driver),
+            sim: RefCell::new(None),
+            batch_size: Cell::new(DEFAULT_BATCH_SIZE),
+            budget: Cell::new(DEFAULT_COMPLETION_BUDGET),
+            remaining: Cell::new(DEFAULT_COMPLETION_BUDGET),
+            inline_streak: Cell::new(0),
+            max_in_flight: Cell::new(usize::MAX),
+            queue: RefCell::new(queue::SubmitQueue::default()),
+            fault_policy: RefCell::new(None),
+            handle_fault_policies: RefCell::new(HashMap::new()),
+            attached: RefCell::new(HashSet::new()),
+            metrics: RefCell::new(Arc::new(IoMetrics::new())),
+            ops: RefCell::new(HashMap::new()),
+            slab: RefCell::new(slab::OpSlab::default()),
+            graveyard: RefCell::new(None),
+            blocking_pool: RefCell::new(None),
+            cancelling: Cell::new(0),
+            drain_wakers: RefCell::new(vec![]),
+            close_wakers: RefCell::new(vec![]),
+            notify_wakers: RefCell::new(vec![]),
+            timers: RefCell::new(timer::Timers::default()),
+            armed: Cell::new(None),
+        })
+    }
+
+    /// Sets the max count of completions handled by one poll.
+    pub fn set_batch_size(&self, size: usize) {
+        self.batch_size.set(size);
+    }
+
+    /// Sets whether the handles attached later skip the completion port on
+    /// synchronous success.
+    #[cfg(windows)]
+    pub fn set_skip_on_success(&self, skip: bool) {
+        self.driver.borrow().set_skip_on_success(skip);
+    }
+
+    /// Sets the max count of completions handled before the driver yields.
+    pub fn set_completion_budget(&self, budget: usize) {
+        self.budget.set(budget);
+        self.remaining.set(budget);
+    }
+
+    /// Refills the budget, after the driver yields or the thread parks.
+    fn refill(&self) {
+        self.remaining.set(self.budget.get());
+    }
+
+    /// Counts an operation completed inline, i.e., without waiting.
+    ///
+    /// Returns `true` if the budget of completions in a row is exhausted, and
+    /// the task should yield.
+    pub fn complete_inline(&self) -> bool {
+        let streak = self.inline_streak.get() + 1;
+        if streak >= self.budget.get() {
+            self.inline_streak.set(0);
+            true
+        } else {
+            self.inline_streak.set(streak);
+            false
+        }
+    }
+
+    /// Sets the max count of the operations in flight on this thread.
+    pub fn set_max_in_flight(&self, max: usize) {
+        self.max_in_flight.set(max);
+        self.grant();
+    }
+
+    /// Queues an operation if the operations in flight reach the limit, or
+    /// other operations are queued.
+    ///
+    /// Returns the ticket of the operation if it is queued.
+    pub fn enqueue(&self) -> Option<u64> {
+        let mut queue = self.queue.borrow_mut();
+        if queue.len() == 0 && self.ops.borrow().len() + queue.granted() < self.max_in_flight.get()
+        {
+            return None;
+        }
+        self.metrics().enqueue();
+        Some(queue.push())
+    }
+
+    /// Checks if the queued operation is granted a slot, which it should be
+    /// submitted with then, and registers the waker otherwise.
+    pub fn poll_granted(&self, ticket: u64, waker: &Waker) -> bool {
+        self.queue.borrow_mut().poll_granted(ticket, waker)
+    }
+
+    /// Removes a queued operation, which is not submitted.
+    pub fn dequeue(&self, ticket: u64) {
+        if self.queue.borrow_mut().remove(ticket) {
+            self.metrics().dequeue();
+        }
+        self.grant();
+    }
+
+    /// Grants the free slots to the queued operations.
+    fn grant(&self) {
+        loop {
+            let in_flight = self.ops.borrow().len() + self.queue.borrow().granted();
+            if in_flight >= self.max_in_flight.get() {
+                break;
+            }
+            let granted = self.queue.borrow_mut().grant();
+            match granted {
+                Some(waker) => {
+                    self.metrics().dequeue();
+                    if let Some(waker) = waker {
+                        waker.wake();
+                    }
+                }
+                None => break,
+            }
+        }
+    }
+
+    /// Sets the metrics of the runtime, which are updated by this thread.
+    pub fn set_metrics(&self, metrics: Arc<IoMetrics>) {
+        *self.metrics.borrow_mut() = metrics;
+    }
+
+    /// The metrics updated by this thread.
+    pub fn metrics(&self) -> Ref<'_, Arc<IoMetrics>> {
+        self.metrics.borrow()
+    }
+
+    /// Sets the callback receiving the buffers of the operations whose futures
+    /// are dropped.
+    pub fn set_graveyard(&self, graveyard: Option<Graveyard>) {
+        *self.graveyard.borrow_mut() = graveyard;
+    }
+
+    /// Sets the blocking pool of the runtime, which is shared by all threads
+    /// of the runtime.
+    pub fn set_blocking_pool(&self, pool: Option<tokio::runtime::Handle>) {
+        #[cfg(target_os = "linux")]
+        self.driver.borrow().set_blocking_pool(pool.clone());
+        *self.blocking_pool.borrow_mut() = pool;
+    }
+
+    /// The blocking pool of the runtime.
+    pub fn blocking_pool(&self) -> Option<tokio::runtime::Handle> {
+        self.blocking_pool.borrow().clone()
+    }
+
+    /// Enables the simulated driver of the runtime with the seed, or disables
+    /// it if `sim` is `None`.
+    ///
+    /// The simulated driver is kept if it is enabled by the same runtime. The
+    /// operations queued in it are leaked when it is replaced or disabled.
+    pub fn set_sim(&self, sim: Option<(u64, u64)>) {
+        let mut current = self.sim.borrow_mut();
+        match sim {
+            Some((runtime, seed)) => {
+                if current.as_ref().map(|sim| sim.runtime()) != Some(runtime) {
+                    *current = Some(sim::Driver::new(runtime, seed));
+                }
+            }
+            None => *current = None,
+        }
+    }
+
+    /// The simulated driver, if enabled.
+    pub fn sim(&self) -> Option<Ref<'_, sim::Driver>> {
+        Ref::filter_map(self.sim.borrow(), Option::as_ref).ok()
+    }
+
+    /// Completes the operation of `id` queued in the simulated driver.
+    pub fn complete_sim(&self, id: u64, res: IoResult<usize>) -> IoResult<()> {
+        let overlapped_ptr = self.sim().ok_or_else(sim_disabled)?.remove(id)?;
+        unsafe { self.complete(overlapped_ptr, res) };
+        Ok(())
+    }
+
+    /// Completes an operation submitted to the driver.
+    ///
+    /// # Safety
+    ///
+    /// See [`OverlappedWakerBase::complete`].
+    unsafe fn complete(&self, overlapped_ptr: *const OverlappedWakerBase, res: IoResult<usize>) {
+        let op = self.ops.borrow_mut().remove(&overlapped_ptr);
+        self.metrics().finish_io();
+        if let Some(op) = &op {
+            self.wake_closing(op.handle);
+        }
+        if op.is_some_and(|op| op.dropped) {
+            self.metrics().finish_cancel();
+            let graveyard = self.graveyard.borrow().clone();
+            if let Some(graveyard) = graveyard {
+                if let Some(buffer) = OverlappedWakerBase::into_buffer(overlapped_ptr) {
+                    graveyard.bury(buffer);
+                }
+            }
+            self.cancelling.set(self.cancelling.get() - 1);
+            if self.cancelling.get() == 0 {
+                let wakers = std::mem::take(&mut *self.drain_wakers.borrow_mut());
+                wakers.into_iter().for_each(Waker::wake);
+            }
+        }
+        OverlappedWakerBase::complete(overlapped_ptr, res);
+        self.grant();
+    }
+
+    /// Checks if all operations whose futures are dropped have completed, and
+    /// registers the waker otherwise.
+    fn poll_drained(&self, cx: &mut Context<'_>) -> Poll<()> {
+        if self.cancelling.get() == 0 {
+            return Poll::Ready(());
+        }
+        let mut wakers = self.drain_wakers.borrow_mut();
+        if !wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+            wakers.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+
+    /// Checks if all operations on the handle have completed, and registers
+    /// the waker otherwise.
+    fn poll_closed(&self, handle: RawRes, cx: &mut Context<'_>) -> Poll<()> {
+        if !self.ops.borrow().values().any(|op| op.handle == handle) {
+            return Poll::Ready(());
+        }
+        let mut wakers = self.close_wakers.borrow_mut();
+        if !wakers
+            .iter()
+            .any(|(h, waker)| *h == handle && waker.will_wake(cx.waker()))
+        {
+            wakers.push((handle, cx.waker().clone()));
+        }
+        Poll::Pending
     }
 
-    pub fn attach(&self, handle: usize) -> IoResult<()> {
-        let port = unsafe {
-            CreateIoCompletionPort(handle as isize, self.port.as_raw_handle() as _, 0, 0)
+    /// Wakes the tasks closing the handle if no operation on it is left.
+    fn wake_closing(&self, handle: RawRes) {
+        let woken = {
+            let mut wakers = self.close_wakers.borrow_mut();
+            if wakers.is_empty() || self.ops.borrow().values().any(|op| op.handle == handle) {
+                return;
+            }
+            let (woken, rest) = std::mem::take(&mut *wakers)
+                .into_iter()
+                .partition::<Vec<_>, _>(|(h, _)| *h == handle);
+            *wakers = rest;
+            woken
         };
-        if port == 0 {
-            Err(IoError::last_os_error())
+        woken.into_iter().for_each(|(_, waker)| waker.wake());
+    }
+
+    /// Forgets the handle, which is about to be closed, or released by the
+    /// resource owning it.
+    fn forget(&self, handle: RawRes) {
+        self.attached.borrow_mut().remove(&handle);
+        self.handle_fault_policies.borrow_mut().remove(&handle);
+        if self.sim().is_none() {
+            self.driver.borrow().forget(handle);
+        }
+    }
+
+    /// Replaces the driver of the current thread with the one of `kind`, if it
+    /// is different. The signal is kept, and the driver could only be replaced
+    /// if no handle is attached to it.
+    #[cfg(target_os = "linux")]
+    pub fn set_driver(&self, kind: Option<runtime::DriverKind>) -> IoResult<()> {
+        let Some(kind) = kind else {
+            return Ok(());
+        };
+        if self.driver.borrow().kind() == kind {
+            return Ok(());
+        }
+        if !self.attached.borrow().is_empty() || !self.ops.borrow().is_empty() {
+            return Err(IoError::new(
+                std::io::ErrorKind::ResourceBusy,
+                "the driver of the current thread has handles attached",
+            ));
+        }
+        let driver = sys::Driver::with_signal(Some(kind), self.signal.clone())?;
+        driver.set_blocking_pool(self.blocking_pool());
+        *self.driver.borrow_mut() = driver;
+        // The timer of the new driver is not armed.
+        self.armed.set(None);
+        self.arm_timer();
+        Ok(())
+    }
+
+    /// The signal posted by other threads to wake the driver.
+    pub fn signal(&self) -> &Signal {
+        &self.signal
+    }
+
+    /// Registers the waker, which is woken when the signal is received.
+    pub fn register_notified(&self, waker: &Waker) {
+        let mut wakers = self.notify_wakers.borrow_mut();
+        if !wakers.iter().any(|w| w.will_wake(waker)) {
+            wakers.push(waker.clone());
+        }
+    }
+
+    /// Registers a timer waking the waker at the deadline, or updates the
+    /// waker of the timer of `key`. Returns the key of the timer.
+    pub fn register_timer(&self, key: Option<u64>, deadline: Instant, waker: &Waker) -> u64 {
+        let key = self.timers.borrow_mut().insert(key, deadline, waker);
+        self.arm_timer();
+        key
+    }
+
+    pub fn remove_timer(&self, key: u64) {
+        self.timers.borrow_mut().remove(key);
+    }
+
+    /// Arms the timer of the driver with the nearest deadline, if it is
+    /// earlier than the armed one.
+    fn arm_timer(&self) {
+        let next = self.timers.borrow_mut().next_deadline();
+        if let Some(next) = next {
+            if self.armed.get().is_none_or(|armed| next < armed)
+                && self.driver.borrow().set_timer(next).is_ok()
+            {
+                self.armed.set(Some(next));
+            }
+        }
+    }
+
+    /// Wakes the expired timers, and arms the timer of the driver for the
+    /// next one.
+    ///
+    /// Returns `true` if any timer expires.
+    fn expire_timers(&self) -> bool {
+        let now = Instant::now();
+        if self.armed.get().is_some_and(|armed| armed <= now) {
+            self.armed.set(None);
+        }
+        let wakers = self.timers.borrow_mut().expire(now);
+        let expired = !wakers.is_empty();
+        wakers.into_iter().for_each(Waker::wake);
+        self.arm_timer();
+        expired
+    }
+
+    /// Sets the fault policy of the runtime.
+    pub fn set_fault_policy(&self, policy: Option<FaultPolicy>) {
+        *self.fault_policy.borrow_mut() = policy;
+    }
+
+    /// Sets the fault policy of the handle, which overrides the policy of the
+    /// runtime.
+    pub fn set_handle_fault_policy(&self, handle: RawRes, policy: Option<FaultPolicy>) {
+        let mut policies = self.handle_fault_policies.borrow_mut();
+        match policy {
+            Some(policy) => {
+                policies.insert(handle, policy);
+            }
+            None => {
+                policies.remove(&handle);
+            }
+        }
+    }
+
+    /// Decides the faults injected into an operation on the handle.
+    fn inject(&self, handle: RawRes, kind: OpKind) -> Injection {
+        let policies = self.handle_fault_policies.borrow();
+        match policies.get(&handle) {
+            Some(policy) => policy.inject(kind),
+            None => match &*self.fault_policy.borrow() {
+                Some(policy) => policy.inject(kind),
+                None => Injection::default(),
+            },
+        }
+    }
+
+    pub fn attach(&self, handle: RawRes) -> IoResult<()> {
+        // The handle value may be reused by a new resource.
+        self.handle_fault_policies.borrow_mut().remove(&handle);
+        if self.sim().is_none() {
+            self.driver.borrow().attach(handle)?;
+        }
+        self.attached.borrow_mut().insert(handle);
+        Ok(())
+    }
+
+    /// Detaches the handle, which could be attached to the driver of another
+    /// thread then.
+    pub fn detach(&self, handle: RawRes) -> IoResult<()> {
+        if !self.attached.borrow().contains(&handle) {
+            return Err(not_attached());
+        }
+        if self.ops.borrow().values().any(|op| op.handle == handle) {
+            return Err(IoError::new(
+                std::io::ErrorKind::ResourceBusy,
+                "the handle has operations in flight",
+            ));
+        }
+        if self.sim().is_none() {
+            self.driver.borrow().detach(handle)?;
+        }
+        self.attached.borrow_mut().remove(&handle);
+        self.handle_fault_policies.borrow_mut().remove(&handle);
+        Ok(())
+    }
+
+    fn submit(
+        &self,
+        handle: RawRes,
+        overlapped_ptr: *const OverlappedWakerBase,
+        op: &mut (impl OpCode + OpCodeExt),
+    ) -> Poll<IoResult<usize>> {
+        // The completions of a handle attached to another driver are never
+        // received by this one.
+        if !self.attached.borrow().contains(&handle) {
+            return Poll::Ready(Err(not_attached()));
+        }
+        let res = match self.sim() {
+            Some(sim) => sim.submit(handle, overlapped_ptr, op),
+            None => self.driver.borrow().submit(handle, overlapped_ptr, op),
+        };
+        if res.is_pending() {
+            self.inline_streak.set(0);
+            self.ops.borrow_mut().insert(
+                overlapped_ptr,
+                Submitted {
+                    handle,
+                    dropped: false,
+                },
+            );
+            self.metrics().start_io();
         } else {
-            Ok(())
-        }
-    }
-
-    pub fn poll(&self) {
-        let mut transferred = 0;
-        let mut key = 0;
-        let mut overlapped_ptr = null_mut();
-        let res = unsafe {
-            GetQueuedCompletionStatus(
-                self.port.as_raw_handle() as _,
-                &mut transferred,
-                &mut key,
-                &mut overlapped_ptr,
-                0,
-            )
+            // The slot is released if the operation was granted one.
+            self.grant();
+        }
+        res
+    }
+
+    /// Cancels the operation whose future is dropped. The buffer is passed to
+    /// the graveyard when the operation completes.
+    ///
+    /// Returns whether the operation is still in flight.
+    fn cancel(&self, handle: RawRes, overlapped_ptr: *const OverlappedWakerBase) -> bool {
+        let in_flight = match self.ops.borrow_mut().get_mut(&overlapped_ptr) {
+            Some(op) => {
+                if !op.dropped {
+                    op.dropped = true;
+                    self.cancelling.set(self.cancelling.get() + 1);
+                    self.metrics().start_cancel();
+                }
+                true
+            }
+            None => false,
         };
-        let err = if res == 0 {
-            let error = unsafe { GetLastError() };
-            match error {
-                WAIT_TIMEOUT | ERROR_HANDLE_EOF => None,
-                _ => Some(IoError::from_raw_os_error(error as _)),
+        self.cancel_op(handle, overlapped_ptr);
+        in_flight
+    }
+
+    fn cancel_op(&self, handle: RawRes, overlapped_ptr: *const OverlappedWakerBase) {
+        let res = match self.sim() {
+            Some(sim) => {
+                sim.cancel(handle, overlapped_ptr);
+                None
             }
+            None => self.driver.borrow().cancel(handle, overlapped_ptr),
+        };
+        if let Some(res) = res {
+            unsafe { self.complete(overlapped_ptr, res) };
+        }
+    }
+
+    /// Polls the driver without blocking, and handles the completions until
+    /// the budget is exhausted.
+    ///
+    /// Returns `true` if the budget is exhausted, and there may be more
+    /// completions. The budget is refilled then, and the driver should yield
+    /// before the next poll.
+    pub fn poll(&self) -> bool {
+        loop {
+            let batch = self.batch_size.get().min(self.remaining.get());
+            if batch == 0 {
+                self.refill();
+                return true;
+            }
+            if self.poll_timeout(Duration::ZERO, batch) < batch {
+                return false;
+            }
+        }
+    }
+
+    /// Refills the budget, and polls the driver, waiting up to `timeout` for
+    /// the first completion or the nearest timer. It is called when the thread
+    /// parks.
+    pub fn park(&self, timeout: Duration) {
+        self.refill();
+        let batch = self.batch_size.get().min(self.budget.get());
+        let Some(waker) = self.park_waker.borrow().clone() else {
+            self.poll_timeout(timeout, batch);
+            return;
+        };
+        // The thread blocks on the driver instead of Tokio, and is woken by
+        // the signal. It doesn't block if it is woken before.
+        let timeout = if self.parker.park() {
+            timeout
         } else {
-            None
+            Duration::ZERO
         };
-        if let Some(overlapped) =
-            unsafe { overlapped_ptr.cast::<waker::OverlappedWakerBase>().as_ref() }
+        self.poll_timeout(timeout, batch);
+        self.parker.unpark();
+        self.parked.set(true);
+        // Tokio parks the thread after return, until it is woken, and the
+        // driver is not polled then. Wake the future, so that Tokio only polls
+        // its own drivers, runs the woken tasks, and parks again.
+        waker.wake();
+    }
+
+    /// The parker of the thread, which wakes the driver if the thread is
+    /// blocked on it.
+    pub fn parker(&self) -> &Parker {
+        &self.parker
+    }
+
+    /// Polls the driver, waiting up to `timeout` for the first completion or
+    /// the nearest timer, and handles at most `batch` completions.
+    ///
+    /// Returns the count of entries handled by the driver.
+    fn poll_timeout(&self, timeout: Duration, batch: usize) -> usize {
+        let start = Instant::now();
+        loop {
+            let (res, woken) = self.poll_once(timeout.saturating_sub(start.elapsed()), batch);
+            // The driver may be woken without waking any task, e.g., by an
+            // outdated timer, and Tokio would park the thread after return.
+            if woken || start.elapsed() >= timeout {
+                return res;
+            }
+        }
+    }
+
+    /// Polls the driver once, and returns the count of completions handled,
+    /// and whether any task is woken.
+    fn poll_once(&self, timeout: Duration, batch: usize) -> (usize, bool) {
+        let timeout = match self.timers.borrow_mut().next_deadline() {
+            Some(deadline) => timeout.min(deadline.saturating_duration_since(Instant::now())),
+            None => timeout,
+        };
+        let mut completions = 0;
+        // The operations queued in the simulated driver are completed by the
+        // test, but the signal is still received by the driver.
+        let res = self
+            .driver
+            .borrow()
+            .poll(timeout, batch, |overlapped_ptr, res| {
+                completions += 1;
+                unsafe { self.complete(overlapped_ptr, res) }
+            });
+        self.remaining
+            .set(self.remaining.get().saturating_sub(completions));
+        let notified = self.driver.borrow().take_notified();
+        if notified {
+            let wakers = std::mem::take(&mut *self.notify_wakers.borrow_mut());
+            wakers.into_iter().for_each(Waker::wake);
+        }
+        let expired = self.expire_timers();
+        if self.sim().is_none() {
+            self.metrics().poll(completions);
+        }
+        (res, completions > 0 || notified || expired)
+    }
+
+    /// Cancels all operations submitted to the driver, and polls the driver
+    /// until they complete, or the timeout expires. The buffers are released
+    /// when the operations complete.
+    ///
+    /// The operations queued in the simulated driver are completed with an
+    /// error. Returns the count of the operations still outstanding, which
+    /// are leaked.
+    pub fn shutdown(&self, timeout: Duration) -> usize {
+        let deadline = Instant::now() + timeout;
+        let ops = self
+            .ops
+            .borrow()
+            .iter()
+            .map(|(ptr, op)| (*ptr, op.handle))
+            .collect::<Vec<_>>();
+        for (overlapped_ptr, handle) in ops {
+            self.cancel_op(handle, overlapped_ptr);
+        }
+        let queued = self
+            .sim()
+            .map(|sim| sim.with_ops(|ops| ops.iter().map(|op| op.id).collect::<Vec<_>>()));
+        for id in queued.into_iter().flatten() {
+            self.complete_sim(id, Err(IoError::other("the runtime is shut down")))
+                .ok();
+        }
+        while !self.ops.borrow().is_empty() {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            self.poll_timeout(
+                (deadline - now).min(SHUTDOWN_POLL_INTERVAL),
+                self.batch_size.get(),
+            );
+        }
+        self.ops.borrow().len()
+    }
+}
+
+/// Waits for the operations whose futures are dropped on the current thread to
+/// complete.
+pub async fn drain_cancelled() {
+    poll_fn(|cx| IO_PORT.with(|port| port.poll_drained(cx))).await
+}
+
+/// Waits for the operations on the handle to complete, before it is closed.
+///
+/// The operations in flight are the ones whose futures are dropped, which are
+/// cancelled already.
+pub async fn prepare_close(handle: RawRes) {
+    poll_fn(|cx| IO_PORT.with(|port| port.poll_closed(handle, cx))).await
+}
+
+/// The error returned if the simulated driver is not enabled.
+pub fn sim_disabled() -> IoError {
+    IoError::other("the simulated driver is not enabled on the current thread")
+}
+
+/// Runs the future on the Tokio runtime and the local set until it completes.
+///
+/// If `drive` is `true`, the driver of the current thread is woken by the Tokio
+/// runtime when completions are ready, so the thread parks in Tokio, which
+/// waits up to the nearest timer, and is woken by tasks spawned or woken from
+/// other threads.
+///
+/// Otherwise, the thread blocks on the driver when it parks. The waker of the
+/// future is wrapped, so that the tasks woken by other threads wake the driver.
+/// Tokio doesn't park the thread while tasks are ready, so the driver is also
+/// polled without blocking each time the future is polled.
+pub fn block_on<F: Future>(
+    rt: &tokio::runtime::Runtime,
+    local: &LocalSet,
+    future: F,
+    drive: bool,
+) -> F::Output {
+    #[cfg(target_os = "linux")]
+    if drive {
+        return local.block_on(rt, run(future));
+    }
+    #[cfg(windows)]
+    debug_assert!(!drive, "the driver could not be woken by Tokio on Windows");
+
+    struct Guard;
+
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            IO_PORT.try_with(|port| port.park_waker.take()).ok();
+        }
+    }
+
+    let _guard = Guard;
+    let mut future = pin!(local.run_until(future));
+    // The waker of Tokio, and the one wrapping it.
+    let mut wakers: Option<(Waker, Waker)> = None;
+    rt.block_on(poll_fn(|cx| {
+        if !wakers
+            .as_ref()
+            .is_some_and(|(waker, _)| waker.will_wake(cx.waker()))
         {
-            let overlapped = unsafe { Rc::from_raw(overlapped) };
-            if let Some(err) = err {
-                overlapped.set_err(err);
+            let wrapped = IO_PORT.with(|port| {
+                *port.park_waker.borrow_mut() = Some(cx.waker().clone());
+                port.parker.wrap(cx.waker())
+            });
+            wakers = Some((cx.waker().clone(), wrapped));
+        }
+        let (_, wrapped) = wakers.as_ref().unwrap();
+        IO_PORT.with(|port| {
+            if !port.parked.replace(false) {
+                port.poll();
             }
-            let waker = overlapped.take_waker();
-            if let Some(waker) = waker {
-                waker.wake();
+        });
+        future.as_mut().poll(&mut Context::from_waker(wrapped))
+    }))
+}
+
+/// Runs the future, and drives the driver of the current thread.
+#[cfg(target_os = "linux")]
+async fn run<F: Future>(future: F) -> F::Output {
+    let mut driver = pin!(self::drive());
+    let mut drive = true;
+    let mut future = pin!(future);
+    poll_fn(|cx| {
+        if drive {
+            if let Poll::Ready(res) = driver.as_mut().poll(cx) {
+                // The driver could still be polled when the thread parks.
+                drive = false;
+                if let Err(e) = res {
+                    panic!("failed to drive the driver: {e}");
+                }
             }
         }
+        future.as_mut().poll(cx)
+    })
+    .await
+}
+
+/// Waits for the readiness of the driver with the Tokio IO driver, and handles
+/// the completions.
+#[cfg(target_os = "linux")]
+async fn drive() -> IoResult<()> {
+    use tokio::io::{unix::AsyncFd, Interest};
+
+    let fd = IO_PORT.with(|port| port.driver.borrow().as_raw_fd());
+    let fd = AsyncFd::with_interest(fd, Interest::READABLE)?;
+    loop {
+        let mut guard = fd.readable().await?;
+        while IO_PORT.with(|port| port.poll()) {
+            // Let the woken tasks run before exhausting the budget again.
+            tokio::task::yield_now().await;
+        }
+        guard.clear_ready();
     }
 }
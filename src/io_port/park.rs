@@ -0,0 +1,79 @@
+use crate::io_port::Signal;
+use std::{
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc,
+    },
+    task::{Wake, Waker},
+};
+
+const IDLE: u8 = 0;
+const PARKED: u8 = 1;
+const NOTIFIED: u8 = 2;
+
+/// Whether the thread is blocked on the driver when it parks, shared with the
+/// threads waking it.
+///
+/// The driver is blocked instead of Tokio if it is not woken by the Tokio IO
+/// driver, e.g., on Windows. A wakeup is posted to the driver only while it is
+/// blocked, or about to block.
+#[derive(Debug, Clone)]
+pub struct Parker {
+    state: Arc<AtomicU8>,
+    signal: Signal,
+}
+
+impl Parker {
+    pub fn new(signal: Signal) -> Self {
+        Self {
+            state: Arc::new(AtomicU8::new(IDLE)),
+            signal,
+        }
+    }
+
+    /// Marks the thread as blocked on the driver. Returns `false` if it has
+    /// been woken since the last call to [`Parker::unpark`], and it should not
+    /// block.
+    pub fn park(&self) -> bool {
+        self.state
+            .compare_exchange(IDLE, PARKED, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+    }
+
+    /// Marks the thread as running.
+    pub fn unpark(&self) {
+        self.state.store(IDLE, Ordering::Release);
+    }
+
+    /// Wakes the driver if the thread is blocked on it.
+    pub fn notify(&self) {
+        if self.state.swap(NOTIFIED, Ordering::AcqRel) == PARKED {
+            self.signal.post().ok();
+        }
+    }
+
+    /// Wraps the waker of the future passed to Tokio, so that the driver is
+    /// also woken when the future is woken.
+    pub fn wrap(&self, waker: &Waker) -> Waker {
+        Waker::from(Arc::new(ThreadWaker {
+            waker: waker.clone(),
+            parker: self.clone(),
+        }))
+    }
+}
+
+struct ThreadWaker {
+    waker: Waker,
+    parker: Parker,
+}
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.waker.wake_by_ref();
+        self.parker.notify();
+    }
+}
@@ -0,0 +1,78 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    task::Waker,
+};
+
+/// The operations waiting for a slot to be submitted, in FIFO order.
+///
+/// A granted operation holds a slot until it is submitted or dropped. The
+/// removed operations are kept in the order, and skipped when they reach the
+/// front.
+#[derive(Debug, Default)]
+pub struct SubmitQueue {
+    order: VecDeque<u64>,
+    waiting: HashMap<u64, Option<Waker>>,
+    granted: HashSet<u64>,
+    next_ticket: u64,
+}
+
+impl SubmitQueue {
+    /// The count of the operations waiting for a slot.
+    pub fn len(&self) -> usize {
+        self.waiting.len()
+    }
+
+    /// The count of the slots held by the granted operations.
+    pub fn granted(&self) -> usize {
+        self.granted.len()
+    }
+
+    /// Queues an operation, and returns its ticket.
+    pub fn push(&mut self) -> u64 {
+        let ticket = self.next_ticket;
+        self.next_ticket += 1;
+        self.order.push_back(ticket);
+        self.waiting.insert(ticket, None);
+        ticket
+    }
+
+    /// Grants a slot to the operation at the front.
+    ///
+    /// Returns the waker of the operation, which is `None` if it hasn't been
+    /// polled, or `None` if no operation is waiting.
+    pub fn grant(&mut self) -> Option<Option<Waker>> {
+        while let Some(ticket) = self.order.pop_front() {
+            if let Some(waker) = self.waiting.remove(&ticket) {
+                self.granted.insert(ticket);
+                return Some(waker);
+            }
+        }
+        None
+    }
+
+    /// Takes the slot if it is granted, or registers the waker otherwise.
+    pub fn poll_granted(&mut self, ticket: u64, waker: &Waker) -> bool {
+        if self.granted.remove(&ticket) {
+            return true;
+        }
+        if let Some(w) = self.waiting.get_mut(&ticket) {
+            match w {
+                Some(w) if w.will_wake(waker) => {}
+                _ => *w = Some(waker.clone()),
+            }
+        }
+        false
+    }
+
+    /// Removes the operation, and releases its slot if it is granted.
+    ///
+    /// Returns `true` if it is still waiting.
+    pub fn remove(&mut self, ticket: u64) -> bool {
+        if self.waiting.remove(&ticket).is_some() {
+            true
+        } else {
+            self.granted.remove(&ticket);
+            false
+        }
+    }
+}
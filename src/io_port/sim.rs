@@ -0,0 +1,127 @@
+use crate::{
+    io_port::{OpCodeExt, OpKind, OverlappedWakerBase, RawRes},
+    *,
+};
+use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    task::Poll,
+};
+
+/// An operation queued in the simulated driver.
+#[derive(Debug)]
+pub struct Queued {
+    pub id: u64,
+    pub kind: OpKind,
+    pub cancelled: bool,
+    ptr: *const OverlappedWakerBase,
+}
+
+/// A driver which queues the operations instead of executing them.
+///
+/// The operations are completed by the test through [`crate::runtime::sim`].
+#[derive(Debug)]
+pub struct Driver {
+    runtime: u64,
+    ops: RefCell<VecDeque<Queued>>,
+    next_id: Cell<u64>,
+    rng: Cell<u64>,
+}
+
+impl Driver {
+    pub fn new(runtime: u64, seed: u64) -> Self {
+        Self {
+            runtime,
+            ops: RefCell::new(VecDeque::new()),
+            next_id: Cell::new(0),
+            // The state of xorshift should not be zero.
+            rng: Cell::new(seed ^ 0x9E37_79B9_7F4A_7C15),
+        }
+    }
+
+    /// The id of the runtime enabling the driver.
+    pub fn runtime(&self) -> u64 {
+        self.runtime
+    }
+
+    pub fn submit(
+        &self,
+        _handle: RawRes,
+        overlapped_ptr: *const OverlappedWakerBase,
+        op: &mut impl OpCodeExt,
+    ) -> Poll<IoResult<usize>> {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        self.ops.borrow_mut().push_back(Queued {
+            id,
+            kind: op.kind(),
+            cancelled: false,
+            ptr: overlapped_ptr,
+        });
+        Poll::Pending
+    }
+
+    pub fn cancel(&self, _handle: RawRes, overlapped_ptr: *const OverlappedWakerBase) {
+        if let Some(op) = self
+            .ops
+            .borrow_mut()
+            .iter_mut()
+            .find(|op| op.ptr == overlapped_ptr)
+        {
+            op.cancelled = true;
+        }
+    }
+
+    /// Calls `f` with the queued operations, in the order of submission.
+    pub fn with_ops<R>(&self, f: impl FnOnce(&VecDeque<Queued>) -> R) -> R {
+        f(&self.ops.borrow())
+    }
+
+    /// Generates the next pseudo-random number with xorshift64*.
+    pub fn next_random(&self) -> u64 {
+        let mut x = self.rng.get();
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.rng.set(x);
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Calls `f` with the queued operation of `id`.
+    pub fn with_op<R>(&self, id: u64, f: impl FnOnce(&mut dyn OpCodeExt) -> R) -> IoResult<R> {
+        let ptr = self.find(id)?;
+        let mut f = Some(f);
+        let mut res = None;
+        unsafe {
+            OverlappedWakerBase::simulate(ptr, &mut |op| res = Some((f.take().unwrap())(op)))
+        };
+        Ok(res.unwrap())
+    }
+
+    /// Removes the queued operation of `id`, and returns the pointer to
+    /// complete.
+    pub fn remove(&self, id: u64) -> IoResult<*const OverlappedWakerBase> {
+        let mut ops = self.ops.borrow_mut();
+        let index = ops
+            .iter()
+            .position(|op| op.id == id)
+            .ok_or_else(|| not_found(id))?;
+        Ok(ops.remove(index).unwrap().ptr)
+    }
+
+    fn find(&self, id: u64) -> IoResult<*const OverlappedWakerBase> {
+        self.ops
+            .borrow()
+            .iter()
+            .find(|op| op.id == id)
+            .map(|op| op.ptr)
+            .ok_or_else(|| not_found(id))
+    }
+}
+
+fn not_found(id: u64) -> IoError {
+    IoError::new(
+        std::io::ErrorKind::NotFound,
+        format!("no queued operation {id}"),
+    )
+}
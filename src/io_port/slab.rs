@@ -0,0 +1,84 @@
+use crate::io_port::OverlappedWakerBase;
+use std::{alloc::Layout, collections::HashMap, mem::MaybeUninit, ptr::NonNull};
+
+/// The count of the states allocated at once when the slab is full.
+const CHUNK_SIZE: usize = 64;
+
+/// The states of the operations on the current thread, and the blocks storing
+/// their buffers.
+///
+/// The states are allocated in chunks, which are never moved, so that a state
+/// could be passed to the kernel. The released states and blocks are reused by
+/// later operations, and submitting an operation doesn't allocate once the
+/// slab is warm.
+#[derive(Debug, Default)]
+pub struct OpSlab {
+    chunks: Vec<NonNull<[MaybeUninit<OverlappedWakerBase>]>>,
+    free: Vec<NonNull<OverlappedWakerBase>>,
+    // The released blocks, by their layouts.
+    blocks: HashMap<Layout, Vec<NonNull<u8>>>,
+    // The count of the states in use.
+    live: usize,
+}
+
+impl OpSlab {
+    /// Takes an uninitialized state.
+    pub fn alloc(&mut self) -> NonNull<OverlappedWakerBase> {
+        if self.free.is_empty() {
+            let chunk = Box::into_raw(Box::new_uninit_slice(CHUNK_SIZE));
+            let chunk = unsafe { NonNull::new_unchecked(chunk) };
+            let ptr = chunk.cast::<OverlappedWakerBase>();
+            // Reversed, so that the states are taken in order.
+            self.free
+                .extend((0..CHUNK_SIZE).rev().map(|i| unsafe { ptr.add(i) }));
+            self.chunks.push(chunk);
+        }
+        self.live += 1;
+        self.free.pop().unwrap()
+    }
+
+    /// Returns a state, which should have been dropped.
+    pub fn free(&mut self, ptr: NonNull<OverlappedWakerBase>) {
+        self.live -= 1;
+        self.free.push(ptr);
+    }
+
+    /// Takes a block of `layout`.
+    pub fn alloc_block(&mut self, layout: Layout) -> NonNull<u8> {
+        if layout.size() == 0 {
+            // The alignment is a valid address for zero-sized values.
+            return unsafe { NonNull::new_unchecked(layout.align() as *mut u8) };
+        }
+        if let Some(block) = self.blocks.get_mut(&layout).and_then(Vec::pop) {
+            return block;
+        }
+        match NonNull::new(unsafe { std::alloc::alloc(layout) }) {
+            Some(block) => block,
+            None => std::alloc::handle_alloc_error(layout),
+        }
+    }
+
+    /// Returns a block of `layout`, whose value should have been dropped.
+    pub fn free_block(&mut self, block: NonNull<u8>, layout: Layout) {
+        if layout.size() != 0 {
+            self.blocks.entry(layout).or_default().push(block);
+        }
+    }
+}
+
+impl Drop for OpSlab {
+    fn drop(&mut self) {
+        for (layout, blocks) in self.blocks.drain() {
+            for block in blocks {
+                unsafe { std::alloc::dealloc(block.as_ptr(), layout) };
+            }
+        }
+        // The states still in use are referenced by the futures or the kernel,
+        // and they are leaked.
+        if self.live == 0 {
+            for chunk in self.chunks.drain(..) {
+                drop(unsafe { Box::from_raw(chunk.as_ptr()) });
+            }
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    sync::atomic::{AtomicU64, Ordering},
+    task::Waker,
+    time::Instant,
+};
+
+// The keys are unique over all threads, so that a timer moved to another
+// thread is never confused with the ones registered there.
+static NEXT_KEY: AtomicU64 = AtomicU64::new(0);
+
+/// The timers registered on the driver.
+///
+/// The removed timers are kept in the heap, and skipped when they reach the
+/// top.
+#[derive(Debug, Default)]
+pub struct Timers {
+    heap: BinaryHeap<Reverse<(Instant, u64)>>,
+    wakers: HashMap<u64, Waker>,
+}
+
+impl Timers {
+    /// Registers a timer, or updates the waker of the timer of `key`.
+    ///
+    /// Returns the key of the timer.
+    pub fn insert(&mut self, key: Option<u64>, deadline: Instant, waker: &Waker) -> u64 {
+        if let Some(key) = key {
+            if let Some(w) = self.wakers.get_mut(&key) {
+                if !w.will_wake(waker) {
+                    *w = waker.clone();
+                }
+                return key;
+            }
+        }
+        let key = NEXT_KEY.fetch_add(1, Ordering::Relaxed);
+        self.heap.push(Reverse((deadline, key)));
+        self.wakers.insert(key, waker.clone());
+        key
+    }
+
+    pub fn remove(&mut self, key: u64) {
+        self.wakers.remove(&key);
+    }
+
+    /// The deadline of the nearest timer.
+    pub fn next_deadline(&mut self) -> Option<Instant> {
+        while let Some(Reverse((deadline, key))) = self.heap.peek() {
+            if self.wakers.contains_key(key) {
+                return Some(*deadline);
+            }
+            self.heap.pop();
+        }
+        None
+    }
+
+    /// Removes the timers expired at `now`, and returns their wakers.
+    pub fn expire(&mut self, now: Instant) -> Vec<Waker> {
+        let mut wakers = vec![];
+        while let Some(Reverse((deadline, key))) = self.heap.peek() {
+            if *deadline > now {
+                break;
+            }
+            if let Some(waker) = self.wakers.remove(key) {
+                wakers.push(waker);
+            }
+            self.heap.pop();
+        }
+        wakers
+    }
+}
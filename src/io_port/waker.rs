@@ -1,27 +1,49 @@
-use crate::*;
+#[cfg(target_os = "linux")]
+use crate::io_port::RawRes;
+use crate::{
+    io_port::{IoPort, OpCode, OpCodeExt, IO_PORT},
+    *,
+};
+#[cfg(target_os = "linux")]
+use std::task::Poll;
 use std::{
-    cell::{RefCell, RefMut},
+    alloc::Layout,
+    any::Any,
+    cell::{Cell, RefCell, RefMut},
+    marker::PhantomData,
     ops::Deref,
+    ptr::NonNull,
     task::Waker,
 };
+#[cfg(windows)]
 use windows_sys::Win32::System::IO::OVERLAPPED;
 
+/// A callback receiving the operation, for the simulated driver.
+pub type SimulateFn<'a> = &'a mut dyn FnMut(&mut dyn OpCodeExt);
+
+/// The state of an operation shared with the kernel.
+///
+/// It lives in the slab of the driver, and is referenced by the future and the
+/// kernel. The buffer of the operation is stored in a block of the slab, and
+/// both are returned to the slab when the last reference is released.
 #[repr(C)]
 pub struct OverlappedWakerBase {
+    #[cfg(windows)]
     overlapped: OVERLAPPED,
     waker: RefCell<Option<Waker>>,
-    err: RefCell<Option<IoError>>,
+    result: RefCell<Option<IoResult<usize>>>,
+    refs: Cell<usize>,
+    // The block storing `RefCell<Option<T>>`.
+    buffer: NonNull<u8>,
+    layout: Layout,
+    drop_buffer: unsafe fn(NonNull<u8>),
+    simulate: unsafe fn(*const OverlappedWakerBase, SimulateFn<'_>),
+    into_buffer: unsafe fn(*const OverlappedWakerBase) -> Option<Box<dyn Any>>,
+    #[cfg(target_os = "linux")]
+    operate: unsafe fn(*const OverlappedWakerBase, RawRes) -> Poll<IoResult<usize>>,
 }
 
 impl OverlappedWakerBase {
-    pub fn new() -> Self {
-        Self {
-            overlapped: unsafe { std::mem::zeroed() },
-            waker: RefCell::new(None),
-            err: RefCell::new(None),
-        }
-    }
-
     pub fn set_waker(&self, waker: Waker) {
         self.waker.borrow_mut().replace(waker);
     }
@@ -30,42 +52,187 @@ impl OverlappedWakerBase {
         self.waker.borrow_mut().take()
     }
 
-    pub fn set_err(&self, err: IoError) {
-        self.err.borrow_mut().replace(err);
+    pub fn set_result(&self, res: IoResult<usize>) {
+        self.result.borrow_mut().replace(res);
+    }
+
+    pub fn take_result(&self) -> Option<IoResult<usize>> {
+        self.result.borrow_mut().take()
+    }
+
+    pub fn has_result(&self) -> bool {
+        self.result.borrow().is_some()
+    }
+
+    /// Sets the result of a pointer leaked by [`OverlappedWaker::leak`], wakes
+    /// the task waiting for it, and releases the reference held by the kernel.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` should be leaked by [`OverlappedWaker::leak`] and not completed.
+    pub unsafe fn complete(ptr: *const Self, res: IoResult<usize>) {
+        let base = &*ptr;
+        base.set_result(res);
+        let waker = base.take_waker();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        Self::release(ptr);
+    }
+
+    /// Releases a reference without setting any result. The state and the
+    /// buffer are returned to the slab when the last one is released.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` should be leaked by [`OverlappedWaker::leak`] and not completed.
+    pub unsafe fn release(ptr: *const Self) {
+        let base = &*ptr;
+        let refs = base.refs.get() - 1;
+        base.refs.set(refs);
+        if refs > 0 {
+            return;
+        }
+        let (buffer, layout) = (base.buffer, base.layout);
+        (base.drop_buffer)(buffer);
+        let ptr = NonNull::new_unchecked(ptr.cast_mut());
+        ptr.drop_in_place();
+        // The slab leaks its chunks if it is dropped before.
+        IO_PORT
+            .try_with(|port| {
+                let mut slab = port.slab.borrow_mut();
+                slab.free_block(buffer, layout);
+                slab.free(ptr);
+            })
+            .ok();
     }
 
-    pub fn take_err(&self) -> Option<IoError> {
-        self.err.borrow_mut().take()
+    /// Calls `f` with the operation, for the simulated driver.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` should be leaked by [`OverlappedWaker::leak`] and not completed.
+    pub unsafe fn simulate(ptr: *const Self, f: SimulateFn<'_>) {
+        ((*ptr).simulate)(ptr, f)
+    }
+
+    /// Takes the buffer of the operation, for the graveyard.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` should be leaked by [`OverlappedWaker::leak`] and not completed.
+    pub unsafe fn into_buffer(ptr: *const Self) -> Option<Box<dyn Any>> {
+        ((*ptr).into_buffer)(ptr)
+    }
+
+    /// Retries the operation with a non-blocking syscall.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` should be leaked by [`OverlappedWaker::leak`] and not completed.
+    #[cfg(target_os = "linux")]
+    pub unsafe fn operate(ptr: *const Self, fd: RawRes) -> Poll<IoResult<usize>> {
+        ((*ptr).operate)(ptr, fd)
     }
 }
 
-#[repr(C)]
+/// The reference of the future to the state of the operation.
 pub struct OverlappedWaker<T> {
-    base: OverlappedWakerBase,
-    buffer: RefCell<Option<T>>,
+    ptr: NonNull<OverlappedWakerBase>,
+    _buffer: PhantomData<T>,
 }
 
-impl<T> OverlappedWaker<T> {
-    pub fn new(buffer: T) -> Self {
+impl<T: OpCode + OpCodeExt> OverlappedWaker<T> {
+    /// Takes a state and a block from the slab of the driver.
+    pub fn new(port: &IoPort, buffer: T) -> Self {
+        let layout = Layout::new::<RefCell<Option<T>>>();
+        let (ptr, block) = {
+            let mut slab = port.slab.borrow_mut();
+            (slab.alloc(), slab.alloc_block(layout))
+        };
+        unsafe {
+            block
+                .cast::<RefCell<Option<T>>>()
+                .write(RefCell::new(Some(buffer)));
+            ptr.write(OverlappedWakerBase {
+                #[cfg(windows)]
+                overlapped: std::mem::zeroed(),
+                waker: RefCell::new(None),
+                result: RefCell::new(None),
+                refs: Cell::new(1),
+                buffer: block,
+                layout,
+                drop_buffer: Self::drop_buffer,
+                simulate: Self::simulate,
+                into_buffer: Self::into_buffer,
+                #[cfg(target_os = "linux")]
+                operate: Self::operate,
+            });
+        }
         Self {
-            base: OverlappedWakerBase::new(),
-            buffer: RefCell::new(Some(buffer)),
+            ptr,
+            _buffer: PhantomData,
         }
     }
 
-    pub fn buffer_mut(&self) -> RefMut<Option<T>> {
-        self.buffer.borrow_mut()
+    unsafe fn simulate(ptr: *const OverlappedWakerBase, f: SimulateFn<'_>) {
+        let mut op = Self::buffer_of(ptr).borrow_mut();
+        f(op.as_mut().unwrap())
+    }
+
+    unsafe fn into_buffer(ptr: *const OverlappedWakerBase) -> Option<Box<dyn Any>> {
+        Self::buffer_of(ptr).take().and_then(T::into_buffer)
+    }
+
+    #[cfg(target_os = "linux")]
+    unsafe fn operate(ptr: *const OverlappedWakerBase, fd: RawRes) -> Poll<IoResult<usize>> {
+        let mut op = Self::buffer_of(ptr).borrow_mut();
+        op.as_mut().unwrap().operate(fd)
+    }
+}
+
+impl<T> OverlappedWaker<T> {
+    unsafe fn drop_buffer(block: NonNull<u8>) {
+        block.cast::<RefCell<Option<T>>>().drop_in_place();
+    }
+
+    unsafe fn buffer_of<'a>(ptr: *const OverlappedWakerBase) -> &'a RefCell<Option<T>> {
+        (*ptr).buffer.cast().as_ref()
+    }
+
+    /// Leaks a reference to the kernel. The pointer is also a pointer to
+    /// `OVERLAPPED` on Windows.
+    pub fn leak(&self) -> *const OverlappedWakerBase {
+        self.refs.set(self.refs.get() + 1);
+        self.as_ptr()
+    }
+
+    pub fn as_ptr(&self) -> *const OverlappedWakerBase {
+        self.ptr.as_ptr()
+    }
+
+    pub fn buffer_mut(&self) -> RefMut<'_, Option<T>> {
+        unsafe { Self::buffer_of(self.as_ptr()) }.borrow_mut()
     }
 
     pub fn take_buffer(&self) -> T {
-        self.buffer.take().unwrap()
+        unsafe { Self::buffer_of(self.as_ptr()) }.take().unwrap()
     }
 }
 
+// The buffer is stored in the block, and never moved with the reference.
+impl<T> Unpin for OverlappedWaker<T> {}
+
 impl<T> Deref for OverlappedWaker<T> {
     type Target = OverlappedWakerBase;
 
     fn deref(&self) -> &Self::Target {
-        &self.base
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T> Drop for OverlappedWaker<T> {
+    fn drop(&mut self) {
+        unsafe { OverlappedWakerBase::release(self.as_ptr()) }
     }
 }
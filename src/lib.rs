@@ -1,11 +1,14 @@
 //! Tokio-iocp provides a safe [IOCP] interface for the Tokio runtime.
+//! On Linux, the same interface is backed by [io_uring].
 //!
 //! [IOCP]: https://docs.microsoft.com/en-us/windows/win32/fileio/i-o-completion-ports
+//! [io_uring]: https://kernel.dk/io_uring.pdf
 //!
 //! # Getting started
 //!
 //! Using `tokio-iocp` requires starting a `tokio-iocp` runtime. This
-//! runtime internally manages the main Tokio runtime and a IOCP handle.
+//! runtime internally manages the main Tokio runtime and a IOCP handle
+//! (or an io_uring instance on Linux).
 //!
 //! ```
 //! use tokio_iocp::{fs::File, IoResult};
@@ -30,8 +33,12 @@
 //! }
 //! ```
 //! Under the hood, `tokio_iocp::start` starts a current-thread Runtime.
-//! For concurrency, spawn multiple threads, each with a `tokio-iocp` runtime.
+//! For concurrency, build a runtime with worker threads by
+//! [`runtime::Builder::worker_threads`], each with its own driver.
 //!
+//! The operations only complete when their driver is driven by a `tokio-iocp`
+//! runtime. An idle runtime parks the thread until any operation completes,
+//! a timer fires, or a task is woken by another thread.
 //!
 //! # Submit-based operations
 //!
@@ -42,16 +49,39 @@
 //!
 //! For example, in the above example, reading from a `File` requires passing
 //! ownership of the buffer.
+//!
+//! # Drivers on Linux
+//!
+//! The io_uring driver is preferred on Linux. If io_uring is not available,
+//! e.g., disabled by seccomp in a container, the runtime falls back to an epoll
+//! driver, which emulates the completion model with non-blocking syscalls.
+//! Regular files don't support epoll, so their operations run on the blocking
+//! pool of the runtime.
+//!
+//! The driver of a runtime could be chosen by `Builder::driver`. Otherwise, it
+//! could be chosen for the whole process by an environment variable.
+//!
+//! ## Environment variables
+//!
+//! - `TOKIO_IOCP_DRIVER`: set it to `epoll` to use the epoll driver, even if
+//!   io_uring is available. Other values are ignored. It is read when the
+//!   driver of a thread is created, unless the runtime is built with
+//!   `Builder::driver`, which takes precedence. It is ignored on Windows.
 
 #![cfg_attr(feature = "read_buf", feature(read_buf))]
 #![warn(missing_docs)]
 
+#[cfg(not(any(windows, target_os = "linux")))]
+compile_error!("tokio-iocp only supports Windows and Linux");
+
 pub mod buf;
+pub mod cancel;
 pub mod fs;
 mod io_port;
 pub mod net;
 mod op;
 pub mod runtime;
+pub mod time;
 
 #[doc(no_inline)]
 pub use runtime::spawn;
@@ -68,7 +98,7 @@ pub use std::io::{Error as IoError, Result as IoResult};
 /// run Tokio based libraries (e.g. hyper) from within the tokio-iocp runtime.
 /// A `tokio-iocp` runtime consists of a Tokio `current_thread` runtime.
 /// All tasks spawned on the `tokio-iocp` runtime are executed on the current thread.
-/// To add concurrency, spawn multiple threads, each with a `tokio-iocp` runtime.
+/// To add concurrency, build a runtime with [`runtime::Builder::worker_threads`].
 pub fn start<F: std::future::Future>(future: F) -> F::Output {
     runtime::Runtime::new().unwrap().block_on(future)
 }
@@ -1,4 +1,4 @@
-//! TCP/UDP bindings for IOCP.
+//! TCP/UDP bindings for IOCP and io_uring.
 //!
 //! This module contains the TCP/UDP networking types, similar to the standard
 //! library, which can be used to implement networking protocols.
@@ -12,6 +12,9 @@
 mod socket;
 pub(crate) use socket::*;
 
+mod recv_half;
+pub use recv_half::*;
+
 mod tcp;
 pub use tcp::*;
 
@@ -21,6 +24,7 @@ pub use udp::*;
 mod unix;
 pub use unix::*;
 
+#[cfg(windows)]
 pub mod named_pipe;
 
 use crate::{IoError, IoResult};
@@ -69,6 +73,7 @@ async fn each_addr_async<T, F: Future<Output = IoResult<T>>>(
     }))
 }
 
+#[cfg(windows)]
 macro_rules! impl_socket {
     ($t:ty, $inner:ident) => {
         impl ::std::os::windows::io::AsRawSocket for $t {
@@ -82,11 +87,77 @@ macro_rules! impl_socket {
             }
         }
         impl ::std::os::windows::io::AsSocket for $t {
-            fn as_socket(&self) -> ::std::os::windows::io::BorrowedSocket {
+            fn as_socket(&self) -> ::std::os::windows::io::BorrowedSocket<'_> {
                 self.$inner.as_socket()
             }
         }
+        impl $crate::runtime::fault::FaultTarget for $t {
+            fn set_fault_policy(&self, policy: Option<$crate::runtime::fault::FaultPolicy>) {
+                $crate::runtime::fault::set_handle_fault_policy(
+                    ::std::os::windows::io::AsRawSocket::as_raw_socket(self) as _,
+                    policy,
+                )
+            }
+        }
+    };
+}
+
+#[cfg(target_os = "linux")]
+macro_rules! impl_socket {
+    ($t:ty, $inner:ident) => {
+        impl ::std::os::fd::AsRawFd for $t {
+            fn as_raw_fd(&self) -> ::std::os::fd::RawFd {
+                self.$inner.as_raw_fd()
+            }
+        }
+        impl ::std::os::fd::IntoRawFd for $t {
+            fn into_raw_fd(self) -> ::std::os::fd::RawFd {
+                self.$inner.into_raw_fd()
+            }
+        }
+        impl ::std::os::fd::AsFd for $t {
+            fn as_fd(&self) -> ::std::os::fd::BorrowedFd<'_> {
+                self.$inner.as_fd()
+            }
+        }
+        impl $crate::runtime::fault::FaultTarget for $t {
+            fn set_fault_policy(&self, policy: Option<$crate::runtime::fault::FaultPolicy>) {
+                $crate::runtime::fault::set_handle_fault_policy(
+                    ::std::os::fd::AsRawFd::as_raw_fd(self),
+                    policy,
+                )
+            }
+        }
     };
 }
 
 pub(crate) use impl_socket;
+
+macro_rules! impl_detached {
+    ($t:ty, $inner:ident) => {
+        impl $t {
+            /// Detaches the socket from the driver of the current thread, to be
+            /// sent to another runtime thread. See [`Detached`].
+            ///
+            /// [`Detached`]: $crate::runtime::Detached
+            pub fn into_detached(self) -> $crate::IoResult<$crate::runtime::Detached<Self>> {
+                $crate::runtime::Detached::detach(self)
+            }
+
+            /// Closes the socket, after the operations on it complete, and
+            /// returns the error of closing. See [`File::close`].
+            ///
+            /// [`File::close`]: $crate::fs::File::close
+            pub async fn close(self) -> $crate::IoResult<()> {
+                self.$inner.close().await
+            }
+        }
+        impl $crate::runtime::Resource for $t {
+            fn as_raw_res(&self) -> $crate::io_port::RawRes {
+                $crate::io_port::BorrowedRes::from(self.$inner.as_res()).as_raw()
+            }
+        }
+    };
+}
+
+pub(crate) use impl_detached;
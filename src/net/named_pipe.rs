@@ -6,6 +6,10 @@ use crate::{
     buf::*,
     io_port::*,
     op::{self, BufResultExt, BufResultIntoInner},
+    runtime::{
+        fault::{set_handle_fault_policy, FaultPolicy, FaultTarget},
+        Detached, Resource,
+    },
     *,
 };
 use std::{
@@ -99,7 +103,7 @@ pub use tokio::net::windows::named_pipe::{PipeEnd, PipeMode};
 /// [Windows named pipe]: https://docs.microsoft.com/en-us/windows/win32/ipc/named-pipes
 #[derive(Debug)]
 pub struct NamedPipeServer {
-    handle: OwnedHandle,
+    handle: Attached<OwnedHandle>,
 }
 
 impl NamedPipeServer {
@@ -114,8 +118,24 @@ impl NamedPipeServer {
     /// this contract which can cause memory unsafety in code that relies on it
     /// being true.
     pub fn from_handle(handle: OwnedHandle) -> IoResult<Self> {
-        IO_PORT.with(|port| port.attach(handle.as_raw_handle() as _))?;
-        Ok(Self { handle })
+        Ok(Self {
+            handle: Attached::new(handle)?,
+        })
+    }
+
+    /// Detaches the server from the driver of the current thread, to be sent
+    /// to another runtime thread. See [`Detached`].
+    pub fn into_detached(self) -> IoResult<Detached<Self>> {
+        Detached::detach(self)
+    }
+
+    /// Closes the server, after the operations on it complete, and returns the
+    /// error of closing. See [`File::close`].
+    ///
+    /// [`File::close`]: crate::fs::File::close
+    pub async fn close(self) -> IoResult<()> {
+        prepare_close(self.handle.as_raw_handle() as _).await;
+        close_handle(self.handle.into_inner())
     }
 
     /// Retrieves information about the named pipe the server is associated
@@ -220,16 +240,36 @@ impl NamedPipeServer {
 
     /// Read some bytes from the pipe into the specified
     /// buffer, returning how many bytes were read.
-    pub async fn read<T: IoBufMut>(&self, buffer: T) -> BufResult<usize, T> {
-        op::read_at(self.as_handle(), buffer, 0)
-            .await
-            .map_advanced()
-            .into_inner()
+    pub fn read<T: IoBufMut>(
+        &self,
+        buffer: T,
+    ) -> impl cancel::OpFuture<Output = BufResult<usize, T>> + use<'_, T> {
+        cancel::Op::new(async move {
+            op::read_at(self.as_handle(), buffer, 0)
+                .await
+                .map_advanced()
+                .into_inner()
+        })
     }
 
     /// Write a buffer into the pipe, returning how many bytes were written.
-    pub async fn write<T: IoBuf>(&self, buffer: T) -> BufResult<usize, T> {
-        op::write_at(self.as_handle(), buffer, 0).await.into_inner()
+    pub fn write<T: IoBuf>(
+        &self,
+        buffer: T,
+    ) -> impl cancel::OpFuture<Output = BufResult<usize, T>> + use<'_, T> {
+        cancel::Op::new(async move { op::write_at(self.as_handle(), buffer, 0).await.into_inner() })
+    }
+}
+
+impl Resource for NamedPipeServer {
+    fn as_raw_res(&self) -> RawRes {
+        self.as_raw_handle() as _
+    }
+}
+
+impl FaultTarget for NamedPipeServer {
+    fn set_fault_policy(&self, policy: Option<FaultPolicy>) {
+        set_handle_fault_policy(self.as_raw_handle() as _, policy)
     }
 }
 
@@ -291,7 +331,7 @@ impl AsHandle for NamedPipeServer {
 /// [Windows named pipe]: https://docs.microsoft.com/en-us/windows/win32/ipc/named-pipes
 #[derive(Debug)]
 pub struct NamedPipeClient {
-    handle: OwnedHandle,
+    handle: Attached<OwnedHandle>,
 }
 
 impl NamedPipeClient {
@@ -306,8 +346,24 @@ impl NamedPipeClient {
     /// this contract which can cause memory unsafety in code that relies on it
     /// being true.
     pub fn from_handle(handle: OwnedHandle) -> IoResult<Self> {
-        IO_PORT.with(|port| port.attach(handle.as_raw_handle() as _))?;
-        Ok(Self { handle })
+        Ok(Self {
+            handle: Attached::new(handle)?,
+        })
+    }
+
+    /// Detaches the client from the driver of the current thread, to be sent
+    /// to another runtime thread. See [`Detached`].
+    pub fn into_detached(self) -> IoResult<Detached<Self>> {
+        Detached::detach(self)
+    }
+
+    /// Closes the client, after the operations on it complete, and returns the
+    /// error of closing. See [`File::close`].
+    ///
+    /// [`File::close`]: crate::fs::File::close
+    pub async fn close(self) -> IoResult<()> {
+        prepare_close(self.handle.as_raw_handle() as _).await;
+        close_handle(self.handle.into_inner())
     }
 
     /// Retrieves information about the named pipe the client is associated
@@ -336,16 +392,36 @@ impl NamedPipeClient {
 
     /// Read some bytes from the pipe into the specified
     /// buffer, returning how many bytes were read.
-    pub async fn read<T: IoBufMut>(&self, buffer: T) -> BufResult<usize, T> {
-        op::read_at(self.as_handle(), buffer, 0)
-            .await
-            .map_advanced()
-            .into_inner()
+    pub fn read<T: IoBufMut>(
+        &self,
+        buffer: T,
+    ) -> impl cancel::OpFuture<Output = BufResult<usize, T>> + use<'_, T> {
+        cancel::Op::new(async move {
+            op::read_at(self.as_handle(), buffer, 0)
+                .await
+                .map_advanced()
+                .into_inner()
+        })
     }
 
     /// Write a buffer into the pipe, returning how many bytes were written.
-    pub async fn write<T: IoBuf>(&self, buffer: T) -> BufResult<usize, T> {
-        op::write_at(self.as_handle(), buffer, 0).await.into_inner()
+    pub fn write<T: IoBuf>(
+        &self,
+        buffer: T,
+    ) -> impl cancel::OpFuture<Output = BufResult<usize, T>> + use<'_, T> {
+        cancel::Op::new(async move { op::write_at(self.as_handle(), buffer, 0).await.into_inner() })
+    }
+}
+
+impl Resource for NamedPipeClient {
+    fn as_raw_res(&self) -> RawRes {
+        self.as_raw_handle() as _
+    }
+}
+
+impl FaultTarget for NamedPipeClient {
+    fn set_fault_policy(&self, policy: Option<FaultPolicy>) {
+        set_handle_fault_policy(self.as_raw_handle() as _, policy)
     }
 }
 
@@ -0,0 +1,108 @@
+use crate::{buf::*, net::Socket, *};
+use std::{future::Future, pin::Pin};
+
+type RecvFuture<'a, T> = Pin<Box<dyn Future<Output = BufResult<usize, T>> + 'a>>;
+
+/// The receiving half of a stream, which is cancellation safe.
+///
+/// Dropping the future of [`recv`](RecvHalf::recv) doesn't cancel the
+/// operation in flight. It is kept by the half, and [`resume`](RecvHalf::resume)
+/// waits for it, returning the received data with the original buffer. It is
+/// cancelled only when the half is dropped.
+///
+/// It is created by [`TcpStream::recv_half`] or [`UnixStream::recv_half`].
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use tokio_iocp::net::{TcpListener, TcpStream};
+///
+/// tokio_iocp::start(async {
+///     let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+///     let addr = listener.local_addr().unwrap();
+///     let (tx, (rx, _)) = tokio::try_join!(TcpStream::connect(addr), listener.accept()).unwrap();
+///
+///     let mut rx = rx.recv_half::<Vec<u8>>();
+///     tokio::select! {
+///         _ = tokio::time::sleep(Duration::from_millis(10)) => {}
+///         (res, _) = rx.recv(Vec::with_capacity(64)) => unreachable!("{:?}", res),
+///     }
+///     assert!(rx.is_pending());
+///
+///     tx.send("hello").await.0.unwrap();
+///     let (res, buffer) = rx.resume().await;
+///     assert_eq!(res.unwrap(), 5);
+///     assert_eq!(buffer, b"hello");
+/// });
+/// ```
+///
+/// [`TcpStream::recv_half`]: crate::net::TcpStream::recv_half
+/// [`UnixStream::recv_half`]: crate::net::UnixStream::recv_half
+pub struct RecvHalf<'a, T> {
+    socket: &'a Socket,
+    op: Option<RecvFuture<'a, T>>,
+}
+
+impl<'a, T: IoBufMut> RecvHalf<'a, T> {
+    pub(crate) fn new(socket: &'a Socket) -> Self {
+        Self { socket, op: None }
+    }
+
+    /// Receives a packet of data from the socket into the buffer, returning the original buffer and
+    /// quantity of data received.
+    ///
+    /// No operation should be pending, see [`RecvHalf::is_pending`]. Otherwise,
+    /// it panics in debug builds, and in release builds, `buffer` is dropped
+    /// and the pending operation is waited for.
+    ///
+    /// # Cancel safety
+    ///
+    /// This method is cancellation safe. If the future is dropped before it
+    /// completes, the operation is kept, and its result is returned by
+    /// [`RecvHalf::resume`].
+    pub fn recv(
+        &mut self,
+        buffer: T,
+    ) -> impl cancel::OpFuture<Output = BufResult<usize, T>> + use<'_, 'a, T> {
+        debug_assert!(self.op.is_none(), "an operation is pending");
+        let socket = self.socket;
+        self.op.get_or_insert_with(|| Box::pin(socket.recv(buffer)));
+        self.resume()
+    }
+
+    /// Waits for the pending operation, whose future was dropped, returning
+    /// its buffer and quantity of data received.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no operation is pending.
+    ///
+    /// # Cancel safety
+    ///
+    /// This method is cancellation safe. If the future is dropped before it
+    /// completes, the operation is still kept.
+    pub fn resume(
+        &mut self,
+    ) -> impl cancel::OpFuture<Output = BufResult<usize, T>> + use<'_, 'a, T> {
+        assert!(self.op.is_some(), "no operation is pending");
+        cancel::Op::new(async move {
+            let res = self.op.as_mut().unwrap().await;
+            self.op = None;
+            res
+        })
+    }
+
+    /// Whether an operation is kept by the half, whose future was dropped.
+    pub fn is_pending(&self) -> bool {
+        self.op.is_some()
+    }
+}
+
+impl<T> std::fmt::Debug for RecvHalf<'_, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RecvHalf")
+            .field("pending", &self.op.is_some())
+            .finish()
+    }
+}
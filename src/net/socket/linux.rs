@@ -0,0 +1,251 @@
+use crate::{
+    io_port::{self, Attached},
+    net::{UnixSocketAddr, *},
+    op, *,
+};
+use aligned_array::{Aligned, A4};
+use libc::{
+    bind, c_int, connect, getpeername, getsockname, in6_addr, in_addr, listen, setsockopt,
+    shutdown, sockaddr_in, sockaddr_in6, sockaddr_storage, sockaddr_un, socket, SHUT_RD, SHUT_RDWR,
+    SHUT_WR, SOCK_CLOEXEC, SOL_SOCKET, SO_REUSEADDR,
+};
+pub use libc::{
+    c_int as SocketType, c_int as Protocol, sa_family_t as AddressFamily, sockaddr as RawSockAddr,
+    socklen_t as SockAddrLen, IPPROTO_TCP, IPPROTO_UDP, SOCK_DGRAM, SOCK_STREAM, SOMAXCONN,
+};
+use std::{
+    net::{Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr, SocketAddrV4, SocketAddrV6},
+    os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd},
+    ptr::NonNull,
+};
+
+pub const AF_UNIX: AddressFamily = libc::AF_UNIX as _;
+const AF_INET: AddressFamily = libc::AF_INET as _;
+const AF_INET6: AddressFamily = libc::AF_INET6 as _;
+
+pub struct Socket {
+    handle: Attached<OwnedFd>,
+}
+
+impl Socket {
+    pub fn new(addr: AddressFamily, ty: SocketType, protocol: Protocol) -> IoResult<Self> {
+        let handle = unsafe { socket(addr as _, ty | SOCK_CLOEXEC, protocol) };
+        if handle >= 0 {
+            Self::from_fd(unsafe { OwnedFd::from_raw_fd(handle) })
+        } else {
+            Err(IoError::last_os_error())
+        }
+    }
+
+    fn from_fd(handle: OwnedFd) -> IoResult<Self> {
+        Ok(Self {
+            handle: Attached::new(handle)?,
+        })
+    }
+
+    pub(crate) fn as_res(&self) -> BorrowedFd<'_> {
+        self.handle.as_fd()
+    }
+
+    pub fn bind(addr: impl SockAddr, ty: SocketType, protocol: Protocol) -> IoResult<Self> {
+        let socket = Self::new(addr.domain(), ty, protocol)?;
+        if ty == SOCK_STREAM && addr.domain() != AF_UNIX {
+            // Same as std, allow to rebind the address in TIME_WAIT.
+            socket.set_reuse_addr()?;
+        }
+        let res = unsafe { addr.with_native(|addr, len| bind(socket.as_raw_fd(), addr, len)) };
+        if res == 0 {
+            Ok(socket)
+        } else {
+            Err(IoError::last_os_error())
+        }
+    }
+
+    fn set_reuse_addr(&self) -> IoResult<()> {
+        let value: c_int = 1;
+        let res = unsafe {
+            setsockopt(
+                self.as_raw_fd(),
+                SOL_SOCKET,
+                SO_REUSEADDR,
+                std::ptr::addr_of!(value).cast(),
+                std::mem::size_of_val(&value) as _,
+            )
+        };
+        if res == 0 {
+            Ok(())
+        } else {
+            Err(IoError::last_os_error())
+        }
+    }
+
+    pub fn connect(&self, addr: impl SockAddr) -> IoResult<()> {
+        let res = unsafe { addr.with_native(|addr, len| connect(self.as_raw_fd(), addr, len)) };
+        if res == 0 {
+            Ok(())
+        } else {
+            Err(IoError::last_os_error())
+        }
+    }
+
+    pub fn listen(&self, backlog: i32) -> IoResult<()> {
+        let res = unsafe { listen(self.as_raw_fd(), backlog) };
+        if res == 0 {
+            Ok(())
+        } else {
+            Err(IoError::last_os_error())
+        }
+    }
+
+    fn get_addr<A: SockAddr>(
+        &self,
+        f: unsafe extern "C" fn(i32, *mut RawSockAddr, *mut SockAddrLen) -> i32,
+    ) -> IoResult<A> {
+        let mut name: Aligned<A4, _> = Aligned([0u8; MAX_ADDR_SIZE]);
+        let mut namelen: SockAddrLen = MAX_ADDR_SIZE as _;
+        let res = unsafe { f(self.as_raw_fd(), name.as_mut_ptr() as _, &mut namelen) };
+        if res == 0 {
+            Ok(unsafe {
+                A::try_from_native(NonNull::new_unchecked(name.as_ptr() as _), namelen).unwrap()
+            })
+        } else {
+            Err(IoError::last_os_error())
+        }
+    }
+
+    pub fn peer_addr<A: SockAddr>(&self) -> IoResult<A> {
+        self.get_addr(getpeername)
+    }
+
+    pub fn local_addr<A: SockAddr>(&self) -> IoResult<A> {
+        self.get_addr(getsockname)
+    }
+
+    pub async fn accept<A: SockAddr + 'static>(
+        &self,
+        _ty: SocketType,
+        _protocol: Protocol,
+    ) -> IoResult<(Socket, A)> {
+        let (res, accept) = op::accept(self.as_fd()).await;
+        let accept_socket = Self::from_fd(unsafe { OwnedFd::from_raw_fd(res? as _) })?;
+        let addr = op::accept_result(&accept)?;
+        Ok((accept_socket, addr))
+    }
+
+    pub fn shutdown(&self, how: Shutdown) -> IoResult<()> {
+        let how = match how {
+            Shutdown::Write => SHUT_WR,
+            Shutdown::Read => SHUT_RD,
+            Shutdown::Both => SHUT_RDWR,
+        };
+        let res = unsafe { shutdown(self.as_raw_fd(), how) };
+        if res == 0 {
+            Ok(())
+        } else {
+            Err(IoError::last_os_error())
+        }
+    }
+
+    pub async fn close(self) -> IoResult<()> {
+        io_port::prepare_close(self.as_raw_fd()).await;
+        io_port::close_handle(self.handle.into_inner())
+    }
+}
+
+impl_socket!(Socket, handle);
+
+pub const MAX_ADDR_SIZE: usize = std::mem::size_of::<sockaddr_storage>();
+
+impl SockAddr for SocketAddr {
+    fn domain(&self) -> AddressFamily {
+        match self {
+            Self::V4(_) => AF_INET,
+            Self::V6(_) => AF_INET6,
+        }
+    }
+
+    unsafe fn try_from_native(addr: NonNull<RawSockAddr>, _len: SockAddrLen) -> Option<Self> {
+        let addr_ref = addr.as_ref();
+        match addr_ref.sa_family {
+            AF_INET => {
+                let addr = addr.cast::<sockaddr_in>().as_ref();
+                Some(SocketAddr::V4(SocketAddrV4::new(
+                    Ipv4Addr::from(addr.sin_addr.s_addr.to_ne_bytes()),
+                    u16::from_be(addr.sin_port),
+                )))
+            }
+            AF_INET6 => {
+                let addr = addr.cast::<sockaddr_in6>().as_ref();
+                Some(SocketAddr::V6(SocketAddrV6::new(
+                    Ipv6Addr::from(addr.sin6_addr.s6_addr),
+                    u16::from_be(addr.sin6_port),
+                    addr.sin6_flowinfo,
+                    addr.sin6_scope_id,
+                )))
+            }
+            _ => None,
+        }
+    }
+
+    unsafe fn with_native<T>(&self, f: impl FnOnce(*const RawSockAddr, SockAddrLen) -> T) -> T {
+        match self {
+            SocketAddr::V4(addr) => {
+                let native_addr = sockaddr_in {
+                    sin_family: AF_INET,
+                    sin_port: addr.port().to_be(),
+                    sin_addr: in_addr {
+                        s_addr: u32::from_ne_bytes(addr.ip().octets()),
+                    },
+                    sin_zero: std::mem::zeroed(),
+                };
+                f(
+                    std::ptr::addr_of!(native_addr) as _,
+                    std::mem::size_of_val(&native_addr) as _,
+                )
+            }
+            SocketAddr::V6(addr) => {
+                let native_addr = sockaddr_in6 {
+                    sin6_family: AF_INET6,
+                    sin6_port: addr.port().to_be(),
+                    sin6_flowinfo: 0,
+                    sin6_addr: in6_addr {
+                        s6_addr: addr.ip().octets(),
+                    },
+                    sin6_scope_id: addr.scope_id(),
+                };
+                f(
+                    std::ptr::addr_of!(native_addr) as _,
+                    std::mem::size_of_val(&native_addr) as _,
+                )
+            }
+        }
+    }
+}
+
+impl SockAddr for UnixSocketAddr {
+    fn domain(&self) -> AddressFamily {
+        AF_UNIX
+    }
+
+    unsafe fn try_from_native(addr: NonNull<RawSockAddr>, len: SockAddrLen) -> Option<Self> {
+        let addr_ref = addr.as_ref();
+        if addr_ref.sa_family == AF_UNIX {
+            let addr = addr.cast::<sockaddr_un>().as_ref();
+            let len = (len as usize).saturating_sub(2);
+            Some(UnixSocketAddr {
+                path: addr.sun_path.map(|c| c as u8),
+                len,
+            })
+        } else {
+            None
+        }
+    }
+
+    unsafe fn with_native<T>(&self, f: impl FnOnce(*const RawSockAddr, SockAddrLen) -> T) -> T {
+        let addr = sockaddr_un {
+            sun_family: AF_UNIX,
+            sun_path: self.path.map(|c| c as _),
+        };
+        f(std::ptr::addr_of!(addr) as _, (self.len + 2) as _)
+    }
+}
@@ -0,0 +1,103 @@
+#[cfg(target_os = "linux")]
+mod linux;
+#[cfg(target_os = "linux")]
+pub use linux::*;
+
+#[cfg(windows)]
+mod windows;
+#[cfg(windows)]
+pub use windows::*;
+
+use crate::{
+    buf::*,
+    op::{self, BufResultExt, BufResultIntoInner, RecvResultExt},
+    *,
+};
+use std::{
+    net::{SocketAddr, SocketAddrV4, SocketAddrV6},
+    ptr::NonNull,
+};
+
+impl Socket {
+    pub fn bind_any_like(addr: SocketAddr, ty: SocketType, protocol: Protocol) -> IoResult<Self> {
+        let new_addr: SocketAddr = match addr {
+            SocketAddr::V4(addr) => SocketAddrV4::new(*addr.ip(), 0).into(),
+            SocketAddr::V6(addr) => SocketAddrV6::new(*addr.ip(), 0, 0, 0).into(),
+        };
+        Self::bind(new_addr, ty, protocol)
+    }
+
+    pub async fn connect_ex(&self, addr: impl SockAddr + 'static) -> IoResult<()> {
+        op::connect(self.as_res(), addr).await.0?;
+        Ok(())
+    }
+
+    pub async fn recv<T: IoBufMut>(&self, buffer: T) -> BufResult<usize, T> {
+        op::recv::<BufWrapper<T>>(self.as_res(), buffer)
+            .await
+            .map_advanced()
+            .into_inner()
+    }
+
+    pub async fn recv_vectored<T: IoBufMut>(&self, buffer: Vec<T>) -> BufResult<usize, Vec<T>> {
+        op::recv::<VectoredBufWrapper<T>>(self.as_res(), buffer)
+            .await
+            .map_advanced()
+            .into_inner()
+    }
+
+    pub async fn send<T: IoBuf>(&self, buffer: T) -> BufResult<usize, T> {
+        op::send::<BufWrapper<T>>(self.as_res(), buffer)
+            .await
+            .into_inner()
+    }
+
+    pub async fn send_vectored<T: IoBuf>(&self, buffer: Vec<T>) -> BufResult<usize, Vec<T>> {
+        op::send::<VectoredBufWrapper<T>>(self.as_res(), buffer)
+            .await
+            .into_inner()
+    }
+
+    pub async fn recv_from<T: IoBufMut, A: SockAddr>(&self, buffer: T) -> BufResult<(usize, A), T> {
+        op::recv_from::<BufWrapper<T>>(self.as_res(), buffer)
+            .await
+            .map_addr()
+            .map_advanced()
+            .into_inner()
+    }
+
+    pub async fn recv_from_vectored<T: IoBufMut, A: SockAddr>(
+        &self,
+        buffer: Vec<T>,
+    ) -> BufResult<(usize, A), Vec<T>> {
+        op::recv_from::<VectoredBufWrapper<T>>(self.as_res(), buffer)
+            .await
+            .map_addr()
+            .map_advanced()
+            .into_inner()
+    }
+
+    pub async fn send_to<T: IoBuf>(&self, buffer: T, addr: impl SockAddr) -> BufResult<usize, T> {
+        op::send_to::<BufWrapper<T>, _>(self.as_res(), buffer, addr)
+            .await
+            .into_inner()
+    }
+
+    pub async fn send_to_vectored<T: IoBuf>(
+        &self,
+        buffer: Vec<T>,
+        addr: impl SockAddr,
+    ) -> BufResult<usize, Vec<T>> {
+        op::send_to::<VectoredBufWrapper<T>, _>(self.as_res(), buffer, addr)
+            .await
+            .into_inner()
+    }
+}
+
+pub trait SockAddr: Sized + Unpin {
+    fn domain(&self) -> AddressFamily;
+
+    unsafe fn try_from_native(addr: NonNull<RawSockAddr>, len: SockAddrLen) -> Option<Self>;
+
+    unsafe fn with_native<T>(&self, f: impl FnOnce(*const RawSockAddr, SockAddrLen) -> T) -> T;
+}
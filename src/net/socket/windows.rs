@@ -1,23 +1,29 @@
 use crate::{
-    buf::*,
-    io_port::IO_PORT,
+    io_port::{self, Attached},
     net::{UnixSocketAddr, *},
-    op::{self, BufResultExt, BufResultIntoInner, RecvResultExt},
-    *,
+    op, *,
 };
 use aligned_array::{Aligned, A4};
 use once_cell::sync::OnceCell as OnceLock;
 use std::{
     net::{Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr, SocketAddrV4, SocketAddrV6},
-    os::windows::prelude::{AsRawSocket, AsSocket, FromRawSocket, OwnedSocket},
+    os::windows::prelude::{
+        AsRawSocket, AsSocket, BorrowedSocket, FromRawSocket, IntoRawSocket, OwnedSocket,
+    },
     ptr::NonNull,
 };
 use windows_sys::Win32::Networking::WinSock::{
-    bind, connect, getpeername, getsockname, listen, shutdown, socket, WSACleanup, WSAStartup,
-    ADDRESS_FAMILY, AF_INET, AF_INET6, AF_UNIX, INVALID_SOCKET, IPPROTO, SD_BOTH, SD_RECEIVE,
-    SD_SEND, SOCKADDR, SOCKADDR_IN, SOCKADDR_IN6, SOCKADDR_STORAGE, SOCKADDR_UN, SOCKET,
-    WINSOCK_SOCKET_TYPE, WSADATA,
+    bind, closesocket, connect, getpeername, getsockname, listen, shutdown, socket, WSACleanup,
+    WSAStartup, AF_INET, AF_INET6, IN6_ADDR, IN6_ADDR_0, INVALID_SOCKET, IN_ADDR, IN_ADDR_0,
+    SD_BOTH, SD_RECEIVE, SD_SEND, SOCKADDR_IN, SOCKADDR_IN6, SOCKADDR_STORAGE, SOCKADDR_UN, SOCKET,
+    WSADATA,
 };
+pub use windows_sys::Win32::Networking::WinSock::{
+    ADDRESS_FAMILY as AddressFamily, AF_UNIX, IPPROTO as Protocol, IPPROTO_TCP, IPPROTO_UDP,
+    SOCKADDR as RawSockAddr, SOCK_DGRAM, SOCK_STREAM, SOMAXCONN, WINSOCK_SOCKET_TYPE as SocketType,
+};
+
+pub type SockAddrLen = i32;
 
 struct WSAInit;
 
@@ -44,30 +50,29 @@ impl Drop for WSAInit {
 static WSA_INIT: OnceLock<WSAInit> = OnceLock::new();
 
 pub struct Socket {
-    handle: OwnedSocket,
+    handle: Attached<OwnedSocket>,
 }
 
 impl Socket {
-    pub fn new(addr: ADDRESS_FAMILY, ty: WINSOCK_SOCKET_TYPE, protocol: IPPROTO) -> IoResult<Self> {
+    pub fn new(addr: AddressFamily, ty: SocketType, protocol: Protocol) -> IoResult<Self> {
         WSA_INIT.get_or_init(WSAInit::init);
 
         let handle = unsafe { socket(addr as _, ty, protocol) };
         if handle != INVALID_SOCKET {
-            let socket = Self {
-                handle: unsafe { OwnedSocket::from_raw_socket(handle as _) },
-            };
-            socket.attach()?;
-            Ok(socket)
+            let handle = unsafe { OwnedSocket::from_raw_socket(handle as _) };
+            Ok(Self {
+                handle: Attached::new(handle)?,
+            })
         } else {
             Err(IoError::last_os_error())
         }
     }
 
-    fn attach(&self) -> IoResult<()> {
-        IO_PORT.with(|port| port.attach(self.as_raw_socket() as _))
+    pub(crate) fn as_res(&self) -> BorrowedSocket<'_> {
+        self.handle.as_socket()
     }
 
-    pub fn bind(addr: impl SockAddr, ty: WINSOCK_SOCKET_TYPE, protocol: IPPROTO) -> IoResult<Self> {
+    pub fn bind(addr: impl SockAddr, ty: SocketType, protocol: Protocol) -> IoResult<Self> {
         let socket = Self::new(addr.domain(), ty, protocol)?;
         let res =
             unsafe { addr.with_native(|addr, len| bind(socket.as_raw_socket() as _, addr, len)) };
@@ -78,18 +83,6 @@ impl Socket {
         }
     }
 
-    pub fn bind_any_like(
-        addr: SocketAddr,
-        ty: WINSOCK_SOCKET_TYPE,
-        protocol: IPPROTO,
-    ) -> IoResult<Self> {
-        let new_addr: SocketAddr = match addr {
-            SocketAddr::V4(addr) => SocketAddrV4::new(*addr.ip(), 0).into(),
-            SocketAddr::V6(addr) => SocketAddrV6::new(*addr.ip(), 0, 0, 0).into(),
-        };
-        Self::bind(new_addr, ty, protocol)
-    }
-
     pub fn connect(&self, addr: impl SockAddr) -> IoResult<()> {
         let res =
             unsafe { addr.with_native(|addr, len| connect(self.as_raw_socket() as _, addr, len)) };
@@ -100,11 +93,6 @@ impl Socket {
         }
     }
 
-    pub async fn connect_ex(&self, addr: impl SockAddr + 'static) -> IoResult<()> {
-        op::connect(self.as_socket(), addr).await.0?;
-        Ok(())
-    }
-
     pub fn listen(&self, backlog: i32) -> IoResult<()> {
         let res = unsafe { listen(self.as_raw_socket() as _, backlog) };
         if res == 0 {
@@ -116,7 +104,7 @@ impl Socket {
 
     fn get_addr<A: SockAddr>(
         &self,
-        f: unsafe extern "system" fn(SOCKET, *mut SOCKADDR, *mut i32) -> i32,
+        f: unsafe extern "system" fn(SOCKET, *mut RawSockAddr, *mut i32) -> i32,
     ) -> IoResult<A> {
         let mut name: Aligned<A4, _> = Aligned([0u8; MAX_ADDR_SIZE]);
         let mut namelen: i32 = MAX_ADDR_SIZE as _;
@@ -146,14 +134,14 @@ impl Socket {
 
     pub async fn accept<A: SockAddr + 'static>(
         &self,
-        ty: WINSOCK_SOCKET_TYPE,
-        protocol: IPPROTO,
+        ty: SocketType,
+        protocol: Protocol,
     ) -> IoResult<(Socket, A)> {
         let local_addr: A = self.local_addr()?;
         let accept_socket = Socket::new(local_addr.domain(), ty, protocol)?;
-        let (res, buffer) = op::accept(self.as_socket(), accept_socket.handle.as_socket()).await;
+        let (res, accept) = op::accept(self.as_socket(), accept_socket.as_raw_socket() as _).await;
         res?;
-        let addr = op::accept_result(self.as_socket(), &buffer)?;
+        let addr = op::accept_result(self.as_socket(), &accept)?;
         Ok((accept_socket, addr))
     }
 
@@ -171,65 +159,14 @@ impl Socket {
         }
     }
 
-    pub async fn recv<T: IoBufMut>(&self, buffer: T) -> BufResult<usize, T> {
-        op::recv::<BufWrapper<T>>(self.as_socket(), buffer)
-            .await
-            .map_advanced()
-            .into_inner()
-    }
-
-    pub async fn recv_vectored<T: IoBufMut>(&self, buffer: Vec<T>) -> BufResult<usize, Vec<T>> {
-        op::recv::<VectoredBufWrapper<T>>(self.as_socket(), buffer)
-            .await
-            .map_advanced()
-            .into_inner()
-    }
-
-    pub async fn send<T: IoBuf>(&self, buffer: T) -> BufResult<usize, T> {
-        op::send::<BufWrapper<T>>(self.as_socket(), buffer)
-            .await
-            .into_inner()
-    }
-
-    pub async fn send_vectored<T: IoBuf>(&self, buffer: Vec<T>) -> BufResult<usize, Vec<T>> {
-        op::send::<VectoredBufWrapper<T>>(self.as_socket(), buffer)
-            .await
-            .into_inner()
-    }
-
-    pub async fn recv_from<T: IoBufMut, A: SockAddr>(&self, buffer: T) -> BufResult<(usize, A), T> {
-        op::recv_from::<BufWrapper<T>>(self.as_socket(), buffer)
-            .await
-            .map_addr()
-            .map_advanced()
-            .into_inner()
-    }
-
-    pub async fn recv_from_vectored<T: IoBufMut, A: SockAddr>(
-        &self,
-        buffer: Vec<T>,
-    ) -> BufResult<(usize, A), Vec<T>> {
-        op::recv_from::<VectoredBufWrapper<T>>(self.as_socket(), buffer)
-            .await
-            .map_addr()
-            .map_advanced()
-            .into_inner()
-    }
-
-    pub async fn send_to<T: IoBuf>(&self, buffer: T, addr: impl SockAddr) -> BufResult<usize, T> {
-        op::send_to::<BufWrapper<T>>(self.as_socket(), buffer, addr)
-            .await
-            .into_inner()
-    }
-
-    pub async fn send_to_vectored<T: IoBuf>(
-        &self,
-        buffer: Vec<T>,
-        addr: impl SockAddr,
-    ) -> BufResult<usize, Vec<T>> {
-        op::send_to::<VectoredBufWrapper<T>>(self.as_socket(), buffer, addr)
-            .await
-            .into_inner()
+    pub async fn close(self) -> IoResult<()> {
+        io_port::prepare_close(self.as_raw_socket() as _).await;
+        let res = unsafe { closesocket(self.handle.into_inner().into_raw_socket() as _) };
+        if res == 0 {
+            Ok(())
+        } else {
+            Err(IoError::last_os_error())
+        }
     }
 }
 
@@ -237,37 +174,29 @@ impl_socket!(Socket, handle);
 
 pub const MAX_ADDR_SIZE: usize = std::mem::size_of::<SOCKADDR_STORAGE>();
 
-pub trait SockAddr: Sized + Unpin {
-    fn domain(&self) -> ADDRESS_FAMILY;
-
-    unsafe fn try_from_native(addr: NonNull<SOCKADDR>, len: i32) -> Option<Self>;
-
-    unsafe fn with_native<T>(&self, f: impl FnOnce(*const SOCKADDR, i32) -> T) -> T;
-}
-
 impl SockAddr for SocketAddr {
-    fn domain(&self) -> ADDRESS_FAMILY {
+    fn domain(&self) -> AddressFamily {
         match self {
             Self::V4(_) => AF_INET,
             Self::V6(_) => AF_INET6,
         }
     }
 
-    unsafe fn try_from_native(addr: NonNull<SOCKADDR>, _len: i32) -> Option<Self> {
+    unsafe fn try_from_native(addr: NonNull<RawSockAddr>, _len: SockAddrLen) -> Option<Self> {
         let addr_ref = addr.as_ref();
         match addr_ref.sa_family {
             AF_INET => {
                 let addr = addr.cast::<SOCKADDR_IN>().as_ref();
                 Some(SocketAddr::V4(SocketAddrV4::new(
                     Ipv4Addr::from(addr.sin_addr.S_un.S_addr.to_ne_bytes()),
-                    addr.sin_port,
+                    u16::from_be(addr.sin_port),
                 )))
             }
             AF_INET6 => {
                 let addr = addr.cast::<SOCKADDR_IN6>().as_ref();
                 Some(SocketAddr::V6(SocketAddrV6::new(
                     Ipv6Addr::from(addr.sin6_addr.u.Byte),
-                    addr.sin6_port,
+                    u16::from_be(addr.sin6_port),
                     addr.sin6_flowinfo,
                     addr.Anonymous.sin6_scope_id,
                 )))
@@ -276,13 +205,17 @@ impl SockAddr for SocketAddr {
         }
     }
 
-    unsafe fn with_native<T>(&self, f: impl FnOnce(*const SOCKADDR, i32) -> T) -> T {
+    unsafe fn with_native<T>(&self, f: impl FnOnce(*const RawSockAddr, SockAddrLen) -> T) -> T {
         match self {
             SocketAddr::V4(addr) => {
                 let native_addr = SOCKADDR_IN {
                     sin_family: AF_INET,
-                    sin_port: addr.port(),
-                    sin_addr: std::mem::transmute(u32::from_ne_bytes(addr.ip().octets())),
+                    sin_port: addr.port().to_be(),
+                    sin_addr: IN_ADDR {
+                        S_un: IN_ADDR_0 {
+                            S_addr: u32::from_ne_bytes(addr.ip().octets()),
+                        },
+                    },
                     sin_zero: std::mem::zeroed(),
                 };
                 f(
@@ -293,9 +226,13 @@ impl SockAddr for SocketAddr {
             SocketAddr::V6(addr) => {
                 let native_addr = SOCKADDR_IN6 {
                     sin6_family: AF_INET6,
-                    sin6_port: addr.port(),
+                    sin6_port: addr.port().to_be(),
                     sin6_flowinfo: 0,
-                    sin6_addr: std::mem::transmute(addr.ip().octets()),
+                    sin6_addr: IN6_ADDR {
+                        u: IN6_ADDR_0 {
+                            Byte: addr.ip().octets(),
+                        },
+                    },
                     Anonymous: std::mem::zeroed(),
                 };
                 f(
@@ -308,11 +245,11 @@ impl SockAddr for SocketAddr {
 }
 
 impl SockAddr for UnixSocketAddr {
-    fn domain(&self) -> ADDRESS_FAMILY {
+    fn domain(&self) -> AddressFamily {
         AF_UNIX
     }
 
-    unsafe fn try_from_native(addr: NonNull<SOCKADDR>, len: i32) -> Option<Self> {
+    unsafe fn try_from_native(addr: NonNull<RawSockAddr>, len: SockAddrLen) -> Option<Self> {
         let addr_ref = addr.as_ref();
         if addr_ref.sa_family == AF_UNIX {
             let addr = addr.cast::<SOCKADDR_UN>().as_ref();
@@ -326,7 +263,7 @@ impl SockAddr for UnixSocketAddr {
         }
     }
 
-    unsafe fn with_native<T>(&self, f: impl FnOnce(*const SOCKADDR, i32) -> T) -> T {
+    unsafe fn with_native<T>(&self, f: impl FnOnce(*const RawSockAddr, SockAddrLen) -> T) -> T {
         let addr = SOCKADDR_UN {
             sun_family: AF_UNIX,
             sun_path: self.path,
@@ -1,10 +1,9 @@
 use crate::{
     buf::*,
     net::{Socket, *},
-    *,
+    runtime, *,
 };
 use std::net::{Shutdown, SocketAddr, ToSocketAddrs};
-use windows_sys::Win32::Networking::WinSock::{IPPROTO_TCP, SOCK_STREAM, SOMAXCONN};
 
 /// A TCP socket server, listening for connections.
 ///
@@ -87,6 +86,7 @@ impl TcpListener {
 }
 
 impl_socket!(TcpListener, inner);
+impl_detached!(TcpListener, inner);
 
 /// A TCP stream between a local and a remote socket.
 ///
@@ -114,6 +114,9 @@ pub struct TcpStream {
 
 impl TcpStream {
     /// Opens a TCP connection to a remote host.
+    ///
+    /// Resolving a host name blocks the runtime thread, see
+    /// [`TcpStream::connect_async`] instead.
     pub async fn connect(addr: impl ToSocketAddrs) -> IoResult<Self> {
         super::each_addr_async(addr, |addr| async move {
             let socket = Socket::bind_any_like(addr, SOCK_STREAM, IPPROTO_TCP)?;
@@ -123,6 +126,14 @@ impl TcpStream {
         .await
     }
 
+    /// Opens a TCP connection to a remote host, resolving the address on the
+    /// blocking pool of the runtime. See [`TcpStream::connect`].
+    pub async fn connect_async(addr: impl ToSocketAddrs + Send + 'static) -> IoResult<Self> {
+        let addrs =
+            runtime::spawn_blocking(move || addr.to_socket_addrs().map(Vec::from_iter)).await?;
+        Self::connect(addrs.as_slice()).await
+    }
+
     /// Returns the socket address of the remote peer of this TCP connection.
     pub fn peer_addr(&self) -> IoResult<SocketAddr> {
         self.inner.peer_addr()
@@ -144,27 +155,46 @@ impl TcpStream {
 
     /// Receives a packet of data from the socket into the buffer, returning the original buffer and
     /// quantity of data received.
-    pub async fn recv<T: IoBufMut>(&self, buffer: T) -> BufResult<usize, T> {
-        self.inner.recv(buffer).await
+    pub fn recv<T: IoBufMut>(
+        &self,
+        buffer: T,
+    ) -> impl cancel::OpFuture<Output = BufResult<usize, T>> + use<'_, T> {
+        cancel::Op::new(async move { self.inner.recv(buffer).await })
     }
 
     /// Receives a packet of data from the socket into the buffer, returning the original buffer and
     /// quantity of data received.
-    pub async fn recv_vectored<T: IoBufMut>(&self, buffer: Vec<T>) -> BufResult<usize, Vec<T>> {
-        self.inner.recv_vectored(buffer).await
+    pub fn recv_vectored<T: IoBufMut>(
+        &self,
+        buffer: Vec<T>,
+    ) -> impl cancel::OpFuture<Output = BufResult<usize, Vec<T>>> + use<'_, T> {
+        cancel::Op::new(async move { self.inner.recv_vectored(buffer).await })
+    }
+
+    /// Creates a cancellation safe receiving half of the stream. See
+    /// [`RecvHalf`].
+    pub fn recv_half<T: IoBufMut>(&self) -> RecvHalf<'_, T> {
+        RecvHalf::new(&self.inner)
     }
 
     /// Sends some data to the socket from the buffer, returning the original buffer and
     /// quantity of data sent.
-    pub async fn send<T: IoBuf>(&self, buffer: T) -> BufResult<usize, T> {
-        self.inner.send(buffer).await
+    pub fn send<T: IoBuf>(
+        &self,
+        buffer: T,
+    ) -> impl cancel::OpFuture<Output = BufResult<usize, T>> + use<'_, T> {
+        cancel::Op::new(async move { self.inner.send(buffer).await })
     }
 
     /// Sends some data to the socket from the buffer, returning the original buffer and
     /// quantity of data sent.
-    pub async fn send_vectored<T: IoBuf>(&self, buffer: Vec<T>) -> BufResult<usize, Vec<T>> {
-        self.inner.send_vectored(buffer).await
+    pub fn send_vectored<T: IoBuf>(
+        &self,
+        buffer: Vec<T>,
+    ) -> impl cancel::OpFuture<Output = BufResult<usize, Vec<T>>> + use<'_, T> {
+        cancel::Op::new(async move { self.inner.send_vectored(buffer).await })
     }
 }
 
 impl_socket!(TcpStream, inner);
+impl_detached!(TcpStream, inner);
@@ -4,7 +4,6 @@ use crate::{
     *,
 };
 use std::net::{SocketAddr, ToSocketAddrs};
-use windows_sys::Win32::Networking::WinSock::{IPPROTO_UDP, SOCK_DGRAM};
 
 /// A UDP socket.
 ///
@@ -143,58 +142,78 @@ impl UdpSocket {
 
     /// Receives a packet of data from the socket into the buffer, returning the original buffer and
     /// quantity of data received.
-    pub async fn recv<T: IoBufMut>(&self, buffer: T) -> BufResult<usize, T> {
-        self.inner.recv(buffer).await
+    pub fn recv<T: IoBufMut>(
+        &self,
+        buffer: T,
+    ) -> impl cancel::OpFuture<Output = BufResult<usize, T>> + use<'_, T> {
+        cancel::Op::new(async move { self.inner.recv(buffer).await })
     }
 
     /// Receives a packet of data from the socket into the buffer, returning the original buffer and
     /// quantity of data received.
-    pub async fn recv_vectored<T: IoBufMut>(&self, buffer: Vec<T>) -> BufResult<usize, Vec<T>> {
-        self.inner.recv_vectored(buffer).await
+    pub fn recv_vectored<T: IoBufMut>(
+        &self,
+        buffer: Vec<T>,
+    ) -> impl cancel::OpFuture<Output = BufResult<usize, Vec<T>>> + use<'_, T> {
+        cancel::Op::new(async move { self.inner.recv_vectored(buffer).await })
     }
 
     /// Sends some data to the socket from the buffer, returning the original buffer and
     /// quantity of data sent.
-    pub async fn send<T: IoBuf>(&self, buffer: T) -> BufResult<usize, T> {
-        self.inner.send(buffer).await
+    pub fn send<T: IoBuf>(
+        &self,
+        buffer: T,
+    ) -> impl cancel::OpFuture<Output = BufResult<usize, T>> + use<'_, T> {
+        cancel::Op::new(async move { self.inner.send(buffer).await })
     }
 
     /// Sends some data to the socket from the buffer, returning the original buffer and
     /// quantity of data sent.
-    pub async fn send_vectored<T: IoBuf>(&self, buffer: Vec<T>) -> BufResult<usize, Vec<T>> {
-        self.inner.send_vectored(buffer).await
+    pub fn send_vectored<T: IoBuf>(
+        &self,
+        buffer: Vec<T>,
+    ) -> impl cancel::OpFuture<Output = BufResult<usize, Vec<T>>> + use<'_, T> {
+        cancel::Op::new(async move { self.inner.send_vectored(buffer).await })
     }
 
     /// Receives a single datagram message on the socket. On success, returns
     /// the number of bytes received and the origin.
-    pub async fn recv_from<T: IoBufMut>(&self, buffer: T) -> BufResult<(usize, SocketAddr), T> {
-        self.inner.recv_from(buffer).await
+    pub fn recv_from<T: IoBufMut>(
+        &self,
+        buffer: T,
+    ) -> impl cancel::OpFuture<Output = BufResult<(usize, SocketAddr), T>> + use<'_, T> {
+        cancel::Op::new(async move { self.inner.recv_from(buffer).await })
     }
 
     /// Receives a single datagram message on the socket. On success, returns
     /// the number of bytes received and the origin.
-    pub async fn recv_from_vectored<T: IoBufMut>(
+    pub fn recv_from_vectored<T: IoBufMut>(
         &self,
         buffer: Vec<T>,
-    ) -> BufResult<(usize, SocketAddr), Vec<T>> {
-        self.inner.recv_from_vectored(buffer).await
+    ) -> impl cancel::OpFuture<Output = BufResult<(usize, SocketAddr), Vec<T>>> + use<'_, T> {
+        cancel::Op::new(async move { self.inner.recv_from_vectored(buffer).await })
     }
 
     /// Sends data on the socket to the given address. On success, returns the
     /// number of bytes sent.
-    pub async fn send_to<T: IoBuf>(&self, buffer: T, addr: SocketAddr) -> BufResult<usize, T> {
-        self.inner.send_to(buffer, addr).await
+    pub fn send_to<T: IoBuf>(
+        &self,
+        buffer: T,
+        addr: SocketAddr,
+    ) -> impl cancel::OpFuture<Output = BufResult<usize, T>> + use<'_, T> {
+        cancel::Op::new(async move { self.inner.send_to(buffer, addr).await })
     }
 
     /// Sends data on the socket to the given address. On success, returns the
     /// number of bytes sent.
-    pub async fn send_to_vectored<T: IoBuf>(
+    pub fn send_to_vectored<T: IoBuf>(
         &self,
         buffer: Vec<T>,
         addr: SocketAddr,
-    ) -> BufResult<usize, Vec<T>> {
-        self.inner.send_to_vectored(buffer, addr).await
+    ) -> impl cancel::OpFuture<Output = BufResult<usize, Vec<T>>> + use<'_, T> {
+        cancel::Op::new(async move { self.inner.send_to_vectored(buffer, addr).await })
     }
 }
 
 impl_socket!(UdpSocket, inner);
+impl_detached!(UdpSocket, inner);
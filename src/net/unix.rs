@@ -1,10 +1,9 @@
 use crate::{
     buf::*,
     net::{Socket, *},
-    *,
+    runtime, *,
 };
 use std::{net::Shutdown, path::Path, str::FromStr};
-use windows_sys::Win32::Networking::WinSock::{AF_UNIX, IPPROTO_HOPOPTS, SOCK_STREAM};
 
 const UNIX_MAX_PATH: usize = 108;
 
@@ -204,10 +203,7 @@ impl UnixListener {
     /// is established. When established, the corresponding [`UnixStream`] and
     /// will be returned.
     pub async fn accept(&self) -> IoResult<(UnixStream, UnixSocketAddr)> {
-        let (socket, addr) = self
-            .inner
-            .accept::<UnixSocketAddr>(SOCK_STREAM, IPPROTO_HOPOPTS)
-            .await?;
+        let (socket, addr) = self.inner.accept::<UnixSocketAddr>(SOCK_STREAM, 0).await?;
         let stream = UnixStream { inner: socket };
         Ok((stream, addr))
     }
@@ -234,6 +230,7 @@ impl UnixListener {
 }
 
 impl_socket!(UnixListener, inner);
+impl_detached!(UnixListener, inner);
 
 /// A Unix stream between two local sockets on Windows & WSL.
 ///
@@ -270,12 +267,32 @@ impl UnixStream {
     /// [`UnixListener`] or equivalent listening on the corresponding Unix domain socket
     /// to successfully connect and return a `UnixStream`.
     pub fn connect_addr(addr: UnixSocketAddr) -> IoResult<Self> {
-        let socket = Socket::new(AF_UNIX, SOCK_STREAM, IPPROTO_HOPOPTS)?;
+        let socket = Socket::new(AF_UNIX, SOCK_STREAM, 0)?;
         socket.connect(addr)?;
         let unix_stream = UnixStream { inner: socket };
         Ok(unix_stream)
     }
 
+    /// Opens a Unix connection to the specified file path, on the blocking
+    /// pool of the runtime. See [`UnixStream::connect`].
+    pub async fn connect_async(path: impl AsRef<Path>) -> IoResult<Self> {
+        Self::connect_addr_async(UnixSocketAddr::from_pathname(path)?).await
+    }
+
+    /// Opens a Unix connection to the specified address, on the blocking pool
+    /// of the runtime. See [`UnixStream::connect_addr`].
+    pub async fn connect_addr_async(addr: UnixSocketAddr) -> IoResult<Self> {
+        let socket = Socket::new(AF_UNIX, SOCK_STREAM, 0)?;
+        // The socket is moved to the pool, and closed there if the future is
+        // dropped.
+        let socket = runtime::spawn_blocking(move || {
+            socket.connect(addr)?;
+            Ok(socket)
+        })
+        .await?;
+        Ok(UnixStream { inner: socket })
+    }
+
     /// Returns the socket path of the remote peer of this connection.
     pub fn peer_addr(&self) -> IoResult<UnixSocketAddr> {
         self.inner.peer_addr()
@@ -297,27 +314,46 @@ impl UnixStream {
 
     /// Receives a packet of data from the socket into the buffer, returning the original buffer and
     /// quantity of data received.
-    pub async fn recv<T: IoBufMut>(&self, buffer: T) -> BufResult<usize, T> {
-        self.inner.recv(buffer).await
+    pub fn recv<T: IoBufMut>(
+        &self,
+        buffer: T,
+    ) -> impl cancel::OpFuture<Output = BufResult<usize, T>> + use<'_, T> {
+        cancel::Op::new(async move { self.inner.recv(buffer).await })
     }
 
     /// Receives a packet of data from the socket into the buffer, returning the original buffer and
     /// quantity of data received.
-    pub async fn recv_vectored<T: IoBufMut>(&self, buffer: Vec<T>) -> BufResult<usize, Vec<T>> {
-        self.inner.recv_vectored(buffer).await
+    pub fn recv_vectored<T: IoBufMut>(
+        &self,
+        buffer: Vec<T>,
+    ) -> impl cancel::OpFuture<Output = BufResult<usize, Vec<T>>> + use<'_, T> {
+        cancel::Op::new(async move { self.inner.recv_vectored(buffer).await })
+    }
+
+    /// Creates a cancellation safe receiving half of the stream. See
+    /// [`RecvHalf`].
+    pub fn recv_half<T: IoBufMut>(&self) -> RecvHalf<'_, T> {
+        RecvHalf::new(&self.inner)
     }
 
     /// Sends some data to the socket from the buffer, returning the original buffer and
     /// quantity of data sent.
-    pub async fn send<T: IoBuf>(&self, buffer: T) -> BufResult<usize, T> {
-        self.inner.send(buffer).await
+    pub fn send<T: IoBuf>(
+        &self,
+        buffer: T,
+    ) -> impl cancel::OpFuture<Output = BufResult<usize, T>> + use<'_, T> {
+        cancel::Op::new(async move { self.inner.send(buffer).await })
     }
 
     /// Sends some data to the socket from the buffer, returning the original buffer and
     /// quantity of data sent.
-    pub async fn send_vectored<T: IoBuf>(&self, buffer: Vec<T>) -> BufResult<usize, Vec<T>> {
-        self.inner.send_vectored(buffer).await
+    pub fn send_vectored<T: IoBuf>(
+        &self,
+        buffer: Vec<T>,
+    ) -> impl cancel::OpFuture<Output = BufResult<usize, Vec<T>>> + use<'_, T> {
+        cancel::Op::new(async move { self.inner.send_vectored(buffer).await })
     }
 }
 
 impl_socket!(UnixStream, inner);
+impl_detached!(UnixStream, inner);
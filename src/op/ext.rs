@@ -0,0 +1,205 @@
+use crate::{
+    buf::*,
+    io_port::{OpCodeExt, OpKind},
+    net::SockAddr,
+    op::*,
+};
+use std::any::Any;
+
+/// Copies `data` into the buffer descriptors in order.
+fn fill_io_vec(ptr: *const IoVec, len: usize, data: &[u8]) -> usize {
+    let slices = unsafe { std::slice::from_raw_parts(ptr, len) };
+    let mut copied = 0;
+    for slice in slices {
+        let (ptr, len) = io_vec_parts(slice);
+        let len = len.min(data.len() - copied);
+        unsafe { std::ptr::copy_nonoverlapping(data[copied..].as_ptr(), ptr, len) };
+        copied += len;
+    }
+    copied
+}
+
+fn io_vec_len(ptr: *const IoVec, len: usize) -> usize {
+    let slices = unsafe { std::slice::from_raw_parts(ptr, len) };
+    slices.iter().map(|slice| io_vec_parts(slice).1).sum()
+}
+
+fn io_vec_data(ptr: *const IoVec, len: usize) -> Vec<u8> {
+    let slices = unsafe { std::slice::from_raw_parts(ptr, len) };
+    slices
+        .iter()
+        .flat_map(|slice| {
+            let (ptr, len) = io_vec_parts(slice);
+            unsafe { std::slice::from_raw_parts(ptr, len) }
+        })
+        .copied()
+        .collect()
+}
+
+impl<T: WithBufMut> OpCodeExt for ReadAt<T> {
+    fn kind(&self) -> OpKind {
+        OpKind::Read
+    }
+
+    fn fill(&mut self, data: &[u8]) -> usize {
+        self.buffer.with_buf_mut(|ptr, len| {
+            let len = len.min(data.len());
+            unsafe { std::ptr::copy_nonoverlapping(data.as_ptr(), ptr, len) };
+            len
+        })
+    }
+
+    fn buf_len(&mut self) -> usize {
+        self.buffer.with_buf_mut(|_, len| len)
+    }
+
+    #[cfg(feature = "tracing")]
+    fn offset(&self) -> Option<usize> {
+        Some(self.pos)
+    }
+
+    fn set_cap(&mut self, cap: usize) {
+        self.buffer.set_cap(cap)
+    }
+
+    fn into_buffer(self) -> Option<Box<dyn Any>> {
+        Some(Box::new(self.into_inner()))
+    }
+}
+
+impl<T: WithBuf> OpCodeExt for WriteAt<T> {
+    fn kind(&self) -> OpKind {
+        OpKind::Write
+    }
+
+    fn buf_len(&mut self) -> usize {
+        self.buffer.with_buf(|_, len| len)
+    }
+
+    #[cfg(feature = "tracing")]
+    fn offset(&self) -> Option<usize> {
+        Some(self.pos)
+    }
+
+    fn data(&self) -> Vec<u8> {
+        self.buffer
+            .with_buf(|ptr, len| unsafe { std::slice::from_raw_parts(ptr, len) }.to_vec())
+    }
+
+    fn set_cap(&mut self, cap: usize) {
+        self.buffer.set_cap(cap)
+    }
+
+    fn into_buffer(self) -> Option<Box<dyn Any>> {
+        Some(Box::new(self.into_inner()))
+    }
+}
+
+impl OpCodeExt for Accept {
+    fn kind(&self) -> OpKind {
+        OpKind::Accept
+    }
+}
+
+impl<A: SockAddr> OpCodeExt for Connect<A> {
+    fn kind(&self) -> OpKind {
+        OpKind::Connect
+    }
+}
+
+impl<T: WithIoVecMut> OpCodeExt for Recv<T> {
+    fn kind(&self) -> OpKind {
+        OpKind::Recv
+    }
+
+    fn fill(&mut self, data: &[u8]) -> usize {
+        self.buffer
+            .with_io_vec_mut(|ptr, len| fill_io_vec(ptr, len, data))
+    }
+
+    fn buf_len(&mut self) -> usize {
+        self.buffer.with_io_vec_mut(io_vec_len)
+    }
+
+    fn set_cap(&mut self, cap: usize) {
+        self.buffer.set_cap(cap)
+    }
+
+    fn into_buffer(self) -> Option<Box<dyn Any>> {
+        Some(Box::new(self.into_inner()))
+    }
+}
+
+impl<T: WithIoVec> OpCodeExt for Send<T> {
+    fn kind(&self) -> OpKind {
+        OpKind::Send
+    }
+
+    fn buf_len(&mut self) -> usize {
+        self.buffer.with_io_vec(io_vec_len)
+    }
+
+    fn data(&self) -> Vec<u8> {
+        self.buffer.with_io_vec(io_vec_data)
+    }
+
+    fn set_cap(&mut self, cap: usize) {
+        self.buffer.set_cap(cap)
+    }
+
+    fn into_buffer(self) -> Option<Box<dyn Any>> {
+        Some(Box::new(self.into_inner()))
+    }
+}
+
+impl<T: WithIoVecMut> OpCodeExt for RecvFrom<T> {
+    fn kind(&self) -> OpKind {
+        OpKind::RecvFrom
+    }
+
+    fn fill(&mut self, data: &[u8]) -> usize {
+        self.buffer
+            .with_io_vec_mut(|ptr, len| fill_io_vec(ptr, len, data))
+    }
+
+    fn buf_len(&mut self) -> usize {
+        self.buffer.with_io_vec_mut(io_vec_len)
+    }
+
+    fn set_cap(&mut self, cap: usize) {
+        self.buffer.set_cap(cap)
+    }
+
+    fn into_buffer(self) -> Option<Box<dyn Any>> {
+        Some(Box::new(self.into_inner()))
+    }
+}
+
+impl<T: WithIoVec, A: SockAddr> OpCodeExt for SendTo<T, A> {
+    fn kind(&self) -> OpKind {
+        OpKind::SendTo
+    }
+
+    fn buf_len(&mut self) -> usize {
+        self.buffer.with_io_vec(io_vec_len)
+    }
+
+    fn data(&self) -> Vec<u8> {
+        self.buffer.with_io_vec(io_vec_data)
+    }
+
+    fn set_cap(&mut self, cap: usize) {
+        self.buffer.set_cap(cap)
+    }
+
+    fn into_buffer(self) -> Option<Box<dyn Any>> {
+        Some(Box::new(self.into_inner()))
+    }
+}
+
+#[cfg(windows)]
+impl OpCodeExt for ConnectNamedPipe {
+    fn kind(&self) -> OpKind {
+        OpKind::ConnectNamedPipe
+    }
+}
@@ -0,0 +1,308 @@
+use crate::{
+    buf::*,
+    io_port::{BlockingCall, Interest, OpCode, RawRes},
+    net::{SockAddr, SockAddrLen, MAX_ADDR_SIZE},
+    op::*,
+    *,
+};
+use io_uring::{opcode, squeue::Entry, types::Fd};
+use std::task::Poll;
+
+/// Maps the result of a non-blocking syscall returning `-1` on error.
+fn syscall_result(res: isize) -> Poll<IoResult<usize>> {
+    if res >= 0 {
+        Poll::Ready(Ok(res as _))
+    } else {
+        let error = IoError::last_os_error();
+        match error.raw_os_error() {
+            Some(libc::EAGAIN) | Some(libc::EINTR) => Poll::Pending,
+            _ => Poll::Ready(Err(error)),
+        }
+    }
+}
+
+/// The message header of `sendmsg` and `recvmsg`.
+///
+/// The buffer descriptors and the address should live until completion.
+pub struct MsgHeader {
+    // The descriptor of a single buffer is stored inline.
+    slice: IoVec,
+    slices: Vec<IoVec>,
+    name: SockAddrBuffer,
+    msg: libc::msghdr,
+}
+
+impl MsgHeader {
+    pub fn new() -> Self {
+        Self {
+            slice: io_vec(std::ptr::null(), 0),
+            slices: vec![],
+            name: sock_addr_buffer(),
+            msg: unsafe { std::mem::zeroed() },
+        }
+    }
+
+    fn set_slices(&mut self, ptr: *const IoVec, len: usize) {
+        let slices = unsafe { std::slice::from_raw_parts(ptr, len) };
+        self.msg.msg_iov = match slices {
+            [slice] => {
+                self.slice = *slice;
+                &mut self.slice
+            }
+            _ => {
+                self.slices = slices.to_vec();
+                self.slices.as_mut_ptr()
+            }
+        };
+        self.msg.msg_iovlen = len as _;
+    }
+
+    fn set_name(&mut self, addr: &impl SockAddr) {
+        let len = unsafe {
+            addr.with_native(|addr, len| {
+                std::ptr::copy_nonoverlapping(
+                    addr.cast::<u8>(),
+                    self.name.as_mut_ptr(),
+                    len as usize,
+                );
+                len
+            })
+        };
+        self.msg.msg_name = self.name.as_mut_ptr() as _;
+        self.msg.msg_namelen = len;
+    }
+
+    fn set_name_buffer(&mut self) {
+        self.msg.msg_name = self.name.as_mut_ptr() as _;
+        self.msg.msg_namelen = MAX_ADDR_SIZE as _;
+    }
+}
+
+impl<T: WithBufMut> OpCode for ReadAt<T> {
+    fn create_entry(&mut self, fd: RawRes) -> Entry {
+        self.buffer.with_buf_mut(|ptr, len| {
+            opcode::Read::new(Fd(fd), ptr, len as _)
+                .offset(self.pos as _)
+                .build()
+        })
+    }
+
+    fn interest(&self) -> Interest {
+        Interest::Readable
+    }
+
+    fn operate(&mut self, fd: RawRes) -> Poll<IoResult<usize>> {
+        let pos = self.pos;
+        let res = self
+            .buffer
+            .with_buf_mut(|ptr, len| unsafe { libc::pread(fd, ptr as _, len, pos as _) });
+        syscall_result(res)
+    }
+
+    fn blocking_call(&mut self) -> Option<BlockingCall> {
+        let pos = self.pos;
+        Some(
+            self.buffer
+                .with_buf_mut(|ptr, len| BlockingCall::Read { ptr, len, pos }),
+        )
+    }
+}
+
+impl<T: WithBuf> OpCode for WriteAt<T> {
+    fn create_entry(&mut self, fd: RawRes) -> Entry {
+        self.buffer.with_buf(|ptr, len| {
+            opcode::Write::new(Fd(fd), ptr, len as _)
+                .offset(self.pos as _)
+                .build()
+        })
+    }
+
+    fn interest(&self) -> Interest {
+        Interest::Writable
+    }
+
+    fn operate(&mut self, fd: RawRes) -> Poll<IoResult<usize>> {
+        let pos = self.pos;
+        let res = self
+            .buffer
+            .with_buf(|ptr, len| unsafe { libc::pwrite(fd, ptr as _, len, pos as _) });
+        syscall_result(res)
+    }
+
+    fn blocking_call(&mut self) -> Option<BlockingCall> {
+        let pos = self.pos;
+        Some(
+            self.buffer
+                .with_buf(|ptr, len| BlockingCall::Write { ptr, len, pos }),
+        )
+    }
+}
+
+impl OpCode for Accept {
+    fn create_entry(&mut self, fd: RawRes) -> Entry {
+        opcode::Accept::new(
+            Fd(fd),
+            self.buffer.as_mut_ptr() as _,
+            &mut self.addr_len as *mut SockAddrLen,
+        )
+        .flags(libc::SOCK_CLOEXEC)
+        .build()
+    }
+
+    fn interest(&self) -> Interest {
+        Interest::Readable
+    }
+
+    fn operate(&mut self, fd: RawRes) -> Poll<IoResult<usize>> {
+        self.addr_len = MAX_ADDR_SIZE as _;
+        let res = unsafe {
+            libc::accept4(
+                fd,
+                self.buffer.as_mut_ptr() as _,
+                &mut self.addr_len,
+                libc::SOCK_CLOEXEC,
+            )
+        };
+        syscall_result(res as _)
+    }
+}
+
+impl<A: SockAddr> Connect<A> {
+    fn native_addr(&mut self) -> SockAddrLen {
+        unsafe {
+            self.addr.with_native(|addr, len| {
+                std::ptr::copy_nonoverlapping(
+                    addr.cast::<u8>(),
+                    self.native_addr.as_mut_ptr(),
+                    len as usize,
+                );
+                len
+            })
+        }
+    }
+}
+
+impl<A: SockAddr> OpCode for Connect<A> {
+    fn create_entry(&mut self, fd: RawRes) -> Entry {
+        let len = self.native_addr();
+        opcode::Connect::new(Fd(fd), self.native_addr.as_ptr() as _, len).build()
+    }
+
+    fn interest(&self) -> Interest {
+        Interest::Writable
+    }
+
+    fn operate(&mut self, fd: RawRes) -> Poll<IoResult<usize>> {
+        let len = self.native_addr();
+        let res = unsafe { libc::connect(fd, self.native_addr.as_ptr() as _, len) };
+        if res == 0 {
+            Poll::Ready(Ok(0))
+        } else {
+            let error = IoError::last_os_error();
+            match error.raw_os_error() {
+                Some(libc::EINPROGRESS) | Some(libc::EALREADY) | Some(libc::EINTR) => Poll::Pending,
+                // Retrying a connection which has been established.
+                Some(libc::EISCONN) => Poll::Ready(Ok(0)),
+                _ => Poll::Ready(Err(error)),
+            }
+        }
+    }
+}
+
+impl<T: WithIoVecMut> Recv<T> {
+    fn msg(&mut self) -> &mut libc::msghdr {
+        let msg = &mut self.msg;
+        self.buffer
+            .with_io_vec_mut(|ptr, len| msg.set_slices(ptr, len));
+        &mut msg.msg
+    }
+}
+
+impl<T: WithIoVecMut> OpCode for Recv<T> {
+    fn create_entry(&mut self, fd: RawRes) -> Entry {
+        opcode::RecvMsg::new(Fd(fd), self.msg()).build()
+    }
+
+    fn interest(&self) -> Interest {
+        Interest::Readable
+    }
+
+    fn operate(&mut self, fd: RawRes) -> Poll<IoResult<usize>> {
+        syscall_result(unsafe { libc::recvmsg(fd, self.msg(), 0) })
+    }
+}
+
+impl<T: WithIoVec> Send<T> {
+    fn msg(&mut self) -> &libc::msghdr {
+        let msg = &mut self.msg;
+        self.buffer.with_io_vec(|ptr, len| msg.set_slices(ptr, len));
+        &msg.msg
+    }
+}
+
+impl<T: WithIoVec> OpCode for Send<T> {
+    fn create_entry(&mut self, fd: RawRes) -> Entry {
+        opcode::SendMsg::new(Fd(fd), self.msg()).build()
+    }
+
+    fn interest(&self) -> Interest {
+        Interest::Writable
+    }
+
+    fn operate(&mut self, fd: RawRes) -> Poll<IoResult<usize>> {
+        syscall_result(unsafe { libc::sendmsg(fd, self.msg(), libc::MSG_NOSIGNAL) })
+    }
+}
+
+impl<T> RecvFrom<T> {
+    pub(crate) fn addr(&self) -> (*const u8, SockAddrLen) {
+        (self.msg.name.as_ptr(), self.msg.msg.msg_namelen)
+    }
+}
+
+impl<T: WithIoVecMut> RecvFrom<T> {
+    fn msg(&mut self) -> &mut libc::msghdr {
+        let msg = &mut self.msg;
+        self.buffer
+            .with_io_vec_mut(|ptr, len| msg.set_slices(ptr, len));
+        msg.set_name_buffer();
+        &mut msg.msg
+    }
+}
+
+impl<T: WithIoVecMut> OpCode for RecvFrom<T> {
+    fn create_entry(&mut self, fd: RawRes) -> Entry {
+        opcode::RecvMsg::new(Fd(fd), self.msg()).build()
+    }
+
+    fn interest(&self) -> Interest {
+        Interest::Readable
+    }
+
+    fn operate(&mut self, fd: RawRes) -> Poll<IoResult<usize>> {
+        syscall_result(unsafe { libc::recvmsg(fd, self.msg(), 0) })
+    }
+}
+
+impl<T: WithIoVec, A: SockAddr> SendTo<T, A> {
+    fn msg(&mut self) -> &libc::msghdr {
+        let msg = &mut self.msg;
+        self.buffer.with_io_vec(|ptr, len| msg.set_slices(ptr, len));
+        msg.set_name(&self.addr);
+        &msg.msg
+    }
+}
+
+impl<T: WithIoVec, A: SockAddr> OpCode for SendTo<T, A> {
+    fn create_entry(&mut self, fd: RawRes) -> Entry {
+        opcode::SendMsg::new(Fd(fd), self.msg()).build()
+    }
+
+    fn interest(&self) -> Interest {
+        Interest::Writable
+    }
+
+    fn operate(&mut self, fd: RawRes) -> Poll<IoResult<usize>> {
+        syscall_result(unsafe { libc::sendmsg(fd, self.msg(), libc::MSG_NOSIGNAL) })
+    }
+}
@@ -0,0 +1,373 @@
+#[cfg(target_os = "linux")]
+mod linux;
+#[cfg(target_os = "linux")]
+use linux::MsgHeader;
+
+#[cfg(windows)]
+mod windows;
+
+mod ext;
+#[cfg(windows)]
+pub use windows::accept_result;
+
+#[cfg(windows)]
+use crate::io_port::RawRes;
+use crate::{
+    buf::*,
+    io_port::{BorrowedRes, IocpFuture},
+    net::{SockAddr, SockAddrLen, MAX_ADDR_SIZE},
+    *,
+};
+use aligned_array::{Aligned, A4};
+use std::ptr::NonNull;
+
+/// A buffer large enough to hold any socket address.
+pub type SockAddrBuffer = Aligned<A4, [u8; MAX_ADDR_SIZE]>;
+
+fn sock_addr_buffer() -> SockAddrBuffer {
+    Aligned([0; MAX_ADDR_SIZE])
+}
+
+/// Operations owning a buffer, which is returned after completion.
+pub trait IntoInner {
+    type Inner;
+
+    fn into_inner(self) -> Self::Inner;
+}
+
+pub struct ReadAt<T> {
+    buffer: T,
+    pos: usize,
+}
+
+impl<T: WrapBuf> IntoInner for ReadAt<T> {
+    type Inner = T::Buffer;
+
+    fn into_inner(self) -> Self::Inner {
+        self.buffer.into_inner()
+    }
+}
+
+impl<T: WrapBufMut> WrapBufMut for ReadAt<T> {
+    fn set_init(&mut self, len: usize) {
+        self.buffer.set_init(len)
+    }
+}
+
+pub fn read_at<'a, T: IoBufMut>(
+    handle: impl Into<BorrowedRes<'a>>,
+    buffer: T,
+    pos: usize,
+) -> IocpFuture<'a, ReadAt<BufWrapper<T>>> {
+    IocpFuture::new(
+        handle,
+        ReadAt {
+            buffer: BufWrapper::new(buffer),
+            pos,
+        },
+    )
+}
+
+pub struct WriteAt<T> {
+    buffer: T,
+    pos: usize,
+}
+
+impl<T: WrapBuf> IntoInner for WriteAt<T> {
+    type Inner = T::Buffer;
+
+    fn into_inner(self) -> Self::Inner {
+        self.buffer.into_inner()
+    }
+}
+
+pub fn write_at<'a, T: IoBuf>(
+    handle: impl Into<BorrowedRes<'a>>,
+    buffer: T,
+    pos: usize,
+) -> IocpFuture<'a, WriteAt<BufWrapper<T>>> {
+    IocpFuture::new(
+        handle,
+        WriteAt {
+            buffer: BufWrapper::new(buffer),
+            pos,
+        },
+    )
+}
+
+/// Accepts a connection.
+///
+/// On Windows, the accepted connection is `accept_handle`, and the result is
+/// `0`. On Linux, the result is the new file descriptor.
+pub struct Accept {
+    #[cfg(windows)]
+    accept_handle: RawRes,
+    #[cfg(windows)]
+    pub(crate) buffer: Aligned<A4, [u8; MAX_ADDR_SIZE * 2]>,
+    #[cfg(target_os = "linux")]
+    pub(crate) buffer: SockAddrBuffer,
+    #[cfg(target_os = "linux")]
+    pub(crate) addr_len: SockAddrLen,
+}
+
+#[cfg(windows)]
+pub fn accept<'a>(
+    handle: impl Into<BorrowedRes<'a>>,
+    accept_handle: RawRes,
+) -> IocpFuture<'a, Accept> {
+    IocpFuture::new(
+        handle,
+        Accept {
+            accept_handle,
+            buffer: Aligned([0; MAX_ADDR_SIZE * 2]),
+        },
+    )
+}
+
+#[cfg(target_os = "linux")]
+pub fn accept<'a>(handle: impl Into<BorrowedRes<'a>>) -> IocpFuture<'a, Accept> {
+    IocpFuture::new(
+        handle,
+        Accept {
+            buffer: sock_addr_buffer(),
+            addr_len: MAX_ADDR_SIZE as _,
+        },
+    )
+}
+
+#[cfg(target_os = "linux")]
+pub fn accept_result<A: SockAddr>(accept: &Accept) -> IoResult<A> {
+    unsafe {
+        A::try_from_native(
+            NonNull::new_unchecked(accept.buffer.as_ptr() as _),
+            accept.addr_len,
+        )
+    }
+    .ok_or_else(|| IoError::new(std::io::ErrorKind::InvalidData, "invalid address"))
+}
+
+pub struct Connect<A> {
+    addr: A,
+    #[cfg(target_os = "linux")]
+    native_addr: SockAddrBuffer,
+}
+
+pub fn connect<'a, A: SockAddr>(
+    handle: impl Into<BorrowedRes<'a>>,
+    addr: A,
+) -> IocpFuture<'a, Connect<A>> {
+    IocpFuture::new(
+        handle,
+        Connect {
+            addr,
+            #[cfg(target_os = "linux")]
+            native_addr: sock_addr_buffer(),
+        },
+    )
+}
+
+pub struct Recv<T> {
+    buffer: T,
+    #[cfg(target_os = "linux")]
+    msg: MsgHeader,
+}
+
+impl<T: WrapBuf> IntoInner for Recv<T> {
+    type Inner = T::Buffer;
+
+    fn into_inner(self) -> Self::Inner {
+        self.buffer.into_inner()
+    }
+}
+
+impl<T: WrapBufMut> WrapBufMut for Recv<T> {
+    fn set_init(&mut self, len: usize) {
+        self.buffer.set_init(len)
+    }
+}
+
+pub fn recv<'a, T: WithIoVecMut>(
+    handle: impl Into<BorrowedRes<'a>>,
+    buffer: T::Buffer,
+) -> IocpFuture<'a, Recv<T>> {
+    IocpFuture::new(
+        handle,
+        Recv {
+            buffer: T::new(buffer),
+            #[cfg(target_os = "linux")]
+            msg: MsgHeader::new(),
+        },
+    )
+}
+
+pub struct Send<T> {
+    buffer: T,
+    #[cfg(target_os = "linux")]
+    msg: MsgHeader,
+}
+
+impl<T: WrapBuf> IntoInner for Send<T> {
+    type Inner = T::Buffer;
+
+    fn into_inner(self) -> Self::Inner {
+        self.buffer.into_inner()
+    }
+}
+
+pub fn send<'a, T: WithIoVec>(
+    handle: impl Into<BorrowedRes<'a>>,
+    buffer: T::Buffer,
+) -> IocpFuture<'a, Send<T>> {
+    IocpFuture::new(
+        handle,
+        Send {
+            buffer: T::new(buffer),
+            #[cfg(target_os = "linux")]
+            msg: MsgHeader::new(),
+        },
+    )
+}
+
+pub struct RecvFrom<T> {
+    buffer: T,
+    #[cfg(windows)]
+    addr: SockAddrBuffer,
+    #[cfg(windows)]
+    addr_len: SockAddrLen,
+    #[cfg(target_os = "linux")]
+    msg: MsgHeader,
+}
+
+impl<T: WrapBuf> IntoInner for RecvFrom<T> {
+    type Inner = T::Buffer;
+
+    fn into_inner(self) -> Self::Inner {
+        self.buffer.into_inner()
+    }
+}
+
+impl<T: WrapBufMut> WrapBufMut for RecvFrom<T> {
+    fn set_init(&mut self, len: usize) {
+        self.buffer.set_init(len)
+    }
+}
+
+pub fn recv_from<'a, T: WithIoVecMut>(
+    handle: impl Into<BorrowedRes<'a>>,
+    buffer: T::Buffer,
+) -> IocpFuture<'a, RecvFrom<T>> {
+    IocpFuture::new(
+        handle,
+        RecvFrom {
+            buffer: T::new(buffer),
+            #[cfg(windows)]
+            addr: sock_addr_buffer(),
+            #[cfg(windows)]
+            addr_len: MAX_ADDR_SIZE as _,
+            #[cfg(target_os = "linux")]
+            msg: MsgHeader::new(),
+        },
+    )
+}
+
+pub struct SendTo<T, A> {
+    buffer: T,
+    addr: A,
+    #[cfg(target_os = "linux")]
+    msg: MsgHeader,
+}
+
+impl<T: WrapBuf, A> IntoInner for SendTo<T, A> {
+    type Inner = T::Buffer;
+
+    fn into_inner(self) -> Self::Inner {
+        self.buffer.into_inner()
+    }
+}
+
+pub fn send_to<'a, T: WithIoVec, A: SockAddr>(
+    handle: impl Into<BorrowedRes<'a>>,
+    buffer: T::Buffer,
+    addr: A,
+) -> IocpFuture<'a, SendTo<T, A>> {
+    IocpFuture::new(
+        handle,
+        SendTo {
+            buffer: T::new(buffer),
+            addr,
+            #[cfg(target_os = "linux")]
+            msg: MsgHeader::new(),
+        },
+    )
+}
+
+#[cfg(windows)]
+pub struct ConnectNamedPipe;
+
+#[cfg(windows)]
+pub fn connect_named_pipe<'a>(
+    handle: impl Into<BorrowedRes<'a>>,
+) -> IocpFuture<'a, ConnectNamedPipe> {
+    IocpFuture::new(handle, ConnectNamedPipe)
+}
+
+pub trait BufResultExt {
+    fn map_advanced(self) -> Self;
+}
+
+impl<T: WrapBufMut> BufResultExt for BufResult<usize, T> {
+    fn map_advanced(self) -> Self {
+        let (res, buffer) = self;
+        let (res, buffer) = (res.map(|res| (res, ())), buffer).map_advanced();
+        let res = res.map(|(res, _)| res);
+        (res, buffer)
+    }
+}
+
+impl<T: WrapBufMut, O> BufResultExt for BufResult<(usize, O), T> {
+    fn map_advanced(self) -> Self {
+        let (res, mut buffer) = self;
+        if let Ok((init, _)) = &res {
+            buffer.set_init(*init);
+        }
+        (res, buffer)
+    }
+}
+
+pub trait BufResultIntoInner {
+    type InnerResult;
+
+    fn into_inner(self) -> Self::InnerResult;
+}
+
+impl<T: IntoInner, O> BufResultIntoInner for BufResult<O, T> {
+    type InnerResult = BufResult<O, T::Inner>;
+
+    fn into_inner(self) -> Self::InnerResult {
+        let (res, buffer) = self;
+        (res, buffer.into_inner())
+    }
+}
+
+pub trait RecvResultExt<A> {
+    type RecvFromResult;
+
+    fn map_addr(self) -> Self::RecvFromResult;
+}
+
+impl<T, A: SockAddr> RecvResultExt<A> for BufResult<usize, RecvFrom<T>> {
+    type RecvFromResult = BufResult<(usize, A), RecvFrom<T>>;
+
+    fn map_addr(self) -> Self::RecvFromResult {
+        let (res, op) = self;
+        let res = res.and_then(|res| {
+            let (addr, addr_len) = op.addr();
+            let addr = unsafe { A::try_from_native(NonNull::new_unchecked(addr as _), addr_len) }
+                .ok_or_else(|| {
+                IoError::new(std::io::ErrorKind::InvalidData, "invalid address")
+            })?;
+            Ok((res, addr))
+        });
+        (res, op)
+    }
+}
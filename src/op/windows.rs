@@ -0,0 +1,238 @@
+use crate::{
+    buf::*,
+    io_port::{OpCode, RawRes},
+    net::{SockAddr, SockAddrLen, MAX_ADDR_SIZE},
+    op::*,
+    *,
+};
+use once_cell::sync::OnceCell as OnceLock;
+use std::{
+    os::windows::prelude::{AsRawSocket, BorrowedSocket},
+    ptr::{null, null_mut, NonNull},
+    task::Poll,
+};
+use windows_sys::{
+    core::GUID,
+    Win32::{
+        Foundation::{GetLastError, ERROR_IO_PENDING},
+        Networking::WinSock::{
+            WSAGetLastError, WSAIoctl, WSARecv, WSARecvFrom, WSASend, WSASendTo, LPFN_ACCEPTEX,
+            LPFN_CONNECTEX, LPFN_GETACCEPTEXSOCKADDRS, SIO_GET_EXTENSION_FUNCTION_POINTER,
+            SOCKADDR, WSAID_ACCEPTEX, WSAID_CONNECTEX, WSAID_GETACCEPTEXSOCKADDRS, WSA_IO_PENDING,
+        },
+        Storage::FileSystem::{ReadFile, WriteFile},
+        System::{Pipes::ConnectNamedPipe, IO::OVERLAPPED},
+    },
+};
+
+/// The transferred bytes of an operation completing synchronously.
+unsafe fn transferred(optr: *mut OVERLAPPED) -> Poll<IoResult<usize>> {
+    Poll::Ready(Ok((*optr).InternalHigh))
+}
+
+/// Maps the result of a Win32 function returning `BOOL`.
+unsafe fn win32_result(res: i32, optr: *mut OVERLAPPED) -> Poll<IoResult<usize>> {
+    if res == 0 {
+        let error = GetLastError();
+        match error {
+            ERROR_IO_PENDING => Poll::Pending,
+            _ => Poll::Ready(Err(IoError::from_raw_os_error(error as _))),
+        }
+    } else {
+        transferred(optr)
+    }
+}
+
+/// Maps the result of a WinSock function returning `0` on success.
+unsafe fn winsock_result(res: i32, optr: *mut OVERLAPPED) -> Poll<IoResult<usize>> {
+    if res == 0 {
+        transferred(optr)
+    } else {
+        let error = WSAGetLastError();
+        match error {
+            WSA_IO_PENDING => Poll::Pending,
+            _ => Poll::Ready(Err(IoError::from_raw_os_error(error))),
+        }
+    }
+}
+
+unsafe fn get_wsa_fn<F>(handle: usize, fguid: GUID) -> IoResult<Option<F>> {
+    let mut fptr = None;
+    let mut returned = 0;
+    let res = WSAIoctl(
+        handle,
+        SIO_GET_EXTENSION_FUNCTION_POINTER,
+        std::ptr::addr_of!(fguid).cast(),
+        std::mem::size_of_val(&fguid) as _,
+        std::ptr::addr_of_mut!(fptr).cast(),
+        std::mem::size_of::<F>() as _,
+        &mut returned,
+        null_mut(),
+        None,
+    );
+    if res == 0 {
+        Ok(fptr)
+    } else {
+        Err(IoError::last_os_error())
+    }
+}
+
+unsafe fn set_offset(optr: *mut OVERLAPPED, pos: usize) {
+    if let Some(overlapped) = optr.as_mut() {
+        overlapped.Anonymous.Anonymous.Offset = (pos & 0xFFFFFFFF) as _;
+        overlapped.Anonymous.Anonymous.OffsetHigh = (pos >> 32) as _;
+    }
+}
+
+impl<T: WithBufMut> OpCode for ReadAt<T> {
+    unsafe fn operate(&mut self, handle: RawRes, optr: *mut OVERLAPPED) -> Poll<IoResult<usize>> {
+        set_offset(optr, self.pos);
+        let res = self.buffer.with_buf_mut(|ptr, len| {
+            let mut read = 0;
+            ReadFile(handle as _, ptr as _, len as _, &mut read, optr)
+        });
+        win32_result(res, optr)
+    }
+}
+
+impl<T: WithBuf> OpCode for WriteAt<T> {
+    unsafe fn operate(&mut self, handle: RawRes, optr: *mut OVERLAPPED) -> Poll<IoResult<usize>> {
+        set_offset(optr, self.pos);
+        let res = self.buffer.with_buf(|ptr, len| {
+            let mut written = 0;
+            WriteFile(handle as _, ptr as _, len as _, &mut written, optr)
+        });
+        win32_result(res, optr)
+    }
+}
+
+static ACCEPT_EX: OnceLock<LPFN_ACCEPTEX> = OnceLock::new();
+
+impl OpCode for Accept {
+    unsafe fn operate(&mut self, handle: RawRes, optr: *mut OVERLAPPED) -> Poll<IoResult<usize>> {
+        let accept_fn = ACCEPT_EX.get_or_try_init(|| get_wsa_fn(handle, WSAID_ACCEPTEX))?;
+        let mut received = 0;
+        let res = accept_fn.unwrap()(
+            handle,
+            self.accept_handle,
+            self.buffer.as_mut_ptr() as _,
+            0,
+            MAX_ADDR_SIZE as _,
+            MAX_ADDR_SIZE as _,
+            &mut received,
+            optr,
+        );
+        win32_result(res, optr)
+    }
+}
+
+static GET_ADDRS: OnceLock<LPFN_GETACCEPTEXSOCKADDRS> = OnceLock::new();
+
+pub fn accept_result<A: SockAddr>(handle: BorrowedSocket, accept: &Accept) -> IoResult<A> {
+    let get_addrs_fn = GET_ADDRS.get_or_try_init(|| unsafe {
+        get_wsa_fn(handle.as_raw_socket() as _, WSAID_GETACCEPTEXSOCKADDRS)
+    })?;
+    let mut local_addr: *mut SOCKADDR = null_mut();
+    let mut local_addr_len = 0;
+    let mut remote_addr: *mut SOCKADDR = null_mut();
+    let mut remote_addr_len = 0;
+    unsafe {
+        (get_addrs_fn.unwrap())(
+            accept.buffer.as_ptr() as _,
+            0,
+            MAX_ADDR_SIZE as _,
+            MAX_ADDR_SIZE as _,
+            &mut local_addr,
+            &mut local_addr_len,
+            &mut remote_addr,
+            &mut remote_addr_len,
+        );
+        A::try_from_native(NonNull::new(remote_addr).unwrap(), remote_addr_len)
+    }
+    .ok_or_else(|| IoError::new(std::io::ErrorKind::InvalidData, "invalid address"))
+}
+
+static CONNECT_EX: OnceLock<LPFN_CONNECTEX> = OnceLock::new();
+
+impl<A: SockAddr> OpCode for Connect<A> {
+    unsafe fn operate(&mut self, handle: RawRes, optr: *mut OVERLAPPED) -> Poll<IoResult<usize>> {
+        let connect_fn = CONNECT_EX.get_or_try_init(|| get_wsa_fn(handle, WSAID_CONNECTEX))?;
+        let mut sent = 0;
+        let res = self.addr.with_native(|addr, len| {
+            connect_fn.unwrap()(handle, addr, len, null(), 0, &mut sent, optr)
+        });
+        win32_result(res, optr)
+    }
+}
+
+impl<T: WithIoVecMut> OpCode for Recv<T> {
+    unsafe fn operate(&mut self, handle: RawRes, optr: *mut OVERLAPPED) -> Poll<IoResult<usize>> {
+        let res = self.buffer.with_io_vec_mut(|ptr, len| {
+            let mut flags = 0;
+            let mut received = 0;
+            WSARecv(handle, ptr, len as _, &mut received, &mut flags, optr, None)
+        });
+        winsock_result(res, optr)
+    }
+}
+
+impl<T: WithIoVec> OpCode for Send<T> {
+    unsafe fn operate(&mut self, handle: RawRes, optr: *mut OVERLAPPED) -> Poll<IoResult<usize>> {
+        let res = self.buffer.with_io_vec(|ptr, len| {
+            let mut sent = 0;
+            WSASend(handle, ptr, len as _, &mut sent, 0, optr, None)
+        });
+        winsock_result(res, optr)
+    }
+}
+
+impl<T> RecvFrom<T> {
+    pub(crate) fn addr(&self) -> (*const u8, SockAddrLen) {
+        (self.addr.as_ptr(), self.addr_len)
+    }
+}
+
+impl<T: WithIoVecMut> OpCode for RecvFrom<T> {
+    unsafe fn operate(&mut self, handle: RawRes, optr: *mut OVERLAPPED) -> Poll<IoResult<usize>> {
+        let addr = self.addr.as_mut_ptr();
+        let addr_len = &mut self.addr_len;
+        let res = self.buffer.with_io_vec_mut(|ptr, len| {
+            let mut flags = 0;
+            let mut received = 0;
+            WSARecvFrom(
+                handle,
+                ptr,
+                len as _,
+                &mut received,
+                &mut flags,
+                addr as _,
+                addr_len,
+                optr,
+                None,
+            )
+        });
+        winsock_result(res, optr)
+    }
+}
+
+impl<T: WithIoVec, A: SockAddr> OpCode for SendTo<T, A> {
+    unsafe fn operate(&mut self, handle: RawRes, optr: *mut OVERLAPPED) -> Poll<IoResult<usize>> {
+        let addr = &self.addr;
+        let res = self.buffer.with_io_vec(|ptr, len| {
+            let mut sent = 0;
+            addr.with_native(|addr, addr_len| {
+                WSASendTo(
+                    handle, ptr, len as _, &mut sent, 0, addr, addr_len, optr, None,
+                )
+            })
+        });
+        winsock_result(res, optr)
+    }
+}
+
+impl OpCode for ConnectNamedPipe {
+    unsafe fn operate(&mut self, handle: RawRes, optr: *mut OVERLAPPED) -> Poll<IoResult<usize>> {
+        let res = ConnectNamedPipe(handle as _, optr);
+        win32_result(res, optr)
+    }
+}
@@ -0,0 +1,574 @@
+use crate::{
+    io_port::{DEFAULT_BATCH_SIZE, DEFAULT_COMPLETION_BUDGET, IO_PORT},
+    runtime::{fault::FaultPolicy, metrics::IoMetrics, worker::Worker, Handle, Runtime},
+    *,
+};
+use std::{
+    any::Any,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::task::LocalSet;
+
+type Callback = Arc<dyn Fn() + Send + Sync>;
+
+const DEFAULT_MAX_BLOCKING_THREADS: usize = 512;
+
+/// Receives the buffers of the operations whose futures are dropped.
+#[derive(Clone)]
+pub(crate) struct Graveyard(Arc<dyn Fn(Box<dyn Any>) + Send + Sync>);
+
+impl Graveyard {
+    pub fn bury(&self, buffer: Box<dyn Any>) {
+        (self.0)(buffer)
+    }
+}
+
+impl std::fmt::Debug for Graveyard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Graveyard").finish_non_exhaustive()
+    }
+}
+
+/// How the runtime waits for completions when there is no task to run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ParkPolicy {
+    /// Polls the driver without blocking, and lets Tokio park the thread.
+    ///
+    /// Tokio parks the thread until a completion is ready, a timer fires,
+    /// or a task is woken by another thread.
+    ///
+    /// On Windows, Tokio could not wait for the completions, so the thread
+    /// blocks on the driver instead, until a completion is ready, a timer of
+    /// [`crate::time`] fires, or a task is woken by another thread. If the
+    /// Tokio IO or time driver is enabled, it blocks up to 1 millisecond, so
+    /// that they are polled in time.
+    #[default]
+    Spin,
+    /// Blocks up to the timeout waiting for the first completion before Tokio
+    /// parks the thread.
+    ///
+    /// The Tokio IO and time drivers are not polled while blocking. The timers
+    /// of [`crate::time`] still fire in time. The tasks woken by other threads
+    /// are delayed until the timeout on Linux with the Tokio IO driver, and
+    /// wake the driver otherwise.
+    Timeout(Duration),
+}
+
+/// The max time to block on the driver with [`ParkPolicy::Spin`] if Tokio
+/// could not wait for the driver, so that the Tokio drivers are polled in time.
+const TOKIO_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// The system driver on Linux.
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DriverKind {
+    /// The io_uring driver.
+    IoUring,
+    /// The epoll driver, which emulates the completion model with non-blocking
+    /// syscalls.
+    Epoll,
+}
+
+/// Builds a `tokio-iocp` runtime with custom configuration values.
+///
+/// # Examples
+///
+/// ```
+/// use tokio_iocp::runtime::Builder;
+///
+/// let runtime = Builder::new().worker_threads(2).build().unwrap();
+/// let handle = runtime.handle();
+/// let res = runtime.block_on(async move {
+///     handle.spawn_on(1, || async { 1 + 1 }).await.unwrap()
+/// });
+/// assert_eq!(res, 2);
+/// ```
+#[derive(Clone)]
+pub struct Builder {
+    worker_threads: usize,
+    worker_affinity: Vec<usize>,
+    event_batch_size: usize,
+    completion_budget: usize,
+    max_in_flight: usize,
+    park_policy: ParkPolicy,
+    #[cfg(windows)]
+    skip_on_success: bool,
+    #[cfg(target_os = "linux")]
+    driver: Option<DriverKind>,
+    sim: Option<u64>,
+    fault_policy: Option<FaultPolicy>,
+    graveyard: Option<Graveyard>,
+    max_blocking_threads: usize,
+    thread_name: String,
+    on_thread_start: Option<Callback>,
+    on_thread_stop: Option<Callback>,
+    enable_io: bool,
+    enable_time: bool,
+}
+
+impl Builder {
+    /// Creates a new builder.
+    ///
+    /// By default, the runtime has no worker threads, and all tasks are
+    /// executed on the thread calling [`Runtime::block_on`]. The Tokio IO and
+    /// time drivers are enabled.
+    #[allow(clippy::new_without_default)]
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            worker_threads: 0,
+            worker_affinity: vec![],
+            event_batch_size: DEFAULT_BATCH_SIZE,
+            completion_budget: DEFAULT_COMPLETION_BUDGET,
+            max_in_flight: usize::MAX,
+            park_policy: ParkPolicy::default(),
+            #[cfg(windows)]
+            skip_on_success: true,
+            #[cfg(target_os = "linux")]
+            driver: None,
+            sim: None,
+            fault_policy: None,
+            graveyard: None,
+            max_blocking_threads: DEFAULT_MAX_BLOCKING_THREADS,
+            thread_name: "tokio-iocp-worker".to_string(),
+            on_thread_start: None,
+            on_thread_stop: None,
+            enable_io: true,
+            enable_time: true,
+        }
+    }
+
+    /// Sets the number of worker threads.
+    ///
+    /// Each worker runs a Tokio `current_thread` runtime with its own driver.
+    /// Tasks could be spawned on the workers by [`Handle::spawn_on`] and
+    /// [`Handle::spawn_pinned`].
+    pub fn worker_threads(&mut self, n: usize) -> &mut Self {
+        self.worker_threads = n;
+        self
+    }
+
+    /// Sets the CPU cores the workers are pinned to.
+    ///
+    /// The worker `i` is pinned to the `i`-th core of `cores`. Workers without
+    /// a corresponding core are not pinned.
+    pub fn worker_affinity(&mut self, cores: impl IntoIterator<Item = usize>) -> &mut Self {
+        self.worker_affinity = cores.into_iter().collect();
+        self
+    }
+
+    /// Sets the max count of completions handled each time the driver is polled.
+    ///
+    /// The default value is 64.
+    pub fn event_batch_size(&mut self, size: usize) -> &mut Self {
+        self.event_batch_size = size;
+        self
+    }
+
+    /// Sets the max count of completions handled before the driver lets other
+    /// tasks run, so that a resource with a constant stream of completions
+    /// could not monopolize the thread.
+    ///
+    /// It is also the max count of operations completing inline in a row, e.g.,
+    /// a non-blocking send succeeding on the epoll driver, before the task
+    /// polling them yields.
+    ///
+    /// The default value is 128.
+    pub fn completion_budget(&mut self, budget: usize) -> &mut Self {
+        self.completion_budget = budget;
+        self
+    }
+
+    /// Sets the max count of the operations in flight on each thread of the
+    /// runtime, which pin their buffers and kernel resources.
+    ///
+    /// Past the limit, the new operations wait in a FIFO queue, and are
+    /// submitted when the operations in flight complete. The operations whose
+    /// futures are dropped count until the driver completes them. See
+    /// [`RuntimeMetrics::queued`] for the queue depth.
+    ///
+    /// There is no limit by default.
+    ///
+    /// [`RuntimeMetrics::queued`]: crate::runtime::metrics::RuntimeMetrics::queued
+    pub fn max_in_flight(&mut self, max: usize) -> &mut Self {
+        self.max_in_flight = max;
+        self
+    }
+
+    /// Sets how the runtime waits for completions when there is no task to run.
+    ///
+    /// The default value is [`ParkPolicy::Spin`].
+    pub fn park_policy(&mut self, policy: ParkPolicy) -> &mut Self {
+        self.park_policy = policy;
+        self
+    }
+
+    /// Sets whether the handles attached on the runtime threads skip the
+    /// completion port when their operations complete synchronously.
+    ///
+    /// It is enabled by default, and only disabled to measure its effect.
+    #[doc(hidden)]
+    #[cfg(windows)]
+    pub fn skip_on_success(&mut self, skip: bool) -> &mut Self {
+        self.skip_on_success = skip;
+        self
+    }
+
+    /// Sets the system driver of the runtime threads.
+    ///
+    /// By default, io_uring is preferred, and the driver falls back to epoll
+    /// if io_uring is not available, or the environment variable
+    /// `TOKIO_IOCP_DRIVER` is set to `epoll`. The driver set here takes
+    /// precedence over the environment variable. See the
+    /// [crate docs](crate#environment-variables).
+    ///
+    /// The driver of a thread is created when it is first used. It is replaced
+    /// when a runtime with a different driver is built on the thread, and
+    /// [`Builder::build`] fails if any handle is attached to it.
+    #[cfg(target_os = "linux")]
+    pub fn driver(&mut self, driver: DriverKind) -> &mut Self {
+        self.driver = Some(driver);
+        self
+    }
+
+    /// Uses the simulated driver with the seed, instead of the system driver.
+    ///
+    /// The operations are queued, and completed by the test through the
+    /// functions in [`sim`]. The worker `i` uses the seed `seed + i`. The
+    /// notifications of [`Notifier`] are still received by the system driver.
+    ///
+    /// [`sim`]: crate::runtime::sim
+    /// [`Notifier`]: crate::runtime::Notifier
+    pub fn sim(&mut self, seed: u64) -> &mut Self {
+        self.sim = Some(seed);
+        self
+    }
+
+    /// Sets the fault policy of the runtime, which applies to all operations
+    /// on the runtime threads.
+    ///
+    /// See [`fault`] for more details.
+    ///
+    /// [`fault`]: crate::runtime::fault
+    pub fn fault_policy(&mut self, policy: FaultPolicy) -> &mut Self {
+        self.fault_policy = Some(policy);
+        self
+    }
+
+    /// Passes the buffers of the cancelled operations to `f`.
+    ///
+    /// When the future of an operation is dropped before it completes, the
+    /// operation is cancelled, but the buffer is held by the system until the
+    /// operation completes. Then the buffer is passed to `f` on the thread
+    /// submitting the operation, e.g., to return it to a buffer pool. It could
+    /// be downcast to the type passed to the operation, or `Vec<T>` for the
+    /// vectored operations. The operations without buffers are skipped.
+    ///
+    /// By default, the buffers are dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::{
+    ///     sync::{Arc, Mutex},
+    ///     time::Duration,
+    /// };
+    /// use tokio_iocp::{net::UdpSocket, runtime::Runtime};
+    ///
+    /// let pool = Arc::new(Mutex::new(Vec::<Vec<u8>>::new()));
+    /// let runtime = Runtime::builder()
+    ///     .graveyard({
+    ///         let pool = pool.clone();
+    ///         move |buffer| {
+    ///             if let Ok(buffer) = buffer.downcast::<Vec<u8>>() {
+    ///                 pool.lock().unwrap().push(*buffer);
+    ///             }
+    ///         }
+    ///     })
+    ///     .build()
+    ///     .unwrap();
+    /// runtime.block_on(async {
+    ///     let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    ///     let recv = socket.recv(Vec::with_capacity(64));
+    ///     tokio::time::timeout(Duration::from_millis(10), recv)
+    ///         .await
+    ///         .unwrap_err();
+    ///     runtime.drain_cancelled().await;
+    /// });
+    /// assert_eq!(pool.lock().unwrap()[0].capacity(), 64);
+    /// ```
+    pub fn graveyard(&mut self, f: impl Fn(Box<dyn Any>) + Send + Sync + 'static) -> &mut Self {
+        self.graveyard = Some(Graveyard(Arc::new(f)));
+        self
+    }
+
+    /// Sets the max count of threads of the blocking pool.
+    ///
+    /// The syscalls which could not be submitted to the driver, e.g., opening
+    /// a file by [`OpenOptions::open_async`], run on the blocking pool instead
+    /// of the runtime thread. The pool is shared by all threads of the runtime,
+    /// including the workers, and the calls are queued when all threads of the
+    /// pool are busy. It doesn't limit the Tokio blocking pool of each thread,
+    /// e.g., used by [`tokio::task::spawn_blocking`].
+    ///
+    /// The default value is 512.
+    ///
+    /// [`OpenOptions::open_async`]: crate::fs::OpenOptions::open_async
+    pub fn max_blocking_threads(&mut self, n: usize) -> &mut Self {
+        self.max_blocking_threads = n;
+        self
+    }
+
+    /// Sets the name of threads spawned by the runtime.
+    ///
+    /// The worker threads are named `{name}-{index}`. The name is also used
+    /// for the threads of the blocking pool and the Tokio blocking threads.
+    pub fn thread_name(&mut self, name: impl Into<String>) -> &mut Self {
+        self.thread_name = name.into();
+        self
+    }
+
+    /// Executes the function after starting a thread spawned by the runtime,
+    /// including the worker threads, the threads of the blocking pool and the
+    /// Tokio blocking threads.
+    pub fn on_thread_start(&mut self, f: impl Fn() + Send + Sync + 'static) -> &mut Self {
+        self.on_thread_start = Some(Arc::new(f));
+        self
+    }
+
+    /// Executes the function before stopping a thread spawned by the runtime,
+    /// including the worker threads, the threads of the blocking pool and the
+    /// Tokio blocking threads.
+    pub fn on_thread_stop(&mut self, f: impl Fn() + Send + Sync + 'static) -> &mut Self {
+        self.on_thread_stop = Some(Arc::new(f));
+        self
+    }
+
+    /// Enables or disables the Tokio IO driver.
+    ///
+    /// It is required by the Tokio networking types, e.g., [`tokio::net::TcpStream`].
+    ///
+    /// On Linux, the driver of this crate waits for completions with the Tokio
+    /// IO driver. If it is disabled, the driver is only polled when the thread
+    /// parks, and [`ParkPolicy::Timeout`] is required to wait for completions.
+    /// [`Builder::build`] fails with [`std::io::ErrorKind::InvalidInput`] if
+    /// [`ParkPolicy::Spin`] is used instead.
+    pub fn enable_io(&mut self, enable: bool) -> &mut Self {
+        self.enable_io = enable;
+        self
+    }
+
+    /// Enables or disables the Tokio time driver.
+    ///
+    /// It is required by [`tokio::time`], but not by [`crate::time`], whose
+    /// timers are driven by the driver of this crate.
+    pub fn enable_time(&mut self, enable: bool) -> &mut Self {
+        self.enable_time = enable;
+        self
+    }
+
+    /// Creates the configured [`Runtime`].
+    ///
+    /// The worker threads are started before this method returns.
+    pub fn build(&mut self) -> IoResult<Runtime> {
+        if self.event_batch_size == 0 {
+            return Err(IoError::new(
+                std::io::ErrorKind::InvalidInput,
+                "the event batch size should be positive",
+            ));
+        }
+        if self.completion_budget == 0 {
+            return Err(IoError::new(
+                std::io::ErrorKind::InvalidInput,
+                "the completion budget should be positive",
+            ));
+        }
+        if self.max_in_flight == 0 {
+            return Err(IoError::new(
+                std::io::ErrorKind::InvalidInput,
+                "the max count of operations in flight should be positive",
+            ));
+        }
+        if self.max_blocking_threads == 0 {
+            return Err(IoError::new(
+                std::io::ErrorKind::InvalidInput,
+                "the max count of blocking threads should be positive",
+            ));
+        }
+        #[cfg(target_os = "linux")]
+        if !self.drive() && self.park_policy == ParkPolicy::Spin {
+            return Err(IoError::new(
+                std::io::ErrorKind::InvalidInput,
+                "the spin park policy requires the Tokio IO driver",
+            ));
+        }
+        // The runtime is not `Send`, so `Runtime::block_on` runs on this thread.
+        #[cfg(target_os = "linux")]
+        IO_PORT.with(|port| port.set_driver(self.driver))?;
+        let blocking_pool = self.blocking_pool()?;
+        let workers = (0..self.worker_threads)
+            .map(|index| Worker::start(index, self, blocking_pool.handle().clone()))
+            .collect::<IoResult<Vec<_>>>()?;
+        Ok(Runtime {
+            rt: self.tokio_runtime()?,
+            local: Some(LocalSet::new()),
+            event_batch_size: self.event_batch_size,
+            completion_budget: self.completion_budget,
+            max_in_flight: self.max_in_flight,
+            #[cfg(windows)]
+            skip_on_success: self.skip_on_success,
+            #[cfg(target_os = "linux")]
+            driver: self.driver,
+            drive: self.drive(),
+            sim: self.sim.map(|seed| (next_runtime_id(), seed)),
+            fault_policy: self.fault_policy.clone(),
+            graveyard: self.graveyard.clone(),
+            metrics: Arc::new(IoMetrics::new()),
+            handle: Handle::new(workers.iter().map(|w| w.handle().clone()).collect()),
+            workers,
+            blocking_pool,
+        })
+    }
+
+    /// Creates the blocking pool shared by all threads of the runtime. It is
+    /// a Tokio runtime which is never run, and only its blocking pool is used.
+    fn blocking_pool(&self) -> IoResult<tokio::runtime::Runtime> {
+        let mut builder = tokio::runtime::Builder::new_current_thread();
+        builder.max_blocking_threads(self.max_blocking_threads);
+        self.thread_hooks(&mut builder);
+        builder.build()
+    }
+
+    pub(super) fn tokio_runtime(&self) -> IoResult<tokio::runtime::Runtime> {
+        let mut builder = tokio::runtime::Builder::new_current_thread();
+        let timeout = self.park_timeout();
+        builder.on_thread_park(move || {
+            IO_PORT.with(|port| port.park(timeout));
+        });
+        self.thread_hooks(&mut builder);
+        if self.enable_io {
+            builder.enable_io();
+        }
+        if self.enable_time {
+            builder.enable_time();
+        }
+        builder.build()
+    }
+
+    /// Sets the name and the hooks of the threads spawned by the Tokio runtime.
+    fn thread_hooks(&self, builder: &mut tokio::runtime::Builder) {
+        builder.thread_name(&self.thread_name);
+        if let Some(f) = &self.on_thread_start {
+            let f = f.clone();
+            builder.on_thread_start(move || f());
+        }
+        if let Some(f) = &self.on_thread_stop {
+            let f = f.clone();
+            builder.on_thread_stop(move || f());
+        }
+    }
+
+    /// Whether the driver is woken by the Tokio runtime. The port of IOCP could
+    /// not be waited by Tokio.
+    pub(super) fn drive(&self) -> bool {
+        cfg!(target_os = "linux") && self.enable_io
+    }
+
+    /// The max time to block on the driver when the thread parks.
+    fn park_timeout(&self) -> Duration {
+        match self.park_policy {
+            ParkPolicy::Timeout(timeout) => timeout,
+            ParkPolicy::Spin if self.drive() => Duration::ZERO,
+            // The thread blocks on the driver instead of Tokio.
+            ParkPolicy::Spin if self.enable_io || self.enable_time => TOKIO_POLL_INTERVAL,
+            ParkPolicy::Spin => Duration::MAX,
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    pub(super) fn driver_kind(&self) -> Option<DriverKind> {
+        self.driver
+    }
+
+    pub(super) fn faults(&self) -> Option<FaultPolicy> {
+        self.fault_policy.clone()
+    }
+
+    pub(super) fn graveyard_hook(&self) -> Option<Graveyard> {
+        self.graveyard.clone()
+    }
+
+    /// The runtime id and the seed of the simulated driver of the worker.
+    pub(super) fn worker_sim(&self, index: usize) -> Option<(u64, u64)> {
+        self.sim
+            .map(|seed| (next_runtime_id(), seed.wrapping_add(index as u64)))
+    }
+
+    pub(super) fn batch_size(&self) -> usize {
+        self.event_batch_size
+    }
+
+    pub(super) fn budget(&self) -> usize {
+        self.completion_budget
+    }
+
+    pub(super) fn in_flight_limit(&self) -> usize {
+        self.max_in_flight
+    }
+
+    #[cfg(windows)]
+    pub(super) fn skip_completion_port(&self) -> bool {
+        self.skip_on_success
+    }
+
+    pub(super) fn worker_name(&self, index: usize) -> String {
+        format!("{}-{}", self.thread_name, index)
+    }
+
+    pub(super) fn worker_core(&self, index: usize) -> Option<usize> {
+        self.worker_affinity.get(index).copied()
+    }
+
+    pub(super) fn thread_start_hook(&self) -> Option<Callback> {
+        self.on_thread_start.clone()
+    }
+
+    pub(super) fn thread_stop_hook(&self) -> Option<Callback> {
+        self.on_thread_stop.clone()
+    }
+}
+
+/// Generates an id to distinguish the simulated drivers of different runtimes
+/// on the same thread.
+fn next_runtime_id() -> u64 {
+    static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+impl std::fmt::Debug for Builder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut f = f.debug_struct("Builder");
+        f.field("worker_threads", &self.worker_threads)
+            .field("worker_affinity", &self.worker_affinity)
+            .field("event_batch_size", &self.event_batch_size)
+            .field("completion_budget", &self.completion_budget)
+            .field("max_in_flight", &self.max_in_flight)
+            .field("park_policy", &self.park_policy);
+        #[cfg(windows)]
+        f.field("skip_on_success", &self.skip_on_success);
+        #[cfg(target_os = "linux")]
+        f.field("driver", &self.driver);
+        f.field("sim", &self.sim)
+            .field("fault_policy", &self.fault_policy)
+            .field("graveyard", &self.graveyard)
+            .field("max_blocking_threads", &self.max_blocking_threads)
+            .field("thread_name", &self.thread_name)
+            .field("enable_io", &self.enable_io)
+            .field("enable_time", &self.enable_time)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,131 @@
+use crate::{
+    io_port::{RawRes, IO_PORT},
+    *,
+};
+
+mod private {
+    use super::*;
+
+    /// A resource attached to the driver of a thread.
+    pub trait Resource {
+        fn as_raw_res(&self) -> RawRes;
+    }
+}
+
+pub(crate) use private::Resource;
+
+/// A resource detached from the driver of a runtime thread.
+///
+/// A resource is attached to the driver of the thread creating it, and its
+/// operations fail on other threads. To use it on another runtime thread,
+/// detach it by `into_detached`, e.g., [`File::into_detached`], send it to
+/// that thread, and attach it to the driver there by [`Detached::attach`].
+///
+/// A resource with operations in flight, including the cancelled ones not
+/// completed yet, could not be detached. See [`Runtime::drain_cancelled`].
+///
+/// # Examples
+///
+/// ```
+/// use tokio_iocp::{fs::File, runtime::Runtime};
+///
+/// let runtime = Runtime::builder().worker_threads(1).build().unwrap();
+/// let handle = runtime.handle();
+/// runtime.block_on(async {
+///     let file = File::open("Cargo.toml").unwrap().into_detached().unwrap();
+///     let n = handle
+///         .spawn_on(0, move || async move {
+///             let file = file.attach().unwrap();
+///             let (res, _) = file.read_at(Vec::with_capacity(64), 0).await;
+///             res.unwrap()
+///         })
+///         .await
+///         .unwrap();
+///     assert_eq!(n, 64);
+/// });
+/// ```
+///
+/// [`File::into_detached`]: crate::fs::File::into_detached
+/// [`Runtime::drain_cancelled`]: crate::runtime::Runtime::drain_cancelled
+pub struct Detached<T> {
+    inner: T,
+}
+
+impl<T: Resource> Detached<T> {
+    pub(crate) fn detach(inner: T) -> IoResult<Self> {
+        IO_PORT.with(|port| port.detach(inner.as_raw_res()))?;
+        Ok(Self { inner })
+    }
+
+    /// Attaches the resource to the driver of the current thread.
+    ///
+    /// On error, the resource is returned still detached in [`AttachError`].
+    pub fn attach(self) -> Result<T, AttachError<T>> {
+        match IO_PORT.with(|port| port.attach(self.inner.as_raw_res())) {
+            Ok(()) => Ok(self.inner),
+            Err(error) => Err(AttachError {
+                error,
+                detached: self,
+            }),
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for Detached<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Detached").finish_non_exhaustive()
+    }
+}
+
+/// The error returned by [`Detached::attach`], holding the resource which
+/// could not be attached, so that it is not closed.
+///
+/// It could be converted into [`std::io::Error`] with `?`, which drops the
+/// resource.
+pub struct AttachError<T> {
+    error: IoError,
+    detached: Detached<T>,
+}
+
+impl<T> AttachError<T> {
+    /// The error of attaching the resource.
+    pub fn error(&self) -> &IoError {
+        &self.error
+    }
+
+    /// Returns the resource still detached.
+    pub fn into_inner(self) -> Detached<T> {
+        self.detached
+    }
+
+    /// Returns the error and the resource still detached.
+    pub fn into_parts(self) -> (IoError, Detached<T>) {
+        (self.error, self.detached)
+    }
+}
+
+impl<T> std::fmt::Debug for AttachError<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AttachError")
+            .field("error", &self.error)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> std::fmt::Display for AttachError<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl<T> std::error::Error for AttachError<T> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.error.source()
+    }
+}
+
+impl<T> From<AttachError<T>> for IoError {
+    fn from(e: AttachError<T>) -> Self {
+        e.error
+    }
+}
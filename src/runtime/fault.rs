@@ -0,0 +1,175 @@
+//! Fault injection for testing.
+//!
+//! A [`FaultPolicy`] injects errors, short transfers and delays into the
+//! operations. It could be set for a runtime by [`Builder::fault_policy`], or
+//! for a resource by [`FaultTarget::set_fault_policy`], which overrides the
+//! policy of the runtime.
+//!
+//! An injected error is returned with the owned buffer, like a real failure.
+//! A delay is implemented by a timer of the driver, see [`crate::time`].
+//!
+//! ```
+//! use std::{io::ErrorKind, time::Duration};
+//! use tokio_iocp::{
+//!     fs::File,
+//!     runtime::{
+//!         fault::{Fault, FaultPolicy, OpKind},
+//!         Runtime,
+//!     },
+//! };
+//!
+//! let policy = FaultPolicy::new()
+//!     .with(Fault::error(ErrorKind::Other).on(OpKind::Write).every(10))
+//!     .with(Fault::cap(7))
+//!     .with(Fault::delay(Duration::from_millis(5)).on(OpKind::Recv));
+//! let runtime = Runtime::builder().fault_policy(policy).build().unwrap();
+//! runtime.block_on(async {
+//!     let file = File::open("Cargo.toml").unwrap();
+//!     let (res, buf) = file.read_at(Vec::with_capacity(64), 0).await;
+//!     assert_eq!(res.unwrap(), 7);
+//!     assert_eq!(buf.len(), 7);
+//! });
+//! ```
+//!
+//! [`Builder::fault_policy`]: crate::runtime::Builder::fault_policy
+
+use crate::io_port::{RawRes, IO_PORT};
+use std::{
+    io::ErrorKind,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+pub use crate::io_port::OpKind;
+
+#[derive(Debug, Clone, Copy)]
+enum Action {
+    Error(ErrorKind),
+    Cap(usize),
+    Delay(Duration),
+}
+
+/// A fault injected into the operations.
+///
+/// By default, the fault is injected into every operation.
+#[derive(Debug, Clone)]
+pub struct Fault {
+    action: Action,
+    kinds: Vec<OpKind>,
+    every: usize,
+    count: Arc<AtomicUsize>,
+}
+
+impl Fault {
+    fn new(action: Action) -> Self {
+        Self {
+            action,
+            kinds: vec![],
+            every: 1,
+            count: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Fails the operation with an error of `kind`, without submitting it.
+    pub fn error(kind: ErrorKind) -> Self {
+        Self::new(Action::Error(kind))
+    }
+
+    /// Limits the count of bytes transferred by the operation to `len`.
+    pub fn cap(len: usize) -> Self {
+        Self::new(Action::Cap(len))
+    }
+
+    /// Delays the completion of the operation by `delay`.
+    pub fn delay(delay: Duration) -> Self {
+        Self::new(Action::Delay(delay))
+    }
+
+    /// Injects the fault only into the operations of `kind`.
+    ///
+    /// It could be called multiple times to match more kinds.
+    pub fn on(mut self, kind: OpKind) -> Self {
+        self.kinds.push(kind);
+        self
+    }
+
+    /// Injects the fault only into every `n`-th matched operation.
+    ///
+    /// The count is shared by the clones of the fault, e.g., among the worker
+    /// threads of a runtime.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is zero.
+    pub fn every(mut self, n: usize) -> Self {
+        assert!(n > 0, "the period should be positive");
+        self.every = n;
+        self
+    }
+
+    fn matches(&self, kind: OpKind) -> bool {
+        (self.kinds.is_empty() || self.kinds.contains(&kind))
+            && (self.count.fetch_add(1, Ordering::Relaxed) + 1).is_multiple_of(self.every)
+    }
+}
+
+/// A set of faults injected into the operations.
+#[derive(Debug, Clone, Default)]
+pub struct FaultPolicy {
+    faults: Vec<Fault>,
+}
+
+impl FaultPolicy {
+    /// Creates an empty policy, which injects nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a fault to the policy.
+    pub fn with(mut self, fault: Fault) -> Self {
+        self.faults.push(fault);
+        self
+    }
+
+    /// Decides the faults injected into an operation of `kind`.
+    pub(crate) fn inject(&self, kind: OpKind) -> Injection {
+        let mut injection = Injection::default();
+        for fault in self.faults.iter().filter(|fault| fault.matches(kind)) {
+            match fault.action {
+                Action::Error(kind) => {
+                    injection.error.get_or_insert(kind);
+                }
+                Action::Cap(len) => {
+                    injection.cap = Some(injection.cap.map_or(len, |cap| cap.min(len)))
+                }
+                Action::Delay(delay) => injection.delay += delay,
+            }
+        }
+        injection
+    }
+}
+
+/// The faults injected into an operation.
+#[derive(Debug, Default)]
+pub(crate) struct Injection {
+    pub error: Option<ErrorKind>,
+    pub cap: Option<usize>,
+    pub delay: Duration,
+}
+
+/// The resources whose operations could be injected with faults.
+pub trait FaultTarget {
+    /// Sets the fault policy of the resource, which overrides the policy of
+    /// the runtime. Passing `None` removes it.
+    ///
+    /// The policy applies to the operations on the current thread.
+    fn set_fault_policy(&self, policy: Option<FaultPolicy>);
+}
+
+/// Implements [`FaultTarget`] with the raw handle of the resource.
+pub(crate) fn set_handle_fault_policy(handle: RawRes, policy: Option<FaultPolicy>) {
+    IO_PORT.with(|port| port.set_handle_fault_policy(handle, policy));
+}
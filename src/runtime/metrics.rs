@@ -0,0 +1,391 @@
+//! The metrics of the runtime.
+//!
+//! The counters are maintained by each thread of the runtime with relaxed
+//! atomic operations, and summed up by [`Runtime::metrics`].
+//!
+//! ```
+//! use tokio_iocp::{
+//!     fs::File,
+//!     runtime::{metrics::OpKind, Runtime},
+//! };
+//!
+//! let runtime = Runtime::new().unwrap();
+//! runtime.block_on(async {
+//!     let file = File::open("Cargo.toml").unwrap();
+//!     let (res, _) = file.read_at(Vec::with_capacity(64), 0).await;
+//!     res.unwrap();
+//! });
+//! let metrics = runtime.metrics();
+//! let read = metrics.op(OpKind::Read);
+//! assert_eq!(read.submitted(), 1);
+//! assert_eq!(read.completed(), 1);
+//! assert_eq!(read.bytes(), 64);
+//! assert_eq!(read.latency().count(), 1);
+//! assert_eq!(metrics.in_flight(), 0);
+//! ```
+//!
+//! [`Runtime::metrics`]: crate::runtime::Runtime::metrics
+
+use crate::*;
+use std::{
+    ops::Range,
+    sync::atomic::{AtomicI64, AtomicU64, Ordering},
+    time::Duration,
+};
+
+pub use crate::io_port::OpKind;
+
+const BUCKETS: usize = 32;
+
+/// A histogram with power-of-two buckets.
+///
+/// The bucket `0` counts the value `0`, and the bucket `i` counts the values in
+/// `2^(i-1)..2^i`. The last bucket also counts all larger values.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Histogram {
+    buckets: [u64; BUCKETS],
+}
+
+impl Histogram {
+    /// The counts of the buckets.
+    pub fn buckets(&self) -> &[u64] {
+        &self.buckets
+    }
+
+    /// The range of the values counted by the bucket `i`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `i` is not less than the length of [`Histogram::buckets`].
+    pub fn bucket_range(&self, i: usize) -> Range<u64> {
+        assert!(i < BUCKETS, "bucket index out of range");
+        match i {
+            0 => 0..1,
+            i if i == BUCKETS - 1 => 1 << (i - 1)..u64::MAX,
+            i => 1 << (i - 1)..1 << i,
+        }
+    }
+
+    /// The count of all values.
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+
+    /// An upper bound of the `q`-quantile, which is the end of the range of
+    /// the bucket containing it.
+    ///
+    /// Returns `None` if the histogram is empty.
+    pub fn quantile(&self, q: f64) -> Option<u64> {
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+        let rank = ((count as f64 * q.clamp(0.0, 1.0)).ceil() as u64).max(1);
+        let mut sum = 0;
+        self.buckets
+            .iter()
+            .position(|n| {
+                sum += n;
+                sum >= rank
+            })
+            .map(|i| self.bucket_range(i).end)
+    }
+
+    fn merge(&mut self, other: &Self) {
+        for (a, b) in self.buckets.iter_mut().zip(other.buckets) {
+            *a += b;
+        }
+    }
+}
+
+/// The metrics of the operations of one kind.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OpMetrics {
+    submitted: u64,
+    completed: u64,
+    failed: u64,
+    cancelled: u64,
+    bytes: u64,
+    latency: Histogram,
+}
+
+impl OpMetrics {
+    /// The count of the operations submitted, including the ones failed by
+    /// fault injection.
+    pub fn submitted(&self) -> u64 {
+        self.submitted
+    }
+
+    /// The count of the operations completed successfully.
+    pub fn completed(&self) -> u64 {
+        self.completed
+    }
+
+    /// The count of the operations completed with an error.
+    pub fn failed(&self) -> u64 {
+        self.failed
+    }
+
+    /// The count of the operations whose futures are dropped while they are
+    /// in flight.
+    pub fn cancelled(&self) -> u64 {
+        self.cancelled
+    }
+
+    /// The count of bytes transferred by the operations completed
+    /// successfully.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// The latencies in microseconds of the operations returning the result,
+    /// from the submission to the completion observed by the future.
+    pub fn latency(&self) -> &Histogram {
+        &self.latency
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.submitted += other.submitted;
+        self.completed += other.completed;
+        self.failed += other.failed;
+        self.cancelled += other.cancelled;
+        self.bytes += other.bytes;
+        self.latency.merge(&other.latency);
+    }
+}
+
+/// A snapshot of the metrics of a [`Runtime`], summed up over its threads.
+///
+/// [`Runtime`]: crate::runtime::Runtime
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuntimeMetrics {
+    ops: Vec<OpMetrics>,
+    in_flight: u64,
+    cancelling: u64,
+    queued: u64,
+    completions_per_poll: Histogram,
+    empty_polls: u64,
+}
+
+impl RuntimeMetrics {
+    pub(crate) fn collect<'a>(metrics: impl IntoIterator<Item = &'a IoMetrics>) -> Self {
+        let mut res = Self {
+            ops: vec![OpMetrics::default(); OpKind::ALL.len()],
+            in_flight: 0,
+            cancelling: 0,
+            queued: 0,
+            completions_per_poll: Histogram::default(),
+            empty_polls: 0,
+        };
+        let mut in_flight = 0;
+        let mut cancelling = 0;
+        let mut queued = 0;
+        for metrics in metrics {
+            for (op, other) in res.ops.iter_mut().zip(&metrics.ops) {
+                op.merge(&other.load());
+            }
+            in_flight += metrics.in_flight.load(Ordering::Relaxed);
+            cancelling += metrics.cancelling.load(Ordering::Relaxed);
+            queued += metrics.queued.load(Ordering::Relaxed);
+            res.completions_per_poll.merge(&metrics.polls.load());
+            res.empty_polls += metrics.empty_polls.load(Ordering::Relaxed);
+        }
+        // The gauge may be negative if an operation outlives the runtime.
+        res.in_flight = in_flight.max(0) as u64;
+        res.cancelling = cancelling.max(0) as u64;
+        res.queued = queued.max(0) as u64;
+        res
+    }
+
+    /// The metrics of the operations of `kind`.
+    pub fn op(&self, kind: OpKind) -> &OpMetrics {
+        &self.ops[kind as usize]
+    }
+
+    /// The count of the operations submitted to the driver and not completed
+    /// by it, including the cancelled ones the driver still holds.
+    pub fn in_flight(&self) -> u64 {
+        self.in_flight
+    }
+
+    /// The count of the operations whose futures are dropped, and not
+    /// completed by the driver yet. Their buffers are still held by the
+    /// driver.
+    pub fn cancelling(&self) -> u64 {
+        self.cancelling
+    }
+
+    /// The count of the operations waiting to be submitted, because the
+    /// operations in flight reach [`Builder::max_in_flight`].
+    ///
+    /// [`Builder::max_in_flight`]: crate::runtime::Builder::max_in_flight
+    pub fn queued(&self) -> u64 {
+        self.queued
+    }
+
+    /// The count of bytes transferred by all operations.
+    pub fn bytes_transferred(&self) -> u64 {
+        self.ops.iter().map(|op| op.bytes).sum()
+    }
+
+    /// The counts of completions handled each time the driver is polled,
+    /// either when it is woken or when the thread parks.
+    ///
+    /// Only the polls handling at least one completion are counted. The others
+    /// are counted by [`RuntimeMetrics::empty_polls`].
+    pub fn completions_per_poll(&self) -> &Histogram {
+        &self.completions_per_poll
+    }
+
+    /// The count of the polls of the driver handling no completion, e.g., when
+    /// the thread parks without any completion ready.
+    pub fn empty_polls(&self) -> u64 {
+        self.empty_polls
+    }
+}
+
+#[derive(Debug)]
+struct AtomicHistogram {
+    buckets: [AtomicU64; BUCKETS],
+}
+
+impl AtomicHistogram {
+    fn new() -> Self {
+        Self {
+            buckets: std::array::from_fn(|_| AtomicU64::new(0)),
+        }
+    }
+
+    fn record(&self, value: u64) {
+        let i = (u64::BITS - value.leading_zeros()) as usize;
+        self.buckets[i.min(BUCKETS - 1)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn load(&self) -> Histogram {
+        Histogram {
+            buckets: std::array::from_fn(|i| self.buckets[i].load(Ordering::Relaxed)),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct AtomicOpMetrics {
+    submitted: AtomicU64,
+    completed: AtomicU64,
+    failed: AtomicU64,
+    cancelled: AtomicU64,
+    bytes: AtomicU64,
+    latency: AtomicHistogram,
+}
+
+impl AtomicOpMetrics {
+    fn new() -> Self {
+        Self {
+            submitted: AtomicU64::new(0),
+            completed: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+            cancelled: AtomicU64::new(0),
+            bytes: AtomicU64::new(0),
+            latency: AtomicHistogram::new(),
+        }
+    }
+
+    fn load(&self) -> OpMetrics {
+        OpMetrics {
+            submitted: self.submitted.load(Ordering::Relaxed),
+            completed: self.completed.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+            cancelled: self.cancelled.load(Ordering::Relaxed),
+            bytes: self.bytes.load(Ordering::Relaxed),
+            latency: self.latency.load(),
+        }
+    }
+}
+
+/// The counters of a thread, updated by the driver and the futures.
+#[derive(Debug)]
+pub(crate) struct IoMetrics {
+    ops: Vec<AtomicOpMetrics>,
+    in_flight: AtomicI64,
+    cancelling: AtomicI64,
+    queued: AtomicI64,
+    polls: AtomicHistogram,
+    empty_polls: AtomicU64,
+}
+
+impl IoMetrics {
+    pub fn new() -> Self {
+        Self {
+            ops: OpKind::ALL.iter().map(|_| AtomicOpMetrics::new()).collect(),
+            in_flight: AtomicI64::new(0),
+            cancelling: AtomicI64::new(0),
+            queued: AtomicI64::new(0),
+            polls: AtomicHistogram::new(),
+            empty_polls: AtomicU64::new(0),
+        }
+    }
+
+    pub fn submit(&self, kind: OpKind) {
+        self.ops[kind as usize]
+            .submitted
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn complete(&self, kind: OpKind, res: &IoResult<usize>, latency: Duration) {
+        let op = &self.ops[kind as usize];
+        match res {
+            Ok(n) => {
+                op.completed.fetch_add(1, Ordering::Relaxed);
+                op.bytes.fetch_add(*n as u64, Ordering::Relaxed);
+            }
+            Err(_) => {
+                op.failed.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        op.latency.record(latency.as_micros() as u64);
+    }
+
+    pub fn cancel(&self, kind: OpKind) {
+        self.ops[kind as usize]
+            .cancelled
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// An operation is submitted to the driver and pending.
+    pub fn start_io(&self) {
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// An operation is completed by the driver.
+    pub fn finish_io(&self) {
+        self.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// The future of an operation in flight is dropped.
+    pub fn start_cancel(&self) {
+        self.cancelling.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// An operation whose future is dropped is completed by the driver.
+    pub fn finish_cancel(&self) {
+        self.cancelling.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// An operation is queued before submission.
+    pub fn enqueue(&self) {
+        self.queued.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A queued operation is granted a slot, or dropped.
+    pub fn dequeue(&self) {
+        self.queued.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn poll(&self, completions: usize) {
+        if completions == 0 {
+            self.empty_polls.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.polls.record(completions as u64);
+        }
+    }
+}
@@ -0,0 +1,322 @@
+//! The runtime of Tokio with IOCP.
+
+mod builder;
+pub use builder::*;
+
+mod detached;
+pub use detached::*;
+
+mod notifier;
+pub use notifier::*;
+
+mod worker;
+
+pub mod fault;
+pub mod metrics;
+pub mod sim;
+
+use crate::{
+    io_port::{self, IO_PORT},
+    runtime::metrics::{IoMetrics, RuntimeMetrics},
+    *,
+};
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::task::{JoinHandle, LocalSet};
+use worker::{Worker, WorkerHandle};
+
+/// The max time to wait for the outstanding operations when the runtime is
+/// dropped.
+const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The `tokio-iocp` runtime.
+///
+/// The runtime executes the future passed to [`Runtime::block_on`] on the
+/// current thread. If it is built with [`Builder::worker_threads`], it also
+/// owns the worker threads, which are stopped when the runtime is dropped.
+///
+/// When the runtime is dropped, the tasks are dropped, and the outstanding
+/// operations are cancelled. The runtime waits up to 1 second for them to
+/// complete, so that their buffers could be released safely. See
+/// [`Runtime::shutdown_timeout`] to wait for a different time.
+#[derive(Debug)]
+pub struct Runtime {
+    rt: tokio::runtime::Runtime,
+    // Taken when shutting down.
+    local: Option<LocalSet>,
+    event_batch_size: usize,
+    completion_budget: usize,
+    max_in_flight: usize,
+    #[cfg(windows)]
+    skip_on_success: bool,
+    #[cfg(target_os = "linux")]
+    driver: Option<DriverKind>,
+    drive: bool,
+    sim: Option<(u64, u64)>,
+    fault_policy: Option<fault::FaultPolicy>,
+    graveyard: Option<Graveyard>,
+    metrics: Arc<IoMetrics>,
+    handle: Handle,
+    // Dropped after all other fields.
+    workers: Vec<Worker>,
+    // Dropped after the workers, which may still run blocking calls when
+    // shutting down.
+    blocking_pool: tokio::runtime::Runtime,
+}
+
+impl Runtime {
+    /// Creates a new Tokio runtime, with all features enabled.
+    ///
+    /// It is the same as `Builder::new().build()`.
+    pub fn new() -> IoResult<Self> {
+        Builder::new().build()
+    }
+
+    /// Creates a [`Builder`].
+    /// It is the same as [`Builder::new`].
+    pub fn builder() -> Builder {
+        Builder::new()
+    }
+
+    /// Runs a future to completion on the runtime.
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        IO_PORT.with(|port| {
+            // The driver is set by `Builder::build`. It is set again in case
+            // another runtime replaced it, and kept if the handles of that
+            // runtime are still attached.
+            #[cfg(target_os = "linux")]
+            port.set_driver(self.driver).ok();
+            port.set_batch_size(self.event_batch_size);
+            port.set_completion_budget(self.completion_budget);
+            port.set_max_in_flight(self.max_in_flight);
+            #[cfg(windows)]
+            port.set_skip_on_success(self.skip_on_success);
+            port.set_sim(self.sim);
+            port.set_fault_policy(self.fault_policy.clone());
+            port.set_metrics(self.metrics.clone());
+            port.set_graveyard(self.graveyard.clone());
+            port.set_blocking_pool(Some(self.blocking_pool.handle().clone()));
+        });
+        io_port::block_on(&self.rt, self.local.as_ref().unwrap(), future, self.drive)
+    }
+
+    /// Returns a snapshot of the metrics, summed up over the thread calling
+    /// [`Runtime::block_on`] and the worker threads.
+    ///
+    /// See [`metrics`] for more details.
+    pub fn metrics(&self) -> RuntimeMetrics {
+        RuntimeMetrics::collect(
+            std::iter::once(&*self.metrics).chain(self.workers.iter().map(|w| w.metrics())),
+        )
+    }
+
+    /// Waits for the cancelled operations on the current thread and the worker
+    /// threads to complete, so that their buffers are released, or passed to
+    /// [`Builder::graveyard`].
+    ///
+    /// It should be awaited in the runtime.
+    pub async fn drain_cancelled(&self) {
+        let workers = (0..self.handle.worker_threads())
+            .map(|worker| self.handle.spawn_on(worker, io_port::drain_cancelled))
+            .collect::<Vec<_>>();
+        io_port::drain_cancelled().await;
+        for worker in workers {
+            worker.await.ok();
+        }
+    }
+
+    /// Returns a [`Handle`] to spawn tasks on the worker threads.
+    pub fn handle(&self) -> Handle {
+        self.handle.clone()
+    }
+
+    /// Shuts down the runtime, waiting up to `timeout` for the outstanding
+    /// operations.
+    ///
+    /// The tasks are dropped, and all operations submitted on the current
+    /// thread and the worker threads are cancelled. The buffers are released
+    /// when the operations complete.
+    ///
+    /// Returns the count of the operations still outstanding when the timeout
+    /// expires. Their buffers are leaked, because the system may still write
+    /// into them.
+    pub fn shutdown_timeout(mut self, timeout: Duration) -> usize {
+        self.shutdown(timeout)
+    }
+
+    fn shutdown(&mut self, timeout: Duration) -> usize {
+        // The workers shut down in parallel.
+        for worker in &mut self.workers {
+            worker.stop(timeout);
+        }
+        let outstanding = match self.local.take() {
+            Some(local) => {
+                {
+                    let _guard = self.rt.enter();
+                    drop(local);
+                }
+                IO_PORT
+                    .try_with(|port| port.shutdown(timeout))
+                    .unwrap_or_default()
+            }
+            None => 0,
+        };
+        outstanding + self.workers.iter_mut().map(Worker::join).sum::<usize>()
+    }
+}
+
+impl Drop for Runtime {
+    fn drop(&mut self) {
+        self.shutdown(DEFAULT_SHUTDOWN_TIMEOUT);
+    }
+}
+
+/// A handle to spawn tasks on the worker threads of a [`Runtime`].
+///
+/// The handle is cheap to clone, and could be sent to other threads.
+#[derive(Debug, Clone)]
+pub struct Handle {
+    workers: Arc<[WorkerHandle]>,
+    next: Arc<AtomicUsize>,
+}
+
+impl Handle {
+    fn new(workers: Arc<[WorkerHandle]>) -> Self {
+        Self {
+            workers,
+            next: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// The number of worker threads.
+    pub fn worker_threads(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Spawns a task on the specified worker.
+    ///
+    /// The factory `f` is sent to the worker, and the future it returns is
+    /// spawned as a local task there, so the future need not be [`Send`].
+    /// Aborting the returned [`JoinHandle`] aborts the task.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `worker` is not less than [`Handle::worker_threads`].
+    pub fn spawn_on<F, Fut>(&self, worker: usize, f: F) -> JoinHandle<Fut::Output>
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future + 'static,
+        Fut::Output: Send + 'static,
+    {
+        self.workers[worker].spawn(async move {
+            let mut task = AbortOnDrop(tokio::task::spawn_local(f()));
+            match (&mut task.0).await {
+                Ok(res) => res,
+                Err(e) => match e.try_into_panic() {
+                    Ok(payload) => std::panic::resume_unwind(payload),
+                    // The task is cancelled because the worker is shutting down.
+                    Err(_) => std::future::pending().await,
+                },
+            }
+        })
+    }
+
+    /// Spawns a task on the workers in a round-robin fashion.
+    ///
+    /// See [`Handle::spawn_on`] for more details.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the runtime has no worker threads.
+    pub fn spawn_pinned<F, Fut>(&self, f: F) -> JoinHandle<Fut::Output>
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future + 'static,
+        Fut::Output: Send + 'static,
+    {
+        assert!(
+            !self.workers.is_empty(),
+            "the runtime has no worker threads"
+        );
+        let worker = self.next.fetch_add(1, Ordering::Relaxed) % self.workers.len();
+        self.spawn_on(worker, f)
+    }
+}
+
+/// Aborts the local task when the remote task is aborted.
+struct AbortOnDrop<T>(JoinHandle<T>);
+
+impl<T> Drop for AbortOnDrop<T> {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Spawns a new asynchronous task, returning a [`JoinHandle`] for it.
+///
+/// Spawning a task enables the task to execute concurrently to other tasks.
+/// There is no guarantee that a spawned task will execute to completion. When a
+/// runtime is shutdown, all outstanding tasks are dropped, regardless of the
+/// lifecycle of that task.
+///
+/// This function must be called from the context of a `tokio-iocp` runtime.
+/// Called from a task on a worker thread, the new task is spawned on the same worker.
+///
+/// [`JoinHandle`]: tokio::task::JoinHandle
+///
+/// # Examples
+///
+/// In this example, a server is started and `spawn` is used to start a new task
+/// that processes each received connection.
+///
+/// ```
+/// tokio_iocp::start(async {
+///     let handle = tokio_iocp::spawn(async {
+///         println!("hello from a background task");
+///     });
+///
+///     // Let the task complete
+///     handle.await.unwrap();
+/// });
+/// ```
+pub fn spawn<F: Future + 'static>(future: F) -> JoinHandle<F::Output> {
+    tokio::task::spawn_local(future)
+}
+
+/// Runs a blocking syscall on the blocking pool of the runtime, or the Tokio
+/// blocking pool outside of a `tokio-iocp` runtime.
+///
+/// See [`Builder::max_blocking_threads`].
+pub(crate) async fn spawn_blocking<T: Send + 'static>(
+    f: impl FnOnce() -> IoResult<T> + Send + 'static,
+) -> IoResult<T> {
+    let task = match IO_PORT.with(|port| port.blocking_pool()) {
+        Some(pool) => pool.spawn_blocking(f),
+        None => tokio::task::spawn_blocking(f),
+    };
+    match task.await {
+        Ok(res) => res,
+        Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+        Err(_) => Err(IoError::other("the runtime is shut down")),
+    }
+}
+
+#[cfg(feature = "criterion")]
+impl criterion::async_executor::AsyncExecutor for Runtime {
+    fn block_on<T>(&self, future: impl Future<Output = T>) -> T {
+        self.block_on(future)
+    }
+}
+
+#[cfg(feature = "criterion")]
+impl criterion::async_executor::AsyncExecutor for &Runtime {
+    fn block_on<T>(&self, future: impl Future<Output = T>) -> T {
+        (*self).block_on(future)
+    }
+}
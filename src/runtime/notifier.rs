@@ -0,0 +1,104 @@
+use crate::{
+    io_port::{Signal, IO_PORT},
+    *,
+};
+use std::{
+    collections::VecDeque,
+    future::poll_fn,
+    sync::{Arc, Mutex},
+    task::Poll,
+};
+
+#[derive(Debug)]
+struct Shared {
+    values: Mutex<VecDeque<Option<u64>>>,
+    signal: Signal,
+}
+
+/// A notifier waking a task on a runtime thread from any thread.
+///
+/// A notification is posted into the driver of the thread creating the
+/// notifier, as a completion packet on Windows, or by an eventfd on Linux, so
+/// that the thread is woken directly. It could carry a `u64` payload. The
+/// notifications are queued, and each one is received once by
+/// [`Notifier::notified`].
+///
+/// The notifier is cheap to clone, and could be sent to other threads.
+///
+/// # Examples
+///
+/// ```
+/// use tokio_iocp::runtime::Notifier;
+///
+/// tokio_iocp::start(async {
+///     let notifier = Notifier::new();
+///     let remote = notifier.clone();
+///     let thread = std::thread::spawn(move || remote.notify_with(42).unwrap());
+///     assert_eq!(notifier.notified().await.unwrap(), Some(42));
+///     thread.join().unwrap();
+/// });
+/// ```
+#[derive(Debug, Clone)]
+pub struct Notifier {
+    shared: Arc<Shared>,
+}
+
+impl Notifier {
+    /// Creates a notifier bound to the driver of the current thread.
+    pub fn new() -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                values: Mutex::new(VecDeque::new()),
+                signal: IO_PORT.with(|port| port.signal().clone()),
+            }),
+        }
+    }
+
+    /// Posts a notification without payload.
+    pub fn notify(&self) -> IoResult<()> {
+        self.post(None)
+    }
+
+    /// Posts a notification with a payload.
+    pub fn notify_with(&self, value: u64) -> IoResult<()> {
+        self.post(Some(value))
+    }
+
+    fn post(&self, value: Option<u64>) -> IoResult<()> {
+        self.shared.values.lock().unwrap().push_back(value);
+        self.shared.signal.post()
+    }
+
+    /// Waits for a notification, and returns its payload.
+    ///
+    /// It should be awaited on the thread creating the notifier, or an
+    /// [`InvalidInput`] error is returned.
+    ///
+    /// [`InvalidInput`]: std::io::ErrorKind::InvalidInput
+    pub async fn notified(&self) -> IoResult<Option<u64>> {
+        poll_fn(|cx| {
+            IO_PORT.with(|port| {
+                if !port.signal().ptr_eq(&self.shared.signal) {
+                    return Poll::Ready(Err(IoError::new(
+                        std::io::ErrorKind::InvalidInput,
+                        "the notifier is not bound to the driver of the current thread",
+                    )));
+                }
+                match self.shared.values.lock().unwrap().pop_front() {
+                    Some(value) => Poll::Ready(Ok(value)),
+                    None => {
+                        port.register_notified(cx.waker());
+                        Poll::Pending
+                    }
+                }
+            })
+        })
+        .await
+    }
+}
+
+impl Default for Notifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,176 @@
+//! The simulated driver for testing.
+//!
+//! A runtime built with [`Builder::sim`] queues the operations instead of
+//! executing them. The test decides when each operation completes, with how
+//! many bytes, or with which error, by the functions in this module. They
+//! operate on the driver of the current thread, so they should be called in
+//! the runtime.
+//!
+//! The resources are still created by the system, but no data is read from or
+//! written to them. Accepted connections and remote addresses are not
+//! simulated, so an operation of [`OpKind::Accept`] could only complete with
+//! an error, and a successful [`OpKind::RecvFrom`] fails with
+//! [`std::io::ErrorKind::InvalidData`].
+//!
+//! ```
+//! use tokio_iocp::{
+//!     net::UdpSocket,
+//!     runtime::{sim, Runtime},
+//! };
+//!
+//! let runtime = Runtime::builder().sim(42).build().unwrap();
+//! runtime.block_on(async {
+//!     let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+//!     let recv = tokio_iocp::spawn(async move { socket.recv(Vec::with_capacity(64)).await });
+//!     tokio::task::yield_now().await;
+//!
+//!     let ops = sim::pending();
+//!     assert_eq!(ops[0].kind(), sim::OpKind::Recv);
+//!     sim::complete_with(ops[0].id(), b"hello").unwrap();
+//!
+//!     let (res, buf) = recv.await.unwrap();
+//!     assert_eq!(res.unwrap(), 5);
+//!     assert_eq!(buf, b"hello");
+//! });
+//! ```
+//!
+//! [`Builder::sim`]: crate::runtime::Builder::sim
+
+use crate::{
+    io_port::{sim_disabled, OpCodeExt, IO_PORT},
+    *,
+};
+
+pub use crate::io_port::OpKind;
+
+/// An operation queued in the simulated driver.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Op {
+    id: u64,
+    kind: OpKind,
+    cancelled: bool,
+}
+
+impl Op {
+    /// The id of the operation, unique in the driver of the current thread.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// The kind of the operation.
+    pub fn kind(&self) -> OpKind {
+        self.kind
+    }
+
+    /// Whether the future of the operation has been dropped.
+    ///
+    /// Like a real driver, the cancelled operation is queued until it is
+    /// completed, and the buffer is released then.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+}
+
+/// The operations queued in the simulated driver of the current thread, in
+/// the order of submission.
+///
+/// # Panics
+///
+/// Panics if the simulated driver is not enabled on the current thread.
+pub fn pending() -> Vec<Op> {
+    IO_PORT.with(|port| {
+        port.sim()
+            .expect("the simulated driver is not enabled on the current thread")
+            .with_ops(|ops| {
+                ops.iter()
+                    .map(|op| Op {
+                        id: op.id,
+                        kind: op.kind,
+                        cancelled: op.cancelled,
+                    })
+                    .collect()
+            })
+    })
+}
+
+/// Picks one of the queued operations with the random generator seeded by
+/// [`Builder::sim`], so that the order is reproducible with the same seed.
+///
+/// Returns `None` if no operation is queued.
+///
+/// # Panics
+///
+/// Panics if the simulated driver is not enabled on the current thread.
+///
+/// [`Builder::sim`]: crate::runtime::Builder::sim
+pub fn pick() -> Option<Op> {
+    let ops = pending();
+    if ops.is_empty() {
+        return None;
+    }
+    let index = IO_PORT.with(|port| port.sim().unwrap().next_random()) % ops.len() as u64;
+    ops.into_iter().nth(index as usize)
+}
+
+/// Completes the queued operation with the result.
+///
+/// A read operation completed with `Ok(n)` receives `n` zero bytes. A write
+/// operation completed with `Ok(n)` writes the first `n` bytes. `n` should not
+/// be larger than the buffer.
+pub fn complete(id: u64, res: IoResult<usize>) -> IoResult<()> {
+    let res = match res {
+        Ok(n) => Ok(with_op(id, |op| match op.kind() {
+            OpKind::Read | OpKind::Recv | OpKind::RecvFrom => {
+                check_len(n, op.buf_len())?;
+                Ok(op.fill(&vec![0; n]))
+            }
+            OpKind::Write | OpKind::Send | OpKind::SendTo => {
+                check_len(n, op.buf_len())?;
+                Ok(n)
+            }
+            OpKind::Accept => Err(IoError::new(
+                std::io::ErrorKind::InvalidInput,
+                "accepted connections are not simulated",
+            )),
+            _ => Ok(0),
+        })?),
+        Err(e) => Err(e),
+    };
+    IO_PORT.with(|port| port.complete_sim(id, res))
+}
+
+/// Completes the queued read operation with the data.
+///
+/// Returns the count of bytes received, which is less than the length of
+/// `data` if the buffer is not large enough.
+pub fn complete_with(id: u64, data: &[u8]) -> IoResult<usize> {
+    let n = with_op(id, |op| match op.kind() {
+        OpKind::Read | OpKind::Recv | OpKind::RecvFrom => Ok(op.fill(data)),
+        _ => Err(IoError::new(
+            std::io::ErrorKind::InvalidInput,
+            "the operation is not a read operation",
+        )),
+    })?;
+    IO_PORT.with(|port| port.complete_sim(id, Ok(n)))?;
+    Ok(n)
+}
+
+/// The data of the queued write operation.
+pub fn data(id: u64) -> IoResult<Vec<u8>> {
+    with_op(id, |op| Ok(op.data()))
+}
+
+fn with_op<R>(id: u64, f: impl FnOnce(&mut dyn OpCodeExt) -> IoResult<R>) -> IoResult<R> {
+    IO_PORT.with(|port| port.sim().ok_or_else(sim_disabled)?.with_op(id, f)?)
+}
+
+fn check_len(n: usize, len: usize) -> IoResult<()> {
+    if n > len {
+        Err(IoError::new(
+            std::io::ErrorKind::InvalidInput,
+            "the result is larger than the buffer",
+        ))
+    } else {
+        Ok(())
+    }
+}
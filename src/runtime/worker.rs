@@ -0,0 +1,192 @@
+use crate::{
+    io_port::{self, Parker, IO_PORT},
+    runtime::{metrics::IoMetrics, Builder, DEFAULT_SHUTDOWN_TIMEOUT},
+    *,
+};
+use std::{future::Future, sync::Arc, thread::JoinHandle, time::Duration};
+use tokio::{sync::oneshot, task::LocalSet};
+
+/// A handle to spawn tasks on a worker thread.
+#[derive(Debug, Clone)]
+pub struct WorkerHandle {
+    handle: tokio::runtime::Handle,
+    parker: Parker,
+}
+
+impl WorkerHandle {
+    /// Spawns a task on the worker, and wakes its driver if the thread is
+    /// blocked on it.
+    pub fn spawn<F>(&self, future: F) -> tokio::task::JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let task = self.handle.spawn(future);
+        self.parker.notify();
+        task
+    }
+}
+
+/// A worker thread running a Tokio `current_thread` runtime with its own driver.
+#[derive(Debug)]
+pub struct Worker {
+    handle: WorkerHandle,
+    metrics: Arc<IoMetrics>,
+    shutdown: Option<oneshot::Sender<Duration>>,
+    // Returns the count of the outstanding operations.
+    thread: Option<JoinHandle<usize>>,
+}
+
+impl Worker {
+    pub fn start(
+        index: usize,
+        builder: &Builder,
+        blocking_pool: tokio::runtime::Handle,
+    ) -> IoResult<Self> {
+        let (started_tx, started_rx) = std::sync::mpsc::channel();
+        let (shutdown_tx, shutdown_rx) = oneshot::channel();
+        let core = builder.worker_core(index);
+        let on_start = builder.thread_start_hook();
+        let on_stop = builder.thread_stop_hook();
+        let metrics = Arc::new(IoMetrics::new());
+        let thread_metrics = metrics.clone();
+        let builder = builder.clone();
+        let thread = std::thread::Builder::new()
+            .name(builder.worker_name(index))
+            .spawn(move || {
+                let rt = core
+                    .map(set_current_affinity)
+                    .transpose()
+                    .and_then(|_| builder.tokio_runtime());
+                let rt = match rt {
+                    Ok(rt) => rt,
+                    Err(e) => {
+                        started_tx.send(Err(e)).ok();
+                        return 0;
+                    }
+                };
+                #[cfg(target_os = "linux")]
+                if let Err(e) = IO_PORT.with(|port| port.set_driver(builder.driver_kind())) {
+                    started_tx.send(Err(e)).ok();
+                    return 0;
+                }
+                IO_PORT.with(|port| {
+                    port.set_batch_size(builder.batch_size());
+                    port.set_completion_budget(builder.budget());
+                    port.set_max_in_flight(builder.in_flight_limit());
+                    #[cfg(windows)]
+                    port.set_skip_on_success(builder.skip_completion_port());
+                    port.set_sim(builder.worker_sim(index));
+                    port.set_fault_policy(builder.faults());
+                    port.set_metrics(thread_metrics);
+                    port.set_graveyard(builder.graveyard_hook());
+                    port.set_blocking_pool(Some(blocking_pool));
+                });
+                if let Some(f) = on_start {
+                    f();
+                }
+                let timeout = {
+                    let local = LocalSet::new();
+                    // Enter the local set, so that the tasks spawned by the handle
+                    // could spawn local tasks.
+                    let _guard = local.enter();
+                    let handle = WorkerHandle {
+                        handle: rt.handle().clone(),
+                        parker: IO_PORT.with(|port| port.parker().clone()),
+                    };
+                    started_tx.send(Ok(handle)).ok();
+                    io_port::block_on(&rt, &local, shutdown_rx, builder.drive())
+                        .unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT)
+                };
+                // The tasks have been dropped with the local set, and their
+                // operations are cancelled.
+                let outstanding = IO_PORT.with(|port| port.shutdown(timeout));
+                drop(rt);
+                if let Some(f) = on_stop {
+                    f();
+                }
+                outstanding
+            })?;
+        match started_rx.recv() {
+            Ok(Ok(handle)) => Ok(Self {
+                handle,
+                metrics,
+                shutdown: Some(shutdown_tx),
+                thread: Some(thread),
+            }),
+            Ok(Err(e)) => {
+                thread.join().ok();
+                Err(e)
+            }
+            Err(_) => {
+                thread.join().ok();
+                Err(IoError::other("the worker thread exited unexpectedly"))
+            }
+        }
+    }
+
+    pub fn handle(&self) -> &WorkerHandle {
+        &self.handle
+    }
+
+    pub fn metrics(&self) -> &IoMetrics {
+        &self.metrics
+    }
+
+    /// Signals the worker to shut down, waiting up to `timeout` for the
+    /// outstanding operations.
+    pub fn stop(&mut self, timeout: Duration) {
+        if let Some(shutdown) = self.shutdown.take() {
+            shutdown.send(timeout).ok();
+        }
+    }
+
+    /// Waits for the worker to exit, and returns the count of the operations
+    /// still outstanding.
+    pub fn join(&mut self) -> usize {
+        self.thread
+            .take()
+            .and_then(|thread| thread.join().ok())
+            .unwrap_or_default()
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        self.stop(DEFAULT_SHUTDOWN_TIMEOUT);
+        self.join();
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn set_current_affinity(core: usize) -> IoResult<()> {
+    if core >= libc::CPU_SETSIZE as usize {
+        return Err(IoError::new(
+            std::io::ErrorKind::InvalidInput,
+            "invalid core index",
+        ));
+    }
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(core, &mut set);
+        if libc::sched_setaffinity(0, std::mem::size_of_val(&set), &set) == 0 {
+            Ok(())
+        } else {
+            Err(IoError::last_os_error())
+        }
+    }
+}
+
+#[cfg(windows)]
+fn set_current_affinity(core: usize) -> IoResult<()> {
+    use windows_sys::Win32::System::Threading::{GetCurrentThread, SetThreadAffinityMask};
+
+    let mask = 1usize
+        .checked_shl(core as _)
+        .ok_or_else(|| IoError::new(std::io::ErrorKind::InvalidInput, "invalid core index"))?;
+    if unsafe { SetThreadAffinityMask(GetCurrentThread(), mask) } != 0 {
+        Ok(())
+    } else {
+        Err(IoError::last_os_error())
+    }
+}
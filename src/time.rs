@@ -0,0 +1,185 @@
+//! Timers driven by the driver of the runtime thread.
+//!
+//! The timers are registered on the driver of the thread polling them, which
+//! waits for the completions and the nearest timer at once. They don't rely on
+//! the Tokio time driver, and fire even if it is disabled.
+//!
+//! A timer should be awaited on a runtime thread. It never fires on other
+//! threads.
+
+use crate::io_port::IO_PORT;
+use std::{
+    future::{poll_fn, Future},
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+/// Waits until `duration` has elapsed.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::{Duration, Instant};
+///
+/// tokio_iocp::start(async {
+///     let start = Instant::now();
+///     tokio_iocp::time::sleep(Duration::from_millis(10)).await;
+///     assert!(start.elapsed() >= Duration::from_millis(10));
+/// });
+/// ```
+pub fn sleep(duration: Duration) -> Sleep {
+    sleep_until(Instant::now() + duration)
+}
+
+/// Waits until `deadline` is reached.
+pub fn sleep_until(deadline: Instant) -> Sleep {
+    Sleep {
+        deadline,
+        key: None,
+    }
+}
+
+/// Future returned by [`sleep`] and [`sleep_until`].
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Sleep {
+    deadline: Instant,
+    // The key of the timer registered on the driver.
+    key: Option<u64>,
+}
+
+impl Sleep {
+    /// Returns the instant at which the future will complete.
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+
+    /// Returns `true` if the deadline has been reached.
+    pub fn is_elapsed(&self) -> bool {
+        Instant::now() >= self.deadline
+    }
+
+    /// Resets the deadline of the future.
+    pub fn reset(&mut self, deadline: Instant) {
+        self.remove();
+        self.deadline = deadline;
+    }
+
+    fn remove(&mut self) {
+        if let Some(key) = self.key.take() {
+            IO_PORT.try_with(|port| port.remove_timer(key)).ok();
+        }
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        if this.is_elapsed() {
+            this.remove();
+            return Poll::Ready(());
+        }
+        let key = IO_PORT.with(|port| port.register_timer(this.key, this.deadline, cx.waker()));
+        this.key = Some(key);
+        Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        self.remove();
+    }
+}
+
+/// Creates an [`Interval`] whose first tick completes immediately.
+///
+/// # Panics
+///
+/// Panics if `period` is zero.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+///
+/// tokio_iocp::start(async {
+///     let mut interval = tokio_iocp::time::interval(Duration::from_millis(10));
+///     let first = interval.tick().await;
+///     let second = interval.tick().await;
+///     assert_eq!(second - first, Duration::from_millis(10));
+/// });
+/// ```
+pub fn interval(period: Duration) -> Interval {
+    interval_at(Instant::now(), period)
+}
+
+/// Creates an [`Interval`] whose first tick completes at `start`.
+///
+/// # Panics
+///
+/// Panics if `period` is zero.
+pub fn interval_at(start: Instant, period: Duration) -> Interval {
+    assert!(period > Duration::ZERO, "`period` must be non-zero.");
+    Interval {
+        sleep: sleep_until(start),
+        period,
+    }
+}
+
+/// An instant which is never reached in practice, for a deadline not
+/// representable by [`Instant`].
+fn far_future(now: Instant) -> Instant {
+    now + Duration::from_secs(86400 * 365 * 30)
+}
+
+/// Interval returned by [`interval`] and [`interval_at`].
+///
+/// If some ticks are missed, e.g., the thread is busy, they are skipped, and
+/// the next tick completes at the next multiple of `period` from the start.
+#[derive(Debug)]
+pub struct Interval {
+    sleep: Sleep,
+    period: Duration,
+}
+
+impl Interval {
+    /// Completes when the next instant in the interval has been reached, and
+    /// returns that instant.
+    pub async fn tick(&mut self) -> Instant {
+        poll_fn(|cx| self.poll_tick(cx)).await
+    }
+
+    /// Polls for the next instant in the interval to be reached.
+    pub fn poll_tick(&mut self, cx: &mut Context<'_>) -> Poll<Instant> {
+        if Pin::new(&mut self.sleep).poll(cx).is_pending() {
+            return Poll::Pending;
+        }
+        let deadline = self.sleep.deadline();
+        let now = Instant::now();
+        let period = self.period.as_nanos();
+        let ticks = now.saturating_duration_since(deadline).as_nanos() / period + 1;
+        // It doesn't overflow, as it is less than the elapsed time plus one
+        // period.
+        let offset = period * ticks;
+        let next = u64::try_from(offset / 1_000_000_000)
+            .ok()
+            .map(|secs| Duration::new(secs, (offset % 1_000_000_000) as u32))
+            .and_then(|offset| deadline.checked_add(offset))
+            .unwrap_or_else(|| far_future(now));
+        self.sleep.reset(next);
+        Poll::Ready(deadline)
+    }
+
+    /// Resets the interval, so that the next tick completes after `period`.
+    pub fn reset(&mut self) {
+        self.sleep.reset(Instant::now() + self.period);
+    }
+
+    /// Returns the period of the interval.
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+}
@@ -0,0 +1,52 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+};
+use tokio_iocp::net::UdpSocket;
+
+/// Counts the allocations of the current thread.
+struct Counter;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for Counter {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.try_with(|c| c.set(c.get() + 1)).ok();
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counter = Counter;
+
+#[test]
+fn no_allocation_per_op() {
+    tokio_iocp::start(async {
+        let rx = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let tx = UdpSocket::bind("127.0.0.1:0").unwrap();
+        tx.connect(rx.local_addr().unwrap()).unwrap();
+
+        let mut send_buf = vec![0u8; 64];
+        let mut recv_buf = Vec::with_capacity(64);
+        // The slab is warm after the first round trips.
+        for i in 0..100 {
+            if i == 10 {
+                ALLOCATIONS.with(|c| c.set(0));
+            }
+            let (recv, (res, buf)) = tokio::join!(rx.recv(recv_buf), tx.send(send_buf));
+            assert_eq!(res.unwrap(), 64);
+            send_buf = buf;
+            let (res, mut buf) = recv;
+            assert_eq!(res.unwrap(), 64);
+            buf.clear();
+            recv_buf = buf;
+        }
+        assert_eq!(ALLOCATIONS.with(Cell::get), 0);
+    });
+}
@@ -0,0 +1,126 @@
+use std::{
+    io::ErrorKind,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+use tempfile::{tempdir, NamedTempFile};
+use tokio_iocp::{
+    fs::OpenOptions,
+    net::{TcpListener, TcpStream, UnixListener, UnixStream},
+    runtime::Runtime,
+};
+
+const HELLO: &[u8] = b"hello world...";
+
+#[test]
+fn open_and_sync() {
+    tokio_iocp::start(async {
+        let tempfile = NamedTempFile::new().unwrap();
+        let file = OpenOptions::new()
+            .write(true)
+            .open_async(tempfile.path())
+            .await
+            .unwrap();
+        file.write_at(HELLO, 0).await.0.unwrap();
+        file.sync_all().await.unwrap();
+        file.sync_data().await.unwrap();
+        assert_eq!(std::fs::read(tempfile.path()).unwrap(), HELLO);
+
+        let err = OpenOptions::new()
+            .read(true)
+            .open_async(tempfile.path().with_extension("missing"))
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+    });
+}
+
+async fn open_many() {
+    let mut options = OpenOptions::new();
+    options.read(true);
+    let opens = (0..8).map(|_| options.open_async("Cargo.toml"));
+    for file in futures_util::future::join_all(opens).await {
+        file.unwrap();
+    }
+}
+
+#[test]
+fn bounded_pool() {
+    let threads = Arc::new(AtomicUsize::new(0));
+    let runtime = Runtime::builder()
+        .worker_threads(2)
+        .max_blocking_threads(1)
+        .thread_name("blocking")
+        .on_thread_start({
+            let threads = threads.clone();
+            move || {
+                // The workers are named `blocking-{index}`.
+                if std::thread::current().name() == Some("blocking") {
+                    threads.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        })
+        .build()
+        .unwrap();
+    let handle = runtime.handle();
+    runtime.block_on(async {
+        let workers = (0..2)
+            .map(|worker| handle.spawn_on(worker, open_many))
+            .collect::<Vec<_>>();
+        open_many().await;
+        for worker in workers {
+            worker.await.unwrap();
+        }
+    });
+    // The pool is shared by the workers, and the calls are queued on the only
+    // blocking thread.
+    assert_eq!(threads.load(Ordering::Relaxed), 1);
+
+    let err = Runtime::builder()
+        .max_blocking_threads(0)
+        .build()
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+}
+
+#[test]
+fn connect_async() {
+    tokio_iocp::start(async {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("connect-async.sock");
+        let listener = UnixListener::bind(&path).unwrap();
+        let (tx, (rx, _)) =
+            tokio::try_join!(UnixStream::connect_async(path), listener.accept()).unwrap();
+        tx.send(HELLO).await.0.unwrap();
+        let (res, buf) = rx.recv(Vec::with_capacity(64)).await;
+        assert_eq!(res.unwrap(), HELLO.len());
+        assert_eq!(buf, HELLO);
+
+        // The host name is resolved on the blocking pool.
+        let listener = TcpListener::bind("localhost:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (tx, _) = tokio::try_join!(
+            TcpStream::connect_async(format!("localhost:{port}")),
+            listener.accept()
+        )
+        .unwrap();
+        assert_eq!(tx.peer_addr().unwrap().port(), port);
+    });
+}
+
+#[test]
+fn connect_borrowed_addr() {
+    tokio_iocp::start(async {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let host = addr.to_string();
+        let (tx, _) =
+            tokio::try_join!(TcpStream::connect(host.as_str()), listener.accept()).unwrap();
+        assert_eq!(tx.peer_addr().unwrap(), addr);
+        let addrs = [addr];
+        let (tx, _) = tokio::try_join!(TcpStream::connect(&addrs[..]), listener.accept()).unwrap();
+        assert_eq!(tx.peer_addr().unwrap(), addr);
+    });
+}
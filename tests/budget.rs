@@ -0,0 +1,91 @@
+use std::{
+    cell::Cell,
+    io::ErrorKind,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+use tokio_iocp::{
+    fs::File,
+    net::UdpSocket,
+    runtime::{
+        fault::{Fault, FaultPolicy, OpKind},
+        Runtime,
+    },
+};
+
+#[test]
+fn completions_per_poll() {
+    let runtime = Runtime::builder()
+        .event_batch_size(64)
+        .completion_budget(4)
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        let file = File::open("Cargo.toml").unwrap();
+        let reads = (0..32).map(|_| file.read_at(Vec::with_capacity(64), 0));
+        for (res, _) in futures_util::future::join_all(reads).await {
+            res.unwrap();
+        }
+    });
+    let polls = runtime.metrics().completions_per_poll().clone();
+    for (i, count) in polls.buckets().iter().enumerate() {
+        if polls.bucket_range(i).start > 4 {
+            assert_eq!(*count, 0, "{polls:?}");
+        }
+    }
+}
+
+#[test]
+fn yield_on_inline_completions() {
+    let policy = FaultPolicy::new().with(Fault::error(ErrorKind::Other).on(OpKind::Send));
+    let runtime = Runtime::builder()
+        .completion_budget(8)
+        .fault_policy(policy)
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.connect(socket.local_addr().unwrap()).unwrap();
+
+        let ran = Rc::new(Cell::new(false));
+        let task = tokio_iocp::spawn({
+            let ran = ran.clone();
+            async move { ran.set(true) }
+        });
+        // Every send fails inline, and the task yields every 8 sends.
+        let mut buf = vec![0u8; 8];
+        for i in 0..8 {
+            assert!(!ran.get());
+            let (res, buffer) = socket.send(buf).await;
+            assert_eq!(res.unwrap_err().kind(), ErrorKind::Other);
+            buf = buffer;
+            if i == 7 {
+                assert!(ran.get());
+            }
+        }
+        task.await.unwrap();
+    });
+}
+
+#[test]
+fn other_tasks_progress() {
+    let runtime = Runtime::builder().completion_budget(16).build().unwrap();
+    runtime.block_on(async {
+        let rx = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let tx = UdpSocket::bind("127.0.0.1:0").unwrap();
+        tx.connect(rx.local_addr().unwrap()).unwrap();
+
+        // A socket with a constant stream of completions.
+        let flood = tokio_iocp::spawn(async move {
+            let deadline = Instant::now() + Duration::from_millis(200);
+            while Instant::now() < deadline {
+                tx.send(vec![0u8; 8]).await.0.unwrap();
+                rx.recv(Vec::with_capacity(8)).await.0.unwrap();
+            }
+        });
+        let start = Instant::now();
+        tokio_iocp::time::sleep(Duration::from_millis(10)).await;
+        assert!(start.elapsed() < Duration::from_millis(150));
+        flood.await.unwrap();
+    });
+}
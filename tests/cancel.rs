@@ -0,0 +1,303 @@
+use std::{io::ErrorKind, net::Ipv4Addr, time::Duration};
+use tokio_iocp::{
+    cancel::{race, CancelExt, CancelToken},
+    fs::File,
+    net::{TcpListener, TcpStream, UdpSocket},
+    runtime::{sim, Runtime},
+};
+
+#[test]
+fn cancel_udp() {
+    tokio_iocp::start(async {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let token = CancelToken::new();
+        let recv = tokio_iocp::spawn({
+            let token = token.clone();
+            async move {
+                let res = socket
+                    .recv(Vec::with_capacity(64))
+                    .with_cancel(&token)
+                    .await;
+                (res, socket)
+            }
+        });
+        tokio::task::yield_now().await;
+        assert!(!recv.is_finished());
+
+        token.cancel();
+        assert!(token.is_cancelled());
+        let ((res, buf), socket) = recv.await.unwrap();
+        assert_eq!(res.unwrap_err().kind(), ErrorKind::Interrupted);
+        assert_eq!(buf.capacity(), 64);
+
+        // The socket is still usable.
+        let addr = socket.local_addr().unwrap();
+        let (res, _) = socket.send_to("hello", addr).await;
+        res.unwrap();
+        let (res, buf) = socket.recv(buf).await;
+        assert_eq!(res.unwrap(), 5);
+        assert_eq!(buf, b"hello");
+    });
+}
+
+#[test]
+fn cancel_tcp_from_thread() {
+    tokio_iocp::start(async {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (_tx, (rx, _)) = tokio::try_join!(TcpStream::connect(addr), listener.accept()).unwrap();
+
+        let token = CancelToken::new();
+        let thread = std::thread::spawn({
+            let token = token.clone();
+            move || {
+                std::thread::sleep(Duration::from_millis(10));
+                token.cancel();
+            }
+        });
+        let (res, buf) = rx.recv(Vec::with_capacity(64)).with_cancel(&token).await;
+        assert_eq!(res.unwrap_err().kind(), ErrorKind::Interrupted);
+        assert_eq!(buf.capacity(), 64);
+        thread.join().unwrap();
+    });
+}
+
+#[test]
+fn cancel_before_submit() {
+    tokio_iocp::start(async {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let token = CancelToken::new();
+        token.cancel();
+        // All operations of the future are cancelled.
+        let (res, buf) = async {
+            let (res, buf) = socket.recv(Vec::with_capacity(8)).await;
+            assert_eq!(res.unwrap_err().kind(), ErrorKind::Interrupted);
+            socket.recv(buf).await
+        }
+        .with_cancel(&token)
+        .await;
+        assert_eq!(res.unwrap_err().kind(), ErrorKind::Interrupted);
+        assert_eq!(buf.capacity(), 8);
+    });
+}
+
+#[test]
+fn nested_tokens() {
+    tokio_iocp::start(async {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let outer = CancelToken::new();
+        let inner = CancelToken::new();
+        let recv = tokio_iocp::spawn({
+            let outer = outer.clone();
+            let inner = inner.clone();
+            async move {
+                socket
+                    .recv(Vec::with_capacity(8))
+                    .with_cancel(&inner)
+                    .with_cancel(&outer)
+                    .await
+            }
+        });
+        tokio::task::yield_now().await;
+
+        outer.cancel();
+        let (res, _) = recv.await.unwrap();
+        assert_eq!(res.unwrap_err().kind(), ErrorKind::Interrupted);
+        assert!(!inner.is_cancelled());
+    });
+}
+
+#[test]
+fn cancel_with_sim() {
+    let runtime = Runtime::builder().sim(0).build().unwrap();
+    runtime.block_on(async {
+        let file = File::open("Cargo.toml").unwrap();
+        let token = CancelToken::new();
+        let read = tokio_iocp::spawn({
+            let token = token.clone();
+            async move {
+                file.read_at(Vec::with_capacity(8), 0)
+                    .with_cancel(&token)
+                    .await
+            }
+        });
+        tokio::task::yield_now().await;
+
+        token.cancel();
+        tokio::task::yield_now().await;
+        let op = sim::pick().unwrap();
+        assert!(op.is_cancelled());
+        assert!(!read.is_finished());
+
+        sim::complete(op.id(), Err(ErrorKind::TimedOut.into())).unwrap();
+        let (res, buf) = read.await.unwrap();
+        assert_eq!(res.unwrap_err().kind(), ErrorKind::Interrupted);
+        assert_eq!(buf.capacity(), 8);
+        // The cancelled operation is not buried.
+        assert_eq!(runtime.metrics().cancelling(), 0);
+    });
+}
+
+#[test]
+fn complete_before_cancel() {
+    let runtime = Runtime::builder().sim(0).build().unwrap();
+    runtime.block_on(async {
+        let file = File::open("Cargo.toml").unwrap();
+        let token = CancelToken::new();
+        let read = tokio_iocp::spawn({
+            let token = token.clone();
+            async move {
+                file.read_at(Vec::with_capacity(8), 0)
+                    .with_cancel(&token)
+                    .await
+            }
+        });
+        tokio::task::yield_now().await;
+
+        token.cancel();
+        tokio::task::yield_now().await;
+        // The operation completes anyway, and the result is kept.
+        sim::complete(sim::pick().unwrap().id(), Ok(8)).unwrap();
+        let (res, buf) = read.await.unwrap();
+        assert_eq!(res.unwrap(), 8);
+        assert_eq!(buf.len(), 8);
+    });
+}
+
+#[test]
+fn timeout_retry() {
+    tokio_iocp::start(async {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (tx, (rx, _)) = tokio::try_join!(TcpStream::connect(addr), listener.accept()).unwrap();
+
+        let (res, buf) = rx
+            .recv(Vec::with_capacity(64))
+            .timeout(Duration::from_millis(10))
+            .await;
+        assert_eq!(res.unwrap_err().kind(), ErrorKind::TimedOut);
+        assert_eq!(buf.capacity(), 64);
+
+        // Retry with the same buffer.
+        tx.send("hello").await.0.unwrap();
+        let (res, buf) = rx.recv(buf).timeout(Duration::from_secs(10)).await;
+        assert_eq!(res.unwrap(), 5);
+        assert_eq!(buf, b"hello");
+    });
+}
+
+#[test]
+fn timeout_with_sim() {
+    let runtime = Runtime::builder().sim(0).build().unwrap();
+    runtime.block_on(async {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let recv = tokio_iocp::spawn(async move {
+            socket
+                .recv(Vec::with_capacity(8))
+                .timeout(Duration::from_millis(10))
+                .await
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        // The future waits for the cancellation to complete.
+        let op = sim::pick().unwrap();
+        assert!(op.is_cancelled());
+        assert!(!recv.is_finished());
+
+        sim::complete(op.id(), Err(ErrorKind::Interrupted.into())).unwrap();
+        let (res, buf) = recv.await.unwrap();
+        assert_eq!(res.unwrap_err().kind(), ErrorKind::TimedOut);
+        assert_eq!(buf.capacity(), 8);
+    });
+}
+
+#[test]
+fn race_streams() {
+    tokio_iocp::start(async {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (tx1, (rx1, _)) =
+            tokio::try_join!(TcpStream::connect(addr), listener.accept()).unwrap();
+        let (_tx2, (rx2, _)) =
+            tokio::try_join!(TcpStream::connect(addr), listener.accept()).unwrap();
+
+        let sender = tokio_iocp::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            tx1.send("hello").await.0.unwrap();
+            tx1
+        });
+        let res = race([
+            rx2.recv(Vec::with_capacity(16)),
+            rx1.recv(Vec::with_capacity(16)),
+        ])
+        .await;
+        assert_eq!(res.index, 1);
+        assert_eq!(res.winner.0.unwrap(), 5);
+        assert_eq!(res.winner.1, b"hello");
+        assert_eq!(res.losers.len(), 1);
+        let (loser, _) = &res.losers[0];
+        assert_eq!(loser.as_ref().unwrap_err().kind(), ErrorKind::Interrupted);
+        sender.await.unwrap();
+    });
+}
+
+#[test]
+fn race_with_sim() {
+    let runtime = Runtime::builder().sim(0).build().unwrap();
+    runtime.block_on(async {
+        let primary = File::open("Cargo.toml").unwrap();
+        let replica = File::open("Cargo.toml").unwrap();
+        let race = tokio_iocp::spawn(async move {
+            race([
+                primary.read_at(Vec::with_capacity(8), 0),
+                replica.read_at(Vec::with_capacity(16), 0),
+            ])
+            .await
+        });
+        tokio::task::yield_now().await;
+
+        let ops = sim::pending();
+        sim::complete(ops[1].id(), Ok(16)).unwrap();
+        tokio::task::yield_now().await;
+        // The winner waits for the loser to be cancelled.
+        let op = sim::pick().unwrap();
+        assert!(op.is_cancelled());
+        assert!(!race.is_finished());
+
+        sim::complete(op.id(), Err(ErrorKind::Other.into())).unwrap();
+        let res = race.await.unwrap();
+        assert_eq!(res.index, 1);
+        assert_eq!(res.winner.0.unwrap(), 16);
+        let (loser, buffer) = &res.losers[0];
+        assert_eq!(loser.as_ref().unwrap_err().kind(), ErrorKind::Interrupted);
+        assert_eq!(buffer.capacity(), 8);
+        assert_eq!(runtime.metrics().cancelling(), 0);
+    });
+}
+
+#[cfg(windows)]
+#[test]
+fn cancel_named_pipe() {
+    use tokio_iocp::net::named_pipe::{ClientOptions, ServerOptions};
+
+    const PIPE_NAME: &str = r"\\.\pipe\tokio-iocp-cancel";
+
+    tokio_iocp::start(async {
+        let server = ServerOptions::new().create(PIPE_NAME).unwrap();
+        let client = ClientOptions::new().open(PIPE_NAME).unwrap();
+        server.connect().await.unwrap();
+
+        let token = CancelToken::new();
+        let read = tokio_iocp::spawn({
+            let token = token.clone();
+            async move { server.read(Vec::with_capacity(8)).with_cancel(&token).await }
+        });
+        tokio::task::yield_now().await;
+
+        token.cancel();
+        let (res, buf) = read.await.unwrap();
+        assert_eq!(res.unwrap_err().kind(), ErrorKind::Interrupted);
+        assert_eq!(buf.capacity(), 8);
+        drop(client);
+    });
+}
@@ -0,0 +1,50 @@
+use futures_util::FutureExt;
+use std::net::Ipv4Addr;
+use tempfile::NamedTempFile;
+use tokio_iocp::{
+    fs::File,
+    net::UdpSocket,
+    runtime::{sim, Runtime},
+};
+
+#[test]
+fn close_file() {
+    tokio_iocp::start(async {
+        let temp = NamedTempFile::new().unwrap();
+        let file = File::create(&temp).unwrap();
+        let (res, _) = file.write_at("hello world", 0).await;
+        assert_eq!(res.unwrap(), 11);
+        file.close().await.unwrap();
+
+        assert_eq!(std::fs::read(&temp).unwrap(), b"hello world");
+    });
+}
+
+#[test]
+fn close_waits_for_dropped() {
+    let runtime = Runtime::builder().sim(0).build().unwrap();
+    runtime.block_on(async {
+        let temp = NamedTempFile::new().unwrap();
+        let file = File::create(&temp).unwrap();
+        // The future is polled once and dropped.
+        assert!(file.write_at("hello", 0).now_or_never().is_none());
+        let op = sim::pending().pop().unwrap();
+        assert!(op.is_cancelled());
+
+        let close = tokio_iocp::spawn(file.close());
+        tokio::task::yield_now().await;
+        assert!(!close.is_finished());
+
+        sim::complete(op.id(), Ok(5)).unwrap();
+        close.await.unwrap().unwrap();
+    });
+}
+
+#[test]
+fn close_socket_with_dropped() {
+    tokio_iocp::start(async {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        assert!(socket.recv(Vec::with_capacity(8)).now_or_never().is_none());
+        socket.close().await.unwrap();
+    });
+}
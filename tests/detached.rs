@@ -0,0 +1,127 @@
+use std::{io::ErrorKind, net::Ipv4Addr, rc::Rc, time::Duration};
+use tokio_iocp::{
+    fs::File,
+    net::{TcpListener, TcpStream, UdpSocket},
+    runtime::{sim, Runtime},
+};
+
+#[test]
+fn move_stream() {
+    let runtime = Runtime::builder().worker_threads(1).build().unwrap();
+    let handle = runtime.handle();
+    runtime.block_on(async {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (tx, (rx, _)) = tokio::try_join!(TcpStream::connect(addr), listener.accept()).unwrap();
+
+        let rx = rx.into_detached().unwrap();
+        let recv = handle.spawn_on(0, move || async move {
+            let rx = rx.attach().unwrap();
+            let (res, buf) = rx.recv(Vec::with_capacity(64)).await;
+            res.unwrap();
+            // Move it back.
+            (rx.into_detached().unwrap(), buf)
+        });
+        tx.send("hello").await.0.unwrap();
+        let (rx, buf) = recv.await.unwrap();
+        assert_eq!(buf, b"hello");
+
+        let rx = rx.attach().unwrap();
+        tx.send("world").await.0.unwrap();
+        let (res, buf) = rx.recv(buf).await;
+        res.unwrap();
+        assert_eq!(buf, b"helloworld");
+    });
+}
+
+#[test]
+fn not_attached() {
+    let runtime = Runtime::builder().worker_threads(1).build().unwrap();
+    let handle = runtime.handle();
+    runtime.block_on(async {
+        let file = File::open("Cargo.toml").unwrap();
+        // The file is attached to the driver of this thread.
+        let (res, buf) = handle
+            .spawn_on(0, move || async move {
+                file.read_at(Vec::with_capacity(64), 0).await
+            })
+            .await
+            .unwrap();
+        assert_eq!(res.unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert_eq!(buf.capacity(), 64);
+
+        // It could not be detached on another thread either.
+        let file = File::open("Cargo.toml").unwrap();
+        let err = handle
+            .spawn_on(0, move || async move { file.into_detached().unwrap_err() })
+            .await
+            .unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    });
+}
+
+#[test]
+fn detach_busy() {
+    let runtime = Runtime::builder().sim(0).build().unwrap();
+    runtime.block_on(async {
+        let socket = Rc::new(UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap());
+        let recv = tokio_iocp::spawn({
+            let socket = socket.clone();
+            async move { socket.recv(Vec::with_capacity(8)).await }
+        });
+        tokio::task::yield_now().await;
+        recv.abort();
+        recv.await.unwrap_err();
+
+        // The cancelled operation is not completed.
+        let socket = Rc::into_inner(socket).unwrap();
+        let err = socket.into_detached().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ResourceBusy);
+
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let op = tokio::time::timeout(
+            Duration::from_millis(10),
+            socket.recv(Vec::with_capacity(8)),
+        );
+        op.await.unwrap_err();
+        for op in sim::pending() {
+            sim::complete(op.id(), Ok(0)).unwrap();
+        }
+        runtime.drain_cancelled().await;
+        socket.into_detached().unwrap().attach().unwrap();
+    });
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn reuse_dropped_handle() {
+    use std::os::fd::AsRawFd;
+
+    let runtime = Runtime::builder().worker_threads(1).build().unwrap();
+    let handle = runtime.handle();
+    runtime.block_on(async {
+        // The value is usually reused by the next file opened in the process,
+        // unless it is taken by another test first.
+        for _ in 0..16 {
+            let file = File::open("Cargo.toml").unwrap();
+            let fd = file.as_raw_fd();
+            drop(file);
+
+            // A file with the same value is opened on the worker, and sent here
+            // without detaching.
+            let file = handle
+                .spawn_on(0, move || async move {
+                    let file = File::open("Cargo.toml").unwrap();
+                    (file.as_raw_fd() == fd).then_some(file)
+                })
+                .await
+                .unwrap();
+            if let Some(file) = file {
+                let (res, _) = file.read_at(Vec::with_capacity(64), 0).await;
+                assert_eq!(res.unwrap_err().kind(), ErrorKind::InvalidInput);
+                return;
+            }
+        }
+        panic!("the handle value is never reused");
+    });
+}
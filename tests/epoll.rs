@@ -0,0 +1,148 @@
+#![cfg(target_os = "linux")]
+
+use std::{
+    future::Future,
+    io::{prelude::*, ErrorKind},
+    net::Ipv4Addr,
+};
+use tempfile::NamedTempFile;
+use tokio_iocp::{
+    fs::File,
+    net::{TcpListener, TcpStream, UdpSocket},
+    runtime::{DriverKind, Runtime},
+};
+
+const HELLO: &[u8] = b"hello world...";
+
+/// Starts the runtime with the epoll driver.
+fn start<F: Future>(future: F) -> F::Output {
+    Runtime::builder()
+        .driver(DriverKind::Epoll)
+        .build()
+        .unwrap()
+        .block_on(future)
+}
+
+#[test]
+fn file_read_write() {
+    start(async {
+        let mut tempfile = NamedTempFile::new().unwrap();
+        tempfile.write_all(HELLO).unwrap();
+
+        let file = File::open(tempfile.path()).unwrap();
+        let (res, buf) = file.read_at(Vec::with_capacity(1024), 0).await;
+        assert_eq!(res.unwrap(), HELLO.len());
+        assert_eq!(buf, HELLO);
+
+        let tempfile = NamedTempFile::new().unwrap();
+        let file = File::create(tempfile.path()).unwrap();
+        file.write_at(HELLO, 0).await.0.unwrap();
+        assert_eq!(std::fs::read(tempfile.path()).unwrap(), HELLO);
+    });
+}
+
+#[test]
+fn tcp_echo() {
+    start(async {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let (tx, (rx, _)) = tokio::try_join!(TcpStream::connect(addr), listener.accept()).unwrap();
+
+        // The receive would block until the data is sent.
+        let recv = rx.recv(Vec::with_capacity(64));
+        let send = tx.send(HELLO);
+        let ((res, buf), (sent, _)) = tokio::join!(recv, send);
+        assert_eq!(sent.unwrap(), HELLO.len());
+        assert_eq!(res.unwrap(), HELLO.len());
+        assert_eq!(buf, HELLO);
+    });
+}
+
+#[test]
+fn udp_send_recv() {
+    start(async {
+        let rx = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let tx = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let rx_addr = rx.local_addr().unwrap();
+        let tx_addr = tx.local_addr().unwrap();
+
+        let recv = rx.recv_from(Vec::with_capacity(64));
+        let send = tx.send_to(HELLO, rx_addr);
+        let ((res, buf), (sent, _)) = tokio::join!(recv, send);
+        sent.unwrap();
+        let (n, addr) = res.unwrap();
+        assert_eq!(n, HELLO.len());
+        assert_eq!(addr, tx_addr);
+        assert_eq!(buf, HELLO);
+    });
+}
+
+#[test]
+fn cancel_recv() {
+    start(async {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let (tx, (rx, _)) = tokio::try_join!(TcpStream::connect(addr), listener.accept()).unwrap();
+
+        // Nothing to receive, so the operation is queued and then cancelled.
+        poll_once(rx.recv(Vec::with_capacity(64))).await;
+
+        tx.send(HELLO).await.0.unwrap();
+        let (res, buf) = rx.recv(Vec::with_capacity(64)).await;
+        assert_eq!(res.unwrap(), HELLO.len());
+        assert_eq!(buf, HELLO);
+    });
+}
+
+async fn poll_once(future: impl std::future::Future) {
+    use std::{future::poll_fn, task::Poll};
+    use tokio::pin;
+
+    pin!(future);
+
+    poll_fn(|cx| {
+        assert!(future.as_mut().poll(cx).is_pending());
+        Poll::Ready(())
+    })
+    .await;
+}
+
+#[test]
+fn replace_driver() {
+    let socket = start(async { UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap() });
+
+    // The driver could not be replaced while the socket is attached.
+    let res = Runtime::builder().driver(DriverKind::IoUring).build();
+    assert_eq!(res.unwrap_err().kind(), ErrorKind::ResourceBusy);
+
+    start(async move {
+        let addr = socket.local_addr().unwrap();
+        let (sent, _) = socket.send_to(HELLO, addr).await;
+        sent.unwrap();
+        let (res, buf) = socket.recv(Vec::with_capacity(64)).await;
+        assert_eq!(res.unwrap(), HELLO.len());
+        assert_eq!(buf, HELLO);
+    });
+}
+
+#[test]
+fn restore_blocking() {
+    use std::os::fd::{AsRawFd, FromRawFd, IntoRawFd, OwnedFd};
+
+    fn nonblocking(fd: i32) -> bool {
+        let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+        assert!(flags >= 0);
+        flags & libc::O_NONBLOCK != 0
+    }
+
+    let fd = start(async {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        assert!(nonblocking(socket.as_raw_fd()));
+        socket.into_raw_fd()
+    });
+    // The flag is shared with the duplicates, and restored when detached.
+    assert!(!nonblocking(fd));
+    drop(unsafe { OwnedFd::from_raw_fd(fd) });
+}
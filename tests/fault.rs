@@ -0,0 +1,103 @@
+use std::{
+    io::ErrorKind,
+    net::Ipv4Addr,
+    time::{Duration, Instant},
+};
+use tempfile::NamedTempFile;
+use tokio_iocp::{
+    fs::File,
+    net::{TcpListener, TcpStream},
+    runtime::{
+        fault::{Fault, FaultPolicy, FaultTarget, OpKind},
+        Runtime,
+    },
+};
+
+const HELLO: &[u8] = b"hello world...";
+
+#[test]
+fn fail_every_nth() {
+    let tempfile = NamedTempFile::new().unwrap();
+    let policy = FaultPolicy::new().with(Fault::error(ErrorKind::Other).on(OpKind::Write).every(3));
+    let runtime = Runtime::builder().fault_policy(policy).build().unwrap();
+    runtime.block_on(async {
+        let file = File::create(tempfile.path()).unwrap();
+        for i in 1..=6 {
+            let (res, buf) = file.write_at(HELLO, 0).await;
+            // The buffer is returned on failure.
+            assert_eq!(buf, HELLO);
+            if i % 3 == 0 {
+                assert_eq!(res.unwrap_err().kind(), ErrorKind::Other);
+            } else {
+                assert_eq!(res.unwrap(), HELLO.len());
+            }
+        }
+        // Reads are not affected.
+        let file = File::open(tempfile.path()).unwrap();
+        let (res, _) = file.read_at(Vec::with_capacity(64), 0).await;
+        assert_eq!(res.unwrap(), HELLO.len());
+    });
+}
+
+#[test]
+fn cap_transfers() {
+    let policy = FaultPolicy::new().with(Fault::cap(7));
+    let runtime = Runtime::builder().fault_policy(policy).build().unwrap();
+    runtime.block_on(async {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (tx, (rx, _)) = tokio::try_join!(TcpStream::connect(addr), listener.accept()).unwrap();
+
+        let (res, _) = tx.send(HELLO).await;
+        assert_eq!(res.unwrap(), 7);
+        let (res, _) = tx.send_vectored(vec![&HELLO[7..], &HELLO[7..]]).await;
+        assert_eq!(res.unwrap(), 7);
+
+        let (res, buf) = rx.recv(Vec::with_capacity(64)).await;
+        assert_eq!(res.unwrap(), 7);
+        assert_eq!(buf, &HELLO[..7]);
+        let (res, buf) = rx.recv(Vec::with_capacity(64)).await;
+        assert_eq!(res.unwrap(), 7);
+        assert_eq!(buf, &HELLO[7..]);
+    });
+}
+
+#[test]
+fn delay_completions() {
+    let policy = FaultPolicy::new().with(Fault::delay(Duration::from_millis(50)));
+    let runtime = Runtime::builder().fault_policy(policy).build().unwrap();
+    runtime.block_on(async {
+        let file = File::open("Cargo.toml").unwrap();
+        let start = Instant::now();
+        let (res, _) = file.read_at(Vec::with_capacity(64), 0).await;
+        res.unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(50));
+    });
+}
+
+#[test]
+fn handle_policy() {
+    let policy = FaultPolicy::new().with(Fault::error(ErrorKind::Other));
+    let runtime = Runtime::builder().fault_policy(policy).build().unwrap();
+    runtime.block_on(async {
+        let file = File::open("Cargo.toml").unwrap();
+        let other = File::open("Cargo.toml").unwrap();
+
+        // An empty policy overrides the policy of the runtime.
+        file.set_fault_policy(Some(FaultPolicy::new()));
+        let (res, _) = file.read_at(Vec::with_capacity(64), 0).await;
+        res.unwrap();
+        let (res, _) = other.read_at(Vec::with_capacity(64), 0).await;
+        assert_eq!(res.unwrap_err().kind(), ErrorKind::Other);
+
+        file.set_fault_policy(Some(
+            FaultPolicy::new().with(Fault::error(ErrorKind::TimedOut)),
+        ));
+        let (res, _) = file.read_at(Vec::with_capacity(64), 0).await;
+        assert_eq!(res.unwrap_err().kind(), ErrorKind::TimedOut);
+
+        file.set_fault_policy(None);
+        let (res, _) = file.read_at(Vec::with_capacity(64), 0).await;
+        assert_eq!(res.unwrap_err().kind(), ErrorKind::Other);
+    });
+}
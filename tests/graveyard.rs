@@ -0,0 +1,109 @@
+use std::{
+    net::Ipv4Addr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio_iocp::{
+    net::UdpSocket,
+    runtime::{sim, Builder, Runtime},
+};
+
+/// The capacities of the buried buffers.
+type Pool = Arc<Mutex<Vec<Vec<usize>>>>;
+
+fn builder(pool: &Pool) -> Builder {
+    let pool = pool.clone();
+    let mut builder = Runtime::builder();
+    builder.graveyard(move |buffer| {
+        let capacities = match buffer.downcast::<Vec<u8>>() {
+            Ok(buffer) => vec![buffer.capacity()],
+            Err(buffer) => buffer
+                .downcast::<Vec<Vec<u8>>>()
+                .unwrap()
+                .iter()
+                .map(Vec::capacity)
+                .collect(),
+        };
+        pool.lock().unwrap().push(capacities);
+    });
+    builder
+}
+
+/// Drops a receiving future after it is submitted.
+async fn cancel_recv(socket: &UdpSocket) {
+    tokio::time::timeout(
+        Duration::from_millis(10),
+        socket.recv(Vec::with_capacity(64)),
+    )
+    .await
+    .unwrap_err();
+}
+
+#[test]
+fn bury_cancelled() {
+    let pool = Pool::default();
+    let runtime = builder(&pool).build().unwrap();
+    runtime.block_on(async {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        cancel_recv(&socket).await;
+        // A completed operation is not buried.
+        let addr = socket.local_addr().unwrap();
+        let (res, _) = socket.send_to(vec![0; 8], addr).await;
+        res.unwrap();
+
+        runtime.drain_cancelled().await;
+        assert_eq!(runtime.metrics().cancelling(), 0);
+    });
+
+    assert_eq!(*pool.lock().unwrap(), [[64]]);
+}
+
+#[test]
+fn drain_with_sim() {
+    let pool = Pool::default();
+    let runtime = builder(&pool).sim(0).build().unwrap();
+    runtime.block_on(async {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let recv = tokio_iocp::spawn(async move {
+            socket
+                .recv_vectored(vec![Vec::with_capacity(8), Vec::with_capacity(16)])
+                .await
+        });
+        tokio::task::yield_now().await;
+        recv.abort();
+        recv.await.unwrap_err();
+        assert_eq!(runtime.metrics().cancelling(), 1);
+
+        let drain = tokio::time::timeout(Duration::from_millis(10), runtime.drain_cancelled());
+        drain.await.unwrap_err();
+        assert!(pool.lock().unwrap().is_empty());
+
+        let op = sim::pick().unwrap();
+        assert!(op.is_cancelled());
+        sim::complete(op.id(), Ok(0)).unwrap();
+        runtime.drain_cancelled().await;
+        assert_eq!(runtime.metrics().cancelling(), 0);
+    });
+
+    assert_eq!(*pool.lock().unwrap(), [[8, 16]]);
+}
+
+#[test]
+fn drain_workers() {
+    let pool = Pool::default();
+    let runtime = builder(&pool).worker_threads(2).build().unwrap();
+    let handle = runtime.handle();
+    runtime.block_on(async {
+        for worker in 0..2 {
+            handle
+                .spawn_on(worker, || async {
+                    let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+                    cancel_recv(&socket).await;
+                })
+                .await
+                .unwrap();
+        }
+        runtime.drain_cancelled().await;
+    });
+    assert_eq!(*pool.lock().unwrap(), [[64], [64]]);
+}
@@ -0,0 +1,101 @@
+use std::{io::ErrorKind, rc::Rc};
+use tokio_iocp::{
+    cancel::{CancelExt, CancelToken},
+    fs::File,
+    runtime::{sim, Runtime},
+};
+
+#[test]
+fn queued_in_order() {
+    let runtime = Runtime::builder().sim(0).max_in_flight(2).build().unwrap();
+    runtime.block_on(async {
+        let file = Rc::new(File::open("Cargo.toml").unwrap());
+        let writes = (0..5)
+            .map(|i| {
+                let file = file.clone();
+                tokio_iocp::spawn(async move { file.write_at(i.to_string(), 0).await })
+            })
+            .collect::<Vec<_>>();
+        tokio::task::yield_now().await;
+        assert_eq!(runtime.metrics().queued(), 3);
+
+        for i in 0..5 {
+            let ops = sim::pending();
+            assert_eq!(ops.len(), 2.min(5 - i));
+            assert_eq!(sim::data(ops[0].id()).unwrap(), i.to_string().as_bytes());
+            sim::complete(ops[0].id(), Ok(1)).unwrap();
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(runtime.metrics().queued(), 0);
+        for write in writes {
+            let (res, _) = write.await.unwrap();
+            assert_eq!(res.unwrap(), 1);
+        }
+    });
+}
+
+#[test]
+fn drop_queued() {
+    let runtime = Runtime::builder().sim(0).max_in_flight(1).build().unwrap();
+    runtime.block_on(async {
+        let file = Rc::new(File::open("Cargo.toml").unwrap());
+        let first = tokio_iocp::spawn({
+            let file = file.clone();
+            async move { file.write_at("first", 0).await }
+        });
+        let dropped = tokio_iocp::spawn({
+            let file = file.clone();
+            async move { file.write_at("dropped", 0).await }
+        });
+        let token = CancelToken::new();
+        let cancelled = tokio_iocp::spawn({
+            let file = file.clone();
+            let token = token.clone();
+            async move { file.write_at("cancelled", 0).with_cancel(&token).await }
+        });
+        let last = tokio_iocp::spawn({
+            let file = file.clone();
+            async move { file.write_at("last", 0).await }
+        });
+        tokio::task::yield_now().await;
+        assert_eq!(runtime.metrics().queued(), 3);
+
+        dropped.abort();
+        token.cancel();
+        let (res, buf) = cancelled.await.unwrap();
+        assert_eq!(res.unwrap_err().kind(), ErrorKind::Interrupted);
+        assert_eq!(buf, "cancelled");
+        assert_eq!(runtime.metrics().queued(), 1);
+
+        let op = sim::pending().pop().unwrap();
+        sim::complete(op.id(), Ok(5)).unwrap();
+        assert_eq!(first.await.unwrap().0.unwrap(), 5);
+
+        // The dropped operations are skipped.
+        tokio::task::yield_now().await;
+        let ops = sim::pending();
+        assert_eq!(ops.len(), 1);
+        assert_eq!(sim::data(ops[0].id()).unwrap(), b"last");
+        sim::complete(ops[0].id(), Ok(4)).unwrap();
+        assert_eq!(last.await.unwrap().0.unwrap(), 4);
+        assert_eq!(runtime.metrics().queued(), 0);
+    });
+}
+
+#[test]
+fn limit_real_driver() {
+    let runtime = Runtime::builder().max_in_flight(4).build().unwrap();
+    runtime.block_on(async {
+        let file = File::open("Cargo.toml").unwrap();
+        let reads = (0..64).map(|_| file.read_at(Vec::with_capacity(64), 0));
+        for (res, buf) in futures_util::future::join_all(reads).await {
+            assert_eq!(res.unwrap(), 64);
+            assert_eq!(buf.len(), 64);
+        }
+    });
+    let metrics = runtime.metrics();
+    assert_eq!(metrics.queued(), 0);
+    assert_eq!(metrics.in_flight(), 0);
+
+    assert!(Runtime::builder().max_in_flight(0).build().is_err());
+}
@@ -0,0 +1,114 @@
+use std::{io::ErrorKind, net::Ipv4Addr, time::Duration};
+use tempfile::NamedTempFile;
+use tokio_iocp::{
+    fs::File,
+    net::UdpSocket,
+    runtime::{
+        fault::{Fault, FaultPolicy},
+        metrics::OpKind,
+        sim, Runtime,
+    },
+};
+
+#[test]
+fn count_ops() {
+    let tempfile = NamedTempFile::new().unwrap();
+    let policy = FaultPolicy::new().with(Fault::error(ErrorKind::Other).on(OpKind::Write).every(2));
+    let runtime = Runtime::builder().fault_policy(policy).build().unwrap();
+    runtime.block_on(async {
+        let file = File::create(tempfile.path()).unwrap();
+        file.write_at("hello", 0).await.0.unwrap();
+        file.write_at("hello", 0).await.0.unwrap_err();
+
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        tokio::time::timeout(
+            Duration::from_millis(10),
+            socket.recv(Vec::with_capacity(8)),
+        )
+        .await
+        .unwrap_err();
+    });
+
+    let metrics = runtime.metrics();
+    let write = metrics.op(OpKind::Write);
+    assert_eq!(write.submitted(), 2);
+    assert_eq!(write.completed(), 1);
+    assert_eq!(write.failed(), 1);
+    assert_eq!(write.cancelled(), 0);
+    assert_eq!(write.bytes(), 5);
+    assert_eq!(write.latency().count(), 2);
+    assert!(write.latency().quantile(0.5).is_some());
+
+    let recv = metrics.op(OpKind::Recv);
+    assert_eq!(recv.submitted(), 1);
+    assert_eq!(recv.completed(), 0);
+    assert_eq!(recv.cancelled(), 1);
+    assert_eq!(recv.latency().count(), 0);
+
+    assert_eq!(metrics.op(OpKind::Read).submitted(), 0);
+    assert_eq!(metrics.bytes_transferred(), 5);
+    assert!(metrics.completions_per_poll().count() > 0);
+    // The polls without completions are only counted as empty.
+    assert_eq!(metrics.completions_per_poll().buckets()[0], 0);
+    assert!(metrics.empty_polls() > 0);
+}
+
+#[test]
+fn in_flight() {
+    let runtime = Runtime::builder().sim(0).build().unwrap();
+    runtime.block_on(async {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let recv = tokio_iocp::spawn(async move { socket.recv(Vec::with_capacity(8)).await });
+        tokio::task::yield_now().await;
+        assert_eq!(runtime.metrics().in_flight(), 1);
+
+        // The cancelled operation is still held by the driver.
+        recv.abort();
+        recv.await.unwrap_err();
+        let metrics = runtime.metrics();
+        assert_eq!(metrics.op(OpKind::Recv).cancelled(), 1);
+        assert_eq!(metrics.in_flight(), 1);
+
+        let op = sim::pick().unwrap();
+        sim::complete(op.id(), Ok(0)).unwrap();
+        assert_eq!(runtime.metrics().in_flight(), 0);
+    });
+}
+
+#[test]
+fn drop_completed() {
+    let runtime = Runtime::builder().sim(0).build().unwrap();
+    runtime.block_on(async {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let mut recv = Box::pin(socket.recv(Vec::with_capacity(8)));
+        assert!(futures_util::poll!(&mut recv).is_pending());
+
+        // The result is ready, but not taken by the future.
+        let op = sim::pick().unwrap();
+        sim::complete(op.id(), Ok(0)).unwrap();
+        drop(recv);
+    });
+    assert_eq!(runtime.metrics().op(OpKind::Recv).cancelled(), 0);
+}
+
+#[test]
+fn sum_workers() {
+    let runtime = Runtime::builder().worker_threads(2).build().unwrap();
+    let handle = runtime.handle();
+    runtime.block_on(async move {
+        for worker in 0..2 {
+            handle
+                .spawn_on(worker, || async {
+                    let file = File::open("Cargo.toml").unwrap();
+                    file.read_at(Vec::with_capacity(8), 0).await.0.unwrap();
+                })
+                .await
+                .unwrap();
+        }
+    });
+
+    let read = runtime.metrics().op(OpKind::Read).clone();
+    assert_eq!(read.submitted(), 2);
+    assert_eq!(read.completed(), 2);
+    assert_eq!(read.bytes(), 16);
+}
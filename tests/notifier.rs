@@ -0,0 +1,86 @@
+use std::{
+    io::ErrorKind,
+    time::{Duration, Instant},
+};
+use tokio_iocp::runtime::{Notifier, ParkPolicy, Runtime};
+
+#[test]
+fn notify_from_thread() {
+    tokio_iocp::start(async {
+        let notifier = Notifier::new();
+        let remote = notifier.clone();
+        let thread = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(10));
+            remote.notify_with(1).unwrap();
+            remote.notify().unwrap();
+        });
+        assert_eq!(notifier.notified().await.unwrap(), Some(1));
+        assert_eq!(notifier.notified().await.unwrap(), None);
+        thread.join().unwrap();
+    });
+}
+
+#[test]
+fn queued_in_order() {
+    tokio_iocp::start(async {
+        let notifier = Notifier::new();
+        for i in 0..100 {
+            notifier.notify_with(i).unwrap();
+        }
+        for i in 0..100 {
+            assert_eq!(notifier.notified().await.unwrap(), Some(i));
+        }
+    });
+}
+
+#[test]
+fn wakes_parked_thread() {
+    let runtime = Runtime::builder()
+        .enable_io(false)
+        .park_policy(ParkPolicy::Timeout(Duration::from_secs(10)))
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        let notifier = Notifier::new();
+        let remote = notifier.clone();
+        let start = Instant::now();
+        let thread = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(10));
+            remote.notify().unwrap();
+        });
+        notifier.notified().await.unwrap();
+        assert!(start.elapsed() < Duration::from_secs(5));
+        thread.join().unwrap();
+    });
+}
+
+#[test]
+fn wrong_thread() {
+    let runtime = Runtime::builder().worker_threads(1).build().unwrap();
+    let handle = runtime.handle();
+    runtime.block_on(async {
+        let notifier = Notifier::new();
+        notifier.notify().unwrap();
+        let err = handle
+            .spawn_on(0, move || async move { notifier.notified().await })
+            .await
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    });
+}
+
+#[test]
+fn notify_with_sim() {
+    let runtime = Runtime::builder().sim(0).build().unwrap();
+    runtime.block_on(async {
+        let notifier = Notifier::new();
+        let remote = notifier.clone();
+        let thread = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(10));
+            remote.notify_with(7).unwrap();
+        });
+        assert_eq!(notifier.notified().await.unwrap(), Some(7));
+        thread.join().unwrap();
+    });
+}
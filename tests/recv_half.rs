@@ -0,0 +1,110 @@
+use futures_util::future::Either;
+use std::{net::Ipv4Addr, time::Duration};
+use tokio_iocp::{
+    net::{TcpListener, TcpStream},
+    runtime::{metrics::OpKind, Runtime},
+};
+
+async fn connect() -> (TcpStream, TcpStream) {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (tx, (rx, _)) = tokio::try_join!(TcpStream::connect(addr), listener.accept()).unwrap();
+    (tx, rx)
+}
+
+#[test]
+fn select_loop() {
+    tokio_iocp::start(async {
+        let (tx, rx) = connect().await;
+        let sender = tokio_iocp::spawn(async move {
+            for i in 0..4u8 {
+                tokio::time::sleep(Duration::from_millis(15)).await;
+                tx.send(vec![i; 4]).await.0.unwrap();
+            }
+        });
+
+        let mut rx = rx.recv_half();
+        let mut received = vec![];
+        let mut ticks = 0;
+        let mut ticker = tokio::time::interval(Duration::from_millis(2));
+        while received.len() < 16 {
+            let recv = if rx.is_pending() {
+                Either::Left(rx.resume())
+            } else {
+                Either::Right(rx.recv(Vec::with_capacity(64)))
+            };
+            tokio::select! {
+                _ = ticker.tick() => ticks += 1,
+                (res, buf) = recv => {
+                    res.unwrap();
+                    received.extend(buf);
+                }
+            }
+        }
+        sender.await.unwrap();
+
+        // The timer wins many times, and no data is lost.
+        assert!(ticks > 4);
+        assert_eq!(received, [[0; 4], [1; 4], [2; 4], [3; 4]].concat());
+        assert!(!rx.is_pending());
+    });
+}
+
+#[test]
+fn drop_half() {
+    let runtime = Runtime::new().unwrap();
+    runtime.block_on(async {
+        let (_tx, rx) = connect().await;
+        let mut half = rx.recv_half();
+        tokio::time::timeout(Duration::from_millis(10), half.recv(Vec::with_capacity(8)))
+            .await
+            .unwrap_err();
+        // The operation is kept until the half is dropped.
+        assert!(half.is_pending());
+        assert_eq!(runtime.metrics().op(OpKind::Recv).cancelled(), 0);
+
+        drop(half);
+        assert_eq!(runtime.metrics().op(OpKind::Recv).cancelled(), 1);
+    });
+}
+
+#[test]
+fn resume_pending() {
+    tokio_iocp::start(async {
+        let (tx, rx) = connect().await;
+        let mut half = rx.recv_half();
+        tokio::time::timeout(Duration::from_millis(10), half.recv(Vec::with_capacity(8)))
+            .await
+            .unwrap_err();
+        assert!(half.is_pending());
+
+        tx.send("hello").await.0.unwrap();
+        let (res, buf) = half.resume().await;
+        assert_eq!(res.unwrap(), 5);
+        assert_eq!(buf, b"hello");
+        assert!(!half.is_pending());
+    });
+}
+
+#[test]
+#[should_panic = "no operation is pending"]
+fn resume_without_pending() {
+    tokio_iocp::start(async {
+        let (_tx, rx) = connect().await;
+        drop(rx.recv_half::<Vec<u8>>().resume());
+    });
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic = "an operation is pending"]
+fn recv_with_pending() {
+    tokio_iocp::start(async {
+        let (_tx, rx) = connect().await;
+        let mut half = rx.recv_half();
+        tokio::time::timeout(Duration::from_millis(10), half.recv(Vec::with_capacity(8)))
+            .await
+            .unwrap_err();
+        drop(half.recv(Vec::with_capacity(8)));
+    });
+}
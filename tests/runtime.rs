@@ -35,3 +35,215 @@ fn spawn_a_task() {
         assert_eq!(2, *cell.borrow());
     });
 }
+
+#[test]
+fn spawn_on_workers() {
+    use tokio_iocp::{fs::File, runtime::Runtime};
+
+    let runtime = Runtime::builder().worker_threads(2).build().unwrap();
+    let handle = runtime.handle();
+    assert_eq!(handle.worker_threads(), 2);
+
+    runtime.block_on(async move {
+        let names = futures_util::future::join_all((0..4).map(|_| {
+            handle.spawn_pinned(|| async {
+                // The driver of the worker is used.
+                let file = File::open("Cargo.toml").unwrap();
+                let (res, _) = file.read_at(Vec::with_capacity(64), 0).await;
+                res.unwrap();
+                std::thread::current().name().unwrap().to_string()
+            })
+        }))
+        .await;
+        let names = names.into_iter().map(Result::unwrap).collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "tokio-iocp-worker-0",
+                "tokio-iocp-worker-1",
+                "tokio-iocp-worker-0",
+                "tokio-iocp-worker-1"
+            ]
+        );
+
+        let res = handle
+            .spawn_on(1, || async {
+                tokio_iocp::spawn(async { 42 }).await.unwrap()
+            })
+            .await
+            .unwrap();
+        assert_eq!(res, 42);
+
+        let err = handle
+            .spawn_on(0, || async { panic!("worker task") })
+            .await
+            .unwrap_err();
+        assert!(err.is_panic());
+    });
+}
+
+#[test]
+fn worker_affinity() {
+    use tokio_iocp::runtime::Runtime;
+
+    let runtime = Runtime::builder()
+        .worker_threads(1)
+        .worker_affinity([0])
+        .build()
+        .unwrap();
+    let handle = runtime.handle();
+    runtime.block_on(async move {
+        handle.spawn_on(0, || async {}).await.unwrap();
+    });
+}
+
+#[test]
+fn builder_options() {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+    use tokio_iocp::{
+        fs::File,
+        runtime::{ParkPolicy, Runtime},
+    };
+
+    let started = Arc::new(AtomicUsize::new(0));
+    let stopped = Arc::new(AtomicUsize::new(0));
+    let runtime = Runtime::builder()
+        .worker_threads(2)
+        .thread_name("custom")
+        .event_batch_size(1)
+        .completion_budget(2)
+        .park_policy(ParkPolicy::Timeout(Duration::from_millis(1)))
+        .enable_io(false)
+        .on_thread_start({
+            let started = started.clone();
+            move || {
+                started.fetch_add(1, Ordering::SeqCst);
+            }
+        })
+        .on_thread_stop({
+            let stopped = stopped.clone();
+            move || {
+                stopped.fetch_add(1, Ordering::SeqCst);
+            }
+        })
+        .build()
+        .unwrap();
+    assert_eq!(started.load(Ordering::SeqCst), 2);
+
+    let handle = runtime.handle();
+    runtime.block_on(async move {
+        let name = handle
+            .spawn_on(1, || async {
+                let file = File::open("Cargo.toml").unwrap();
+                let reads = (0..4).map(|_| file.read_at(Vec::with_capacity(64), 0));
+                for (res, _) in futures_util::future::join_all(reads).await {
+                    res.unwrap();
+                }
+                tokio::time::sleep(Duration::from_millis(1)).await;
+                std::thread::current().name().unwrap().to_string()
+            })
+            .await
+            .unwrap();
+        assert_eq!(name, "custom-1");
+    });
+
+    drop(runtime);
+    // The threads of the blocking pool are also counted, e.g., reading files
+    // with the epoll driver.
+    assert_eq!(
+        stopped.load(Ordering::SeqCst),
+        started.load(Ordering::SeqCst)
+    );
+
+    assert!(Runtime::builder().event_batch_size(0).build().is_err());
+    assert!(Runtime::builder().completion_budget(0).build().is_err());
+    // Nothing waits for the driver on Linux.
+    #[cfg(target_os = "linux")]
+    assert!(Runtime::builder().enable_io(false).build().is_err());
+}
+
+#[test]
+fn idle_with_pending_io() {
+    use std::time::Duration;
+    use tokio_iocp::net::{TcpListener, TcpStream};
+
+    tokio_iocp::start(async {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (_tx, (rx, _)) = tokio::try_join!(TcpStream::connect(addr), listener.accept()).unwrap();
+
+        // Nothing to receive, so the thread should park until the timeout.
+        let start = thread_cpu_time();
+        let recv = rx.recv(Vec::with_capacity(64));
+        tokio::time::timeout(Duration::from_millis(500), recv)
+            .await
+            .unwrap_err();
+        let used = thread_cpu_time() - start;
+        assert!(used < Duration::from_millis(50), "CPU time used: {used:?}");
+    });
+}
+
+#[cfg(target_os = "linux")]
+fn thread_cpu_time() -> std::time::Duration {
+    let mut time: libc::timespec = unsafe { std::mem::zeroed() };
+    assert_eq!(
+        unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut time) },
+        0
+    );
+    std::time::Duration::new(time.tv_sec as _, time.tv_nsec as _)
+}
+
+#[cfg(windows)]
+fn thread_cpu_time() -> std::time::Duration {
+    use windows_sys::Win32::{
+        Foundation::FILETIME,
+        System::Threading::{GetCurrentThread, GetThreadTimes},
+    };
+
+    let mut times: [FILETIME; 4] = unsafe { std::mem::zeroed() };
+    let [creation, exit, kernel, user] = &mut times;
+    assert_ne!(
+        unsafe { GetThreadTimes(GetCurrentThread(), creation, exit, kernel, user) },
+        0
+    );
+    let ticks = |time: &FILETIME| (time.dwHighDateTime as u64) << 32 | time.dwLowDateTime as u64;
+    // The times are in 100-nanosecond units.
+    std::time::Duration::from_nanos((ticks(kernel) + ticks(user)) * 100)
+}
+
+#[test]
+fn wake_blocked_driver() {
+    use std::time::{Duration, Instant};
+    use tokio_iocp::runtime::{ParkPolicy, Runtime};
+
+    // The threads block on the driver when they park, and are woken by the
+    // tasks woken or spawned by other threads.
+    let runtime = Runtime::builder()
+        .worker_threads(1)
+        .enable_io(false)
+        .park_policy(ParkPolicy::Timeout(Duration::from_secs(10)))
+        .build()
+        .unwrap();
+    let handle = runtime.handle();
+    runtime.block_on(async {
+        let start = Instant::now();
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let thread = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(10));
+            tx.send(()).unwrap();
+        });
+        tokio_iocp::spawn(rx).await.unwrap().unwrap();
+        thread.join().unwrap();
+
+        // Let the worker park first.
+        std::thread::sleep(Duration::from_millis(10));
+        handle.spawn_on(0, || async {}).await.unwrap();
+        assert!(start.elapsed() < Duration::from_secs(5));
+    });
+}
@@ -0,0 +1,113 @@
+use std::{net::Ipv4Addr, sync::Arc, time::Duration};
+use tokio_iocp::{buf::*, net::UdpSocket, runtime::Runtime};
+
+/// A buffer counting its references.
+#[derive(Debug)]
+struct TrackedBuf {
+    data: Vec<u8>,
+    _ref_cnt: Arc<()>,
+}
+
+impl TrackedBuf {
+    fn new(ref_cnt: &Arc<()>) -> Self {
+        Self {
+            data: Vec::with_capacity(8),
+            _ref_cnt: ref_cnt.clone(),
+        }
+    }
+}
+
+unsafe impl IoBuf for TrackedBuf {
+    fn as_buf_ptr(&self) -> *const u8 {
+        self.data.as_buf_ptr()
+    }
+
+    fn buf_len(&self) -> usize {
+        self.data.buf_len()
+    }
+
+    fn buf_capacity(&self) -> usize {
+        self.data.buf_capacity()
+    }
+}
+
+unsafe impl IoBufMut for TrackedBuf {
+    fn as_buf_mut_ptr(&mut self) -> *mut u8 {
+        self.data.as_buf_mut_ptr()
+    }
+
+    fn set_buf_init(&mut self, pos: usize) {
+        self.data.set_buf_init(pos);
+    }
+}
+
+/// Spawns a task receiving from a socket without any sender.
+async fn spawn_recv(ref_cnt: &Arc<()>) {
+    let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let buf = TrackedBuf::new(ref_cnt);
+    tokio_iocp::spawn(async move { socket.recv(buf).await });
+    tokio::task::yield_now().await;
+}
+
+#[test]
+fn shutdown_timeout() {
+    let ref_cnt = Arc::new(());
+    let runtime = Runtime::new().unwrap();
+    runtime.block_on(spawn_recv(&ref_cnt));
+    assert_eq!(Arc::strong_count(&ref_cnt), 2);
+
+    assert_eq!(runtime.shutdown_timeout(Duration::from_secs(1)), 0);
+    assert_eq!(Arc::strong_count(&ref_cnt), 1);
+}
+
+#[test]
+fn shutdown_on_drop() {
+    let ref_cnt = Arc::new(());
+    let runtime = Runtime::new().unwrap();
+    runtime.block_on(async {
+        spawn_recv(&ref_cnt).await;
+        // The future is dropped, and the operation is still in flight.
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        tokio::time::timeout(
+            Duration::from_millis(10),
+            socket.recv(TrackedBuf::new(&ref_cnt)),
+        )
+        .await
+        .unwrap_err();
+    });
+
+    drop(runtime);
+    assert_eq!(Arc::strong_count(&ref_cnt), 1);
+}
+
+#[test]
+fn shutdown_workers() {
+    let ref_cnt = Arc::new(());
+    let runtime = Runtime::builder().worker_threads(2).build().unwrap();
+    let handle = runtime.handle();
+    runtime.block_on(async {
+        for worker in 0..2 {
+            let ref_cnt = ref_cnt.clone();
+            handle
+                .spawn_on(worker, move || async move { spawn_recv(&ref_cnt).await })
+                .await
+                .unwrap();
+        }
+    });
+    assert_eq!(Arc::strong_count(&ref_cnt), 3);
+
+    assert_eq!(runtime.shutdown_timeout(Duration::from_secs(1)), 0);
+    assert_eq!(Arc::strong_count(&ref_cnt), 1);
+}
+
+#[test]
+fn shutdown_sim() {
+    let ref_cnt = Arc::new(());
+    let runtime = Runtime::builder().sim(0).build().unwrap();
+    runtime.block_on(spawn_recv(&ref_cnt));
+    assert_eq!(Arc::strong_count(&ref_cnt), 2);
+
+    // The queued operation is completed with an error.
+    assert_eq!(runtime.shutdown_timeout(Duration::ZERO), 0);
+    assert_eq!(Arc::strong_count(&ref_cnt), 1);
+}
@@ -0,0 +1,176 @@
+use std::{net::Ipv4Addr, sync::Arc};
+use tokio_iocp::{
+    buf::*,
+    fs::File,
+    net::UdpSocket,
+    runtime::{
+        sim::{self, OpKind},
+        Runtime,
+    },
+};
+
+#[test]
+fn complete_in_order() {
+    let runtime = Runtime::builder().sim(0).build().unwrap();
+    runtime.block_on(async {
+        let file = std::rc::Rc::new(File::open("Cargo.toml").unwrap());
+        let read = tokio_iocp::spawn({
+            let file = file.clone();
+            async move { file.read_at(Vec::with_capacity(8), 0).await }
+        });
+        let write = tokio_iocp::spawn({
+            let file = file.clone();
+            async move { file.write_at("hello", 0).await }
+        });
+        tokio::task::yield_now().await;
+
+        let ops = sim::pending();
+        assert_eq!(
+            ops.iter().map(|op| op.kind()).collect::<Vec<_>>(),
+            [OpKind::Read, OpKind::Write]
+        );
+        assert_eq!(sim::data(ops[1].id()).unwrap(), b"hello");
+
+        // Complete the write first, with a partial result.
+        sim::complete(ops[1].id(), Ok(3)).unwrap();
+        let (res, _) = write.await.unwrap();
+        assert_eq!(res.unwrap(), 3);
+        assert!(!read.is_finished());
+
+        // The buffer is not large enough for all data.
+        assert_eq!(sim::complete_with(ops[0].id(), b"0123456789").unwrap(), 8);
+        let (res, buf) = read.await.unwrap();
+        assert_eq!(res.unwrap(), 8);
+        assert_eq!(buf, b"01234567");
+
+        assert!(sim::pending().is_empty());
+        assert!(sim::complete(ops[0].id(), Ok(0)).is_err());
+    });
+}
+
+#[test]
+fn complete_with_error() {
+    let runtime = Runtime::builder().sim(0).build().unwrap();
+    runtime.block_on(async {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let recv = tokio_iocp::spawn(async move { socket.recv(Vec::with_capacity(8)).await });
+        tokio::task::yield_now().await;
+
+        let op = sim::pending().pop().unwrap();
+        assert_eq!(op.kind(), OpKind::Recv);
+        // Larger than the buffer.
+        assert!(sim::complete(op.id(), Ok(9)).is_err());
+        sim::complete(op.id(), Err(std::io::ErrorKind::ConnectionReset.into())).unwrap();
+        let (res, buf) = recv.await.unwrap();
+        assert_eq!(res.unwrap_err().kind(), std::io::ErrorKind::ConnectionReset);
+        assert!(buf.is_empty());
+    });
+}
+
+#[test]
+fn cancel_returns_buffer() {
+    struct MyBuf {
+        data: Vec<u8>,
+        _ref_cnt: Arc<()>,
+    }
+
+    unsafe impl IoBuf for MyBuf {
+        fn as_buf_ptr(&self) -> *const u8 {
+            self.data.as_buf_ptr()
+        }
+
+        fn buf_len(&self) -> usize {
+            self.data.buf_len()
+        }
+
+        fn buf_capacity(&self) -> usize {
+            self.data.buf_capacity()
+        }
+    }
+
+    unsafe impl IoBufMut for MyBuf {
+        fn as_buf_mut_ptr(&mut self) -> *mut u8 {
+            self.data.as_buf_mut_ptr()
+        }
+
+        fn set_buf_init(&mut self, pos: usize) {
+            self.data.set_buf_init(pos);
+        }
+    }
+
+    let ref_cnt = Arc::new(());
+
+    let runtime = Runtime::builder().sim(0).build().unwrap();
+    runtime.block_on(async {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let buf = MyBuf {
+            data: Vec::with_capacity(8),
+            _ref_cnt: ref_cnt.clone(),
+        };
+        let recv = tokio_iocp::spawn(async move { socket.recv(buf).await });
+        tokio::task::yield_now().await;
+        recv.abort();
+        assert!(recv.await.is_err_and(|e| e.is_cancelled()));
+
+        // The buffer is held by the driver until the operation completes.
+        let op = sim::pending().pop().unwrap();
+        assert!(op.is_cancelled());
+        assert_eq!(Arc::strong_count(&ref_cnt), 2);
+
+        sim::complete(op.id(), Ok(8)).unwrap();
+        assert_eq!(Arc::strong_count(&ref_cnt), 1);
+    });
+}
+
+#[test]
+fn reproducible_order() {
+    fn order(seed: u64) -> Vec<usize> {
+        let runtime = Runtime::builder().sim(seed).build().unwrap();
+        runtime.block_on(async {
+            let socket = std::rc::Rc::new(UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap());
+            let sends = (0..8)
+                .map(|i| {
+                    let socket = socket.clone();
+                    tokio_iocp::spawn(async move { socket.send(vec![0; i + 1]).await })
+                })
+                .collect::<Vec<_>>();
+            tokio::task::yield_now().await;
+
+            let mut order = vec![];
+            while let Some(op) = sim::pick() {
+                let len = sim::data(op.id()).unwrap().len();
+                sim::complete(op.id(), Ok(len)).unwrap();
+                order.push(len);
+            }
+            for send in sends {
+                send.await.unwrap().0.unwrap();
+            }
+            order
+        })
+    }
+
+    let first = order(1);
+    assert_eq!(first.len(), 8);
+    assert_eq!(first, order(1));
+    assert_ne!(first, order(2));
+}
+
+#[test]
+fn sim_on_workers() {
+    let runtime = Runtime::builder().worker_threads(1).sim(0).build().unwrap();
+    let handle = runtime.handle();
+    runtime.block_on(async move {
+        let res = handle
+            .spawn_on(0, || async {
+                let file = File::open("Cargo.toml").unwrap();
+                let read = tokio_iocp::spawn(async move { file.read_at(vec![], 0).await });
+                tokio::task::yield_now().await;
+                let op = sim::pick().unwrap();
+                sim::complete(op.id(), Err(std::io::ErrorKind::Other.into())).unwrap();
+                read.await.unwrap().0
+            })
+            .await
+            .unwrap();
+        assert_eq!(res.unwrap_err().kind(), std::io::ErrorKind::Other);
+    });
+}
@@ -0,0 +1,143 @@
+use std::{
+    io::ErrorKind,
+    time::{Duration, Instant},
+};
+use tokio_iocp::{
+    cancel::CancelExt,
+    net::UdpSocket,
+    runtime::{ParkPolicy, Runtime},
+    time,
+};
+
+#[test]
+fn sleep() {
+    tokio_iocp::start(async {
+        let start = Instant::now();
+        let (a, b) = tokio::join!(
+            time::sleep(Duration::from_millis(20)),
+            time::sleep(Duration::from_millis(10)),
+        );
+        assert_eq!((a, b), ((), ()));
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(20));
+        assert!(elapsed < Duration::from_secs(1), "{elapsed:?}");
+    });
+}
+
+#[test]
+fn without_tokio_time() {
+    let runtime = Runtime::builder().enable_time(false).build().unwrap();
+    runtime.block_on(async {
+        let start = Instant::now();
+        time::sleep(Duration::from_millis(10)).await;
+        assert!(start.elapsed() >= Duration::from_millis(10));
+
+        // The operations time out with the timer of the driver.
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let (res, _) = socket
+            .recv(Vec::with_capacity(64))
+            .timeout(Duration::from_millis(10))
+            .await;
+        assert_eq!(res.unwrap_err().kind(), ErrorKind::TimedOut);
+    });
+}
+
+#[test]
+fn wakes_blocking_wait() {
+    let runtime = Runtime::builder()
+        .enable_io(false)
+        .enable_time(false)
+        .park_policy(ParkPolicy::Timeout(Duration::from_secs(10)))
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        let start = Instant::now();
+        time::sleep(Duration::from_millis(10)).await;
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(10));
+        assert!(elapsed < Duration::from_secs(5), "{elapsed:?}");
+    });
+}
+
+#[test]
+fn reset_and_drop() {
+    tokio_iocp::start(async {
+        let start = Instant::now();
+        // A dropped timer doesn't delay the later ones.
+        drop(time::sleep(Duration::from_millis(5)));
+        let mut sleep = time::sleep(Duration::from_secs(60));
+        tokio::select! {
+            _ = &mut sleep => unreachable!(),
+            _ = time::sleep(Duration::from_millis(10)) => {}
+        }
+        assert!(!sleep.is_elapsed());
+        sleep.reset(Instant::now() + Duration::from_millis(10));
+        sleep.await;
+        assert!(start.elapsed() < Duration::from_secs(5));
+    });
+}
+
+#[test]
+fn rearm_timer() {
+    tokio_iocp::start(async {
+        // Every sleep is earlier than the previous ones, and replaces the timer
+        // of the driver.
+        let sleeps = (0..64)
+            .rev()
+            .map(|i| tokio_iocp::spawn(time::sleep(Duration::from_millis(200 + i * 10))))
+            .collect::<Vec<_>>();
+        tokio::task::yield_now().await;
+
+        let start = Instant::now();
+        time::sleep(Duration::from_millis(5)).await;
+        assert!(start.elapsed() < Duration::from_millis(100));
+        sleeps.into_iter().for_each(|sleep| sleep.abort());
+    });
+}
+
+#[test]
+fn interval() {
+    tokio_iocp::start(async {
+        let period = Duration::from_millis(10);
+        let mut interval = time::interval(period);
+        let start = interval.tick().await;
+        assert_eq!(interval.tick().await, start + period);
+        assert_eq!(interval.tick().await, start + period * 2);
+
+        // The missed ticks are skipped.
+        std::thread::sleep(period * 3);
+        let tick = interval.tick().await;
+        assert_eq!(tick, start + period * 3);
+        let next = interval.tick().await;
+        assert!(next >= start + period * 5);
+        assert_eq!((next - start).as_nanos() % period.as_nanos(), 0);
+    });
+}
+
+#[test]
+fn interval_many_missed() {
+    tokio_iocp::start(async {
+        // More ticks are missed than `u32::MAX`.
+        let now = Instant::now();
+        let start = now.checked_sub(Duration::from_secs(10)).unwrap();
+        let mut interval = time::interval_at(start, Duration::from_nanos(1));
+        assert_eq!(interval.tick().await, start);
+        assert!(interval.tick().await > now);
+
+        // The next tick is not representable.
+        let mut interval = time::interval(Duration::MAX);
+        interval.tick().await;
+        let next = tokio::time::timeout(Duration::from_millis(10), interval.tick());
+        next.await.unwrap_err();
+    });
+}
+
+#[test]
+fn sleep_with_sim() {
+    let runtime = Runtime::builder().sim(0).build().unwrap();
+    runtime.block_on(async {
+        let start = Instant::now();
+        time::sleep(Duration::from_millis(10)).await;
+        assert!(start.elapsed() >= Duration::from_millis(10));
+    });
+}
@@ -0,0 +1,134 @@
+#![cfg(feature = "tracing")]
+
+use std::{
+    collections::HashMap,
+    io::ErrorKind,
+    net::Ipv4Addr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio_iocp::{
+    fs::File,
+    net::UdpSocket,
+    runtime::{
+        fault::{Fault, FaultPolicy},
+        Runtime,
+    },
+};
+use tracing::{
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+    Event, Metadata, Subscriber,
+};
+
+#[derive(Debug, Default)]
+struct Fields(HashMap<&'static str, String>);
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0.insert(field.name(), format!("{value:?}"));
+    }
+}
+
+/// Records the fields of all spans.
+#[derive(Clone, Default)]
+struct Recorder {
+    spans: Arc<Mutex<Vec<Fields>>>,
+}
+
+impl Recorder {
+    fn field(&self, name: &str) -> Option<String> {
+        let spans = self.spans.lock().unwrap();
+        assert_eq!(spans.len(), 1);
+        spans[0].0.get(name).cloned()
+    }
+}
+
+impl Subscriber for Recorder {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.target().starts_with("tokio_iocp")
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let mut fields = Fields::default();
+        span.record(&mut fields);
+        let mut spans = self.spans.lock().unwrap();
+        spans.push(fields);
+        Id::from_u64(spans.len() as u64)
+    }
+
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        let mut spans = self.spans.lock().unwrap();
+        values.record(&mut spans[span.into_u64() as usize - 1]);
+    }
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, _event: &Event<'_>) {}
+
+    fn enter(&self, _span: &Id) {}
+
+    fn exit(&self, _span: &Id) {}
+}
+
+#[test]
+fn completed() {
+    let recorder = Recorder::default();
+    tracing::subscriber::with_default(recorder.clone(), || {
+        Runtime::new().unwrap().block_on(async {
+            let file = File::open("Cargo.toml").unwrap();
+            file.read_at(Vec::with_capacity(64), 8).await.0.unwrap();
+        })
+    });
+    assert_eq!(recorder.field("kind").unwrap(), "Read");
+    assert_eq!(recorder.field("len").unwrap(), "64");
+    assert_eq!(recorder.field("offset").unwrap(), "8");
+    assert_eq!(recorder.field("bytes").unwrap(), "64");
+    assert!(recorder.field("latency").is_some());
+    assert!(recorder.field("error").is_none());
+    assert_eq!(recorder.field("cancelled").unwrap(), "false");
+}
+
+#[test]
+fn failed() {
+    let recorder = Recorder::default();
+    let policy = FaultPolicy::new().with(Fault::error(ErrorKind::TimedOut));
+    tracing::subscriber::with_default(recorder.clone(), || {
+        Runtime::builder()
+            .fault_policy(policy)
+            .build()
+            .unwrap()
+            .block_on(async {
+                let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+                socket.send(vec![0; 8]).await.0.unwrap_err();
+            })
+    });
+    assert_eq!(recorder.field("kind").unwrap(), "Send");
+    assert_eq!(recorder.field("len").unwrap(), "8");
+    assert!(recorder.field("offset").is_none());
+    assert!(recorder.field("bytes").is_none());
+    assert_eq!(
+        recorder.field("error").unwrap(),
+        std::io::Error::from(ErrorKind::TimedOut).to_string()
+    );
+}
+
+#[test]
+fn cancelled() {
+    let recorder = Recorder::default();
+    tracing::subscriber::with_default(recorder.clone(), || {
+        Runtime::new().unwrap().block_on(async {
+            let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+            tokio::time::timeout(
+                Duration::from_millis(10),
+                socket.recv(Vec::with_capacity(8)),
+            )
+            .await
+            .unwrap_err();
+        })
+    });
+    assert_eq!(recorder.field("kind").unwrap(), "Recv");
+    assert!(recorder.field("bytes").is_none());
+    assert!(recorder.field("latency").is_none());
+    assert_eq!(recorder.field("cancelled").unwrap(), "true");
+}